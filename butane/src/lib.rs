@@ -1,12 +1,13 @@
-pub use butane_codegen::{butane_type, dataresult, model};
+pub use butane_codegen::{butane_type, dataresult, model, FieldType};
 pub use butane_core::custom;
 pub use butane_core::fkey::ForeignKey;
+pub use butane_core::json::{self, Json};
 pub use butane_core::many::Many;
 pub use butane_core::migrations;
 pub use butane_core::query;
 pub use butane_core::{
-    AsPrimaryKey, DataObject, DataResult, Error, FieldType, FromSql, ObjectState, Result, SqlType,
-    SqlVal, SqlValRef, ToSql,
+    ArrayElement, AsPrimaryKey, DataObject, DataResult, Error, FieldType, FromSql, ObjectState,
+    Result, SqlType, SqlVal, SqlValRef, ToSql,
 };
 
 pub mod db {
@@ -31,10 +32,61 @@ pub mod db {
 /// To refer to values from the surrounding rust function, enclose
 /// them in braces, like `filter!(Foo, bar == {bar})`
 ///
+/// # Named parameters
+/// To compare against a value supplied only when the query is run,
+/// use `param!(name)`, like `filter!(Foo, bar == param!(bar))`. Such
+/// a query must be turned into a [`PreparedQuery`] with
+/// [`Query::prepare`] before it is executed.
+///
+/// # Operators
+/// Comparisons (`==`, `!=`, `<`, `>`, `<=`, `>=`) between a field and a
+/// value may be combined with `&&`, `||` and `!`, and grouped with
+/// parentheses.
+///
+/// Either side of a comparison may also refer to fields, and fields
+/// and values may be combined with the arithmetic operators `+`, `-`,
+/// `*`, `/` and `%`, e.g. `filter!(Foo, bar > baz)` or
+/// `filter!(Foo, bar + baz > 100)`. Both sides must then be of the
+/// same type; `param!` is not supported in such comparisons.
+///
+/// # SQL functions
+/// Some SQL functions may be applied to fields (and values) with
+/// method syntax, and the result compared, e.g.
+/// `filter!(Foo, bar.lower() == "x")`.
+/// * `lower`, `upper`: converts text to lowercase or uppercase.
+/// * `length`: the length of text, in characters.
+/// * `abs`: the absolute value of a number.
+/// * `coalesce`: for nullable fields, the value or the given default
+///   where it is null, e.g. `bar.coalesce(0) > 5`.
+/// * `extract`: a part of a timestamp as an integer, e.g.
+///   `created.extract("month") == 3`. The part is one of `"year"`,
+///   `"month"`, `"day"`, `"hour"`, `"minute"` or `"second"`.
+/// * `date_trunc`: a timestamp truncated to the start of the given
+///   part, e.g. `created.date_trunc("day")`.
+///
 /// # Function-like operations
 /// Filters support some operations for which Rust does not have operators and which are instead
 /// represented syntactically as function calls.
 /// * `like`: parameter is a SQL LIKE expression string, e.g. `title.like("M%").
+/// * `ilike`: case-insensitive version of `like`. Uses ILIKE on
+///   Postgres and compares lowercased values on other backends.
+/// * `is_in`: parameter is a list of values, e.g. `rank.is_in([1, 2, 3])`,
+///   or a Rust value in braces which can be iterated over.
+/// * `between`: takes two parameters, the inclusive lower and upper bounds,
+///   e.g. `rank.between(1, 10)`.
+/// * `is_none`, `is_some`: for nullable fields, test whether the
+///   value is (or is not) SQL NULL.
+/// * `path`: for fields stored as JSON (with [`Json`] or
+///   `#[butane_type(Json)]`), the value at a path within the
+///   document, which may be compared with a value, e.g.
+///   `settings.path("font.size") > 12`. The path is a sequence of
+///   object keys and array indices separated by dots. Values are
+///   compared as JSON, so numbers compare numerically and values of
+///   different types are never equal.
+/// * `matches_text`: full-text search of a field marked
+///   `#[fulltext]`, e.g. `body.matches_text("tiger")`. The parameter
+///   may contain several words, which must all match, "quoted
+///   phrases" and `OR`. Words are stemmed, so "tigers" also matches.
 /// * `matches`: Parmeter is a sub-expression. Use with a
 ///   [`ForeignKey`] field to evaluate as true if the referent
 ///   matches. For example, to find all posts made in blogs by people
@@ -46,7 +98,14 @@ pub mod db {
 ///    `tags: Many<Tag>` we could filter to posts with a "cats" with
 ///    the following `tags.contains(tag == "cats"). If the expression
 ///    is single literal, it is assumed to be used to match the
-///    primary key.
+///    primary key. Negate it, as in `!tags.contains(tag == "cats")`,
+///    to match when none of the referents match.
+///
+///   `contains` may also follow a [`ForeignKey`] backwards, written
+///   as `Type::field.contains(expr)`, where `field` is a foreign key
+///   of `Type` referring to the model being filtered. For example, to
+///   find all blogs with at least one published post we might say
+///   `filter!(Blog, Post::blog.contains(published == true))`.
 ///
 /// # Examples
 /// ```
@@ -58,16 +117,22 @@ pub mod db {
 ///   #[pk]
 ///   name: String,
 ///   rank: i32,
+///   best_rank: i32,
 ///   nationality: String
 /// }
 /// let e: BoolExpr = filter!(Contestant, nationality == "US" && rank < 42);
 /// let firstplace = 1;
 /// let e2 = filter!(Contestant, rank == { firstplace });
 /// let e3 = filter!(Contestant, name.like("A%"));
+/// let e4 = filter!(Contestant, !(rank.between(1, 3) || nationality.is_in(["US", "CA"])));
+/// let e5 = filter!(Contestant, rank * 2 > best_rank + 10);
+/// let e6 = filter!(Contestant, nationality.lower() == "us");
 ///```
 ///
 /// [`BoolExpr`]: crate::query::BoolExpr
 /// [`Query`]: crate::query::Query
+/// [`Query::prepare`]: crate::query::Query::prepare
+/// [`PreparedQuery`]: crate::query::PreparedQuery
 pub use butane_codegen::filter;
 
 /// Constructs a filtered database query.
@@ -101,6 +166,49 @@ macro_rules! query {
     };
 }
 
+/// Constructs an [`Order`] for use with [`Query::order_with`].
+///
+/// Use as `order!(Foo, expr)`, where `Foo` is a model type and `expr`
+/// is a field of `Foo`, optionally followed by `asc` (the default) or
+/// `desc`, and by `nulls first` or `nulls last` to control where null
+/// values are placed. As with [`colname`], field names are checked
+/// at compile time. `expr` may also be
+/// * a field of the object referred to by a [`ForeignKey`] field,
+///   like `blog.name`, which is looked up with a join.
+/// * an expression using arithmetic or SQL functions as described
+///   for [`filter`], like `title.length()` or `likes + shares`.
+/// * `field.text_rank("query")` for a field marked `#[fulltext]`,
+///   the relevance of the field to a full-text search, for ordering
+///   the results of `matches_text` with `desc`.
+///
+/// Where null values are placed by default depends on the
+/// backend. Backends without native support for `NULLS FIRST` and
+/// `NULLS LAST`, such as SQLite, emulate them.
+///
+/// # Examples
+/// ```
+/// # use butane::query::*;
+/// # use butane_codegen::model;
+/// # use butane::{order, query};
+/// # use butane::prelude::*;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: Option<i32>,
+///   nationality: String
+/// }
+/// let ranked: Query<Contestant> = Contestant::query()
+///     .order_with(order!(Contestant, rank desc nulls last))
+///     .order_with(order!(Contestant, name.lower()));
+///```
+///
+/// [`Order`]: crate::query::Order
+/// [`Query::order_with`]: crate::query::Query::order_with
+/// [`colname`]: crate::colname
+/// [`filter`]: crate::filter
+pub use butane_codegen::order;
+
 /// Typesafe way to refer to a column name. Use as
 /// `colname!(MODEL_TYPE, FIELD_NAME)`. E.g. For a model type `Foo`
 /// with a field `bar`, `colname!(Foo, bar) would return `"bar"`, but
@@ -164,6 +272,8 @@ pub mod prelude {
     //! of butane's macros may require some of its re-exports to be
     //! used manually.
     #[doc(no_inline)]
+    pub use crate::query::FallibleIterator;
+    #[doc(no_inline)]
     pub use crate::DataObject;
     #[doc(no_inline)]
     pub use crate::DataResult;
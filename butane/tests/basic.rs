@@ -323,3 +323,23 @@ fn fkey_same_type(conn: Connection) {
     assert!(inner.reference.is_none());
 }
 testall!(fkey_same_type);
+
+fn fkey_same_type_backref(conn: Connection) {
+    let mut o1 = SelfReferential::new(1);
+    let mut o2 = SelfReferential::new(2);
+    SelfReferential::new(3).save(&conn).unwrap();
+    o2.save(&conn).unwrap();
+    o1.reference = Some(ForeignKey::from_pk(o2.id));
+    o1.save(&conn).unwrap();
+
+    // Objects referred to by object 1, rather than object 1 itself
+    let referred = query!(
+        SelfReferential,
+        SelfReferential::reference.contains(id == 1)
+    )
+    .load(&conn)
+    .unwrap();
+    assert_eq!(referred.len(), 1);
+    assert_eq!(referred[0].id, 2);
+}
+testall!(fkey_same_type_backref);
@@ -24,6 +24,7 @@ impl Blog {
 pub struct Post {
     pub id: i64,
     pub title: String,
+    #[fulltext]
     pub body: String,
     pub published: bool,
     pub pub_time: Option<NaiveDateTime>,
@@ -0,0 +1,145 @@
+use butane::custom::{SqlValRefCustom, SqliteValue};
+use butane::db::Connection;
+use butane::prelude::*;
+use butane::{butane_type, model, ObjectState};
+use butane::{FieldType, FromSql, SqlType, SqlVal, SqlValRef, ToSql};
+use std::result::Result;
+
+mod common;
+
+// Stored natively as a POINT with Postgres and as "x,y" text with Sqlite.
+#[butane_type(Custom(POINT, sqlite = Text))]
+#[derive(Debug, PartialEq, Clone)]
+struct Location(geo_types::Point<f64>);
+impl Location {
+    fn new(x: f64, y: f64) -> Self {
+        Location(geo_types::Point::<f64>::new(x, y))
+    }
+    fn to_text(&self) -> String {
+        format!("{},{}", self.0.x(), self.0.y())
+    }
+    fn from_text(text: &str) -> Option<Self> {
+        let (x, y) = text.split_once(',')?;
+        Some(Location::new(x.parse().ok()?, y.parse().ok()?))
+    }
+}
+
+impl ToSql for Location {
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        #[cfg(feature = "pg")]
+        return SqlValRef::Custom(SqlValRefCustom::Portable {
+            ty: postgres::types::Type::POINT,
+            tosql: &self.0,
+            sqlite: SqliteValue::Text(self.to_text()),
+        });
+        #[cfg(not(feature = "pg"))]
+        SqlValRef::Custom(SqlValRefCustom::Sqlite(SqliteValue::Text(self.to_text())))
+    }
+    fn to_sql(&self) -> SqlVal {
+        self.to_sql_ref().into()
+    }
+}
+
+impl FromSql for Location {
+    fn from_sql_ref(val: SqlValRef) -> Result<Self, butane::Error> {
+        match val {
+            #[cfg(feature = "pg")]
+            SqlValRef::Custom(SqlValRefCustom::PgBytes { ty, data }) => {
+                Ok(Location(postgres::types::FromSql::from_sql(&ty, data)?))
+            }
+            SqlValRef::Custom(SqlValRefCustom::Sqlite(SqliteValue::Text(ref text))) => {
+                Location::from_text(text).ok_or_else(|| {
+                    butane::Error::CannotConvertSqlVal(Location::SQLTYPE, val.clone().into())
+                })
+            }
+            _ => Err(butane::Error::CannotConvertSqlVal(
+                Location::SQLTYPE,
+                val.into(),
+            )),
+        }
+    }
+}
+
+impl FieldType for Location {
+    #[cfg(feature = "pg")]
+    const SQLTYPE: SqlType = SqlType::Custom(butane::custom::SqlTypeCustom::Portable {
+        pg: postgres::types::Type::POINT,
+        sqlite: butane::custom::SqliteAffinity::Text,
+    });
+    #[cfg(not(feature = "pg"))]
+    const SQLTYPE: SqlType = SqlType::Custom(butane::custom::SqlTypeCustom::Sqlite(
+        butane::custom::SqliteAffinity::Text,
+    ));
+    type RefType = Self;
+}
+
+#[model]
+#[derive(Debug, PartialEq)]
+struct Landmark {
+    id: i64,
+    name: String,
+    location: Location,
+    entrance: Option<Location>,
+}
+
+fn roundtrip_portable_custom(conn: Connection) {
+    let mut landmark = Landmark {
+        id: 1,
+        name: "tower".to_string(),
+        location: Location::new(2.2945, 48.8584),
+        entrance: None,
+        state: ObjectState::default(),
+    };
+    landmark.save(&conn).unwrap();
+    assert_eq!(Landmark::get(&conn, 1).unwrap(), landmark);
+
+    landmark.location = Location::new(-0.5, 12.0);
+    landmark.entrance = Some(Location::new(-0.25, 12.5));
+    landmark.save(&conn).unwrap();
+    assert_eq!(Landmark::get(&conn, 1).unwrap(), landmark);
+}
+testall!(roundtrip_portable_custom);
+
+#[cfg(feature = "pg")]
+#[test]
+fn portable_custom_serialization_error() {
+    // Text cannot be written as the declared Postgres type
+    let text = "not a point";
+    let valref = SqlValRef::Custom(SqlValRefCustom::Portable {
+        ty: postgres::types::Type::POINT,
+        tosql: &text,
+        sqlite: SqliteValue::Text(text.to_string()),
+    });
+    assert!(valref.try_into_owned().is_err());
+    assert!(Location::new(1.0, 2.0)
+        .to_sql_ref()
+        .try_into_owned()
+        .is_ok());
+}
+
+#[cfg(all(feature = "pg", feature = "sqlite"))]
+#[test]
+fn pg_custom_on_sqlite_is_error() {
+    use butane::custom::{SqlTypeCustom, SqlValCustom};
+    use butane::db::{Backend, ConnectionMethods};
+    use butane::migrations::adb::{AColumn, ATable, DeferredSqlType, Operation, TypeIdentifier};
+
+    let conn = common::sqlite_connection();
+    let point = SqlVal::Custom(Box::new(SqlValCustom::Pg {
+        ty: postgres::types::Type::POINT,
+        data: Vec::new(),
+    }));
+    assert!(conn.raw_execute("SELECT ?", &[point]).is_err());
+
+    let mut table = ATable::new("PgOnly".to_string());
+    table.add_column(AColumn::new_simple(
+        "location",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Custom(SqlTypeCustom::Pg(
+            postgres::types::Type::POINT,
+        )))),
+    ));
+    let sqlite = butane::db::get_backend("sqlite").unwrap();
+    assert!(sqlite
+        .create_migration_sql(&Default::default(), vec![Operation::AddTable(table)])
+        .is_err());
+}
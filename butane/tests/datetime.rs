@@ -0,0 +1,101 @@
+use butane::db::{Connection, ConnectionMethods};
+use butane::prelude::*;
+use butane::{model, query, ObjectState};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Appointment {
+    id: i64,
+    day: NaiveDate,
+    time: NaiveTime,
+    starts: DateTime<Utc>,
+    local_start: DateTime<FixedOffset>,
+    length: chrono::Duration,
+    cancelled: Option<DateTime<Utc>>,
+}
+impl Appointment {
+    fn new(id: i64, starts: DateTime<Utc>, length: Duration) -> Self {
+        Appointment {
+            id,
+            day: starts.date_naive(),
+            time: starts.time(),
+            starts,
+            local_start: starts.with_timezone(&FixedOffset::east(2 * 3600)),
+            length,
+            cancelled: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn roundtrip_datetime(conn: Connection) {
+    let mut appt = Appointment::new(
+        1,
+        Utc.ymd(2022, 5, 17).and_hms_micro(9, 30, 15, 250_000),
+        Duration::minutes(45) + Duration::microseconds(7),
+    );
+    appt.cancelled = Some(Utc.ymd(2022, 5, 16).and_hms(18, 0, 0));
+    appt.save(&conn).unwrap();
+
+    let appt2 = Appointment::get(&conn, 1).unwrap();
+    assert_eq!(appt, appt2);
+    // Timestamps with an offset are stored in UTC
+    assert_eq!(appt2.local_start.offset().local_minus_utc(), 0);
+    assert_eq!(appt2.local_start, appt.starts);
+}
+testall!(roundtrip_datetime);
+
+fn query_datetime(conn: Connection) {
+    let start = Utc.ymd(2022, 5, 17).and_hms(9, 0, 0);
+    Appointment::new(1, start, Duration::minutes(30))
+        .save(&conn)
+        .unwrap();
+    Appointment::new(2, start + Duration::hours(3), Duration::hours(2))
+        .save(&conn)
+        .unwrap();
+    Appointment::new(3, start + Duration::days(1), Duration::minutes(90))
+        .save(&conn)
+        .unwrap();
+
+    let ids = |q: butane::query::Query<Appointment>| -> Vec<i64> {
+        let mut ids: Vec<i64> = q.load(&conn).unwrap().iter().map(|a| a.id).collect();
+        ids.sort();
+        ids
+    };
+    let day = NaiveDate::from_ymd(2022, 5, 17);
+    assert_eq!(ids(query!(Appointment, day == { day })), [1, 2]);
+    assert_eq!(ids(query!(Appointment, day > { day })), [3]);
+    let noon = NaiveTime::from_hms(12, 0, 0);
+    assert_eq!(ids(query!(Appointment, time >= { noon })), [2]);
+    let cutoff = start + Duration::hours(1);
+    assert_eq!(ids(query!(Appointment, starts > { cutoff })), [2, 3]);
+    let local_cutoff = cutoff.with_timezone(&FixedOffset::west(5 * 3600));
+    assert_eq!(
+        ids(query!(Appointment, local_start < { local_cutoff })),
+        [1]
+    );
+    let hour = Duration::hours(1);
+    assert_eq!(ids(query!(Appointment, length > { hour })), [2, 3]);
+    assert_eq!(ids(query!(Appointment, cancelled.is_none())), [1, 2, 3]);
+}
+testall!(query_datetime);
+
+fn interval_with_months(conn: Connection) {
+    Appointment::new(1, Utc.ymd(2022, 5, 17).and_hms(9, 0, 0), Duration::hours(1))
+        .save(&conn)
+        .unwrap();
+    if conn.backend_name() == "pg" {
+        // A month has no fixed length
+        conn.raw_execute("UPDATE Appointment SET length = '1 month'", &[])
+            .unwrap();
+        assert!(Appointment::get(&conn, 1).is_err());
+        conn.raw_execute("UPDATE Appointment SET length = '1 day 2 hours'", &[])
+            .unwrap();
+        let appt = Appointment::get(&conn, 1).unwrap();
+        assert_eq!(appt.length, Duration::hours(26));
+    }
+}
+testall!(interval_with_months);
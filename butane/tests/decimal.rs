@@ -0,0 +1,83 @@
+#![cfg(feature = "decimal")]
+use butane::db::Connection;
+use butane::prelude::*;
+use butane::{colname, model, query, ObjectState};
+use rust_decimal_for_test::Decimal;
+use std::str::FromStr;
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Invoice {
+    id: i64,
+    #[numeric(precision = 12, scale = 2)]
+    total: Decimal,
+    rate: Decimal,
+    #[numeric(precision = 8, scale = 3)]
+    discount: Option<Decimal>,
+}
+impl Invoice {
+    fn new(id: i64, total: &str) -> Self {
+        Invoice {
+            id,
+            total: dec(total),
+            rate: Decimal::ZERO,
+            discount: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn dec(s: &str) -> Decimal {
+    Decimal::from_str(s).unwrap()
+}
+
+fn roundtrip_decimal(conn: Connection) {
+    let mut invoice = Invoice::new(1, "-1234567890.25");
+    invoice.rate = dec("79228162514264337593.543950335");
+    invoice.discount = Some(dec("0.125"));
+    invoice.save(&conn).unwrap();
+    let invoice2 = Invoice::get(&conn, 1).unwrap();
+    assert_eq!(invoice, invoice2);
+    assert_eq!(invoice2.rate.to_string(), "79228162514264337593.543950335");
+
+    invoice.rate = dec("0.0000000000000000000000000001");
+    invoice.save(&conn).unwrap();
+    assert_eq!(Invoice::get(&conn, 1).unwrap().rate, invoice.rate);
+}
+testall!(roundtrip_decimal);
+
+fn query_decimal(conn: Connection) {
+    let totals = [
+        "-100", "-20.5", "-20.05", "-2", "-0.5", "0", "0.05", "0.5", "2", "9.99", "10", "19.99",
+        "100",
+    ];
+    // Saved out of order, so that the ids give the expected order
+    for (i, total) in totals.iter().enumerate().rev() {
+        Invoice::new(i as i64, total).save(&conn).unwrap();
+    }
+
+    let ids = |q: butane::query::Query<Invoice>| -> Vec<i64> {
+        q.order_asc(colname!(Invoice, total))
+            .load(&conn)
+            .unwrap()
+            .iter()
+            .map(|i| i.id)
+            .collect()
+    };
+    let all: Vec<i64> = (0..totals.len() as i64).collect();
+    assert_eq!(ids(Invoice::query()), all);
+    let ten = dec("10");
+    assert_eq!(ids(query!(Invoice, total >= { ten })), [10, 11, 12]);
+    let limit = dec("-20.05");
+    assert_eq!(ids(query!(Invoice, total < { limit })), [0, 1]);
+    let zero = Decimal::ZERO;
+    assert_eq!(ids(query!(Invoice, total == { zero })), [5]);
+    let (low, high) = (dec("-0.5"), dec("9.990"));
+    assert_eq!(
+        ids(query!(Invoice, total > { low } && total <= { high })),
+        [5, 6, 7, 8, 9]
+    );
+}
+testall!(query_decimal);
@@ -0,0 +1,151 @@
+use butane::db::{Connection, ConnectionMethods};
+use butane::prelude::*;
+use butane::{model, query, FieldType, ObjectState};
+
+mod common;
+
+#[derive(FieldType, PartialEq, Eq, Debug, Clone)]
+enum TicketStatus {
+    Open,
+    #[butane(rename = "in-progress")]
+    InProgress,
+    Closed,
+}
+
+#[derive(FieldType, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+#[butane(int)]
+enum Priority {
+    Low = 1,
+    Medium,
+    High = 10,
+}
+
+#[derive(FieldType, PartialEq, Eq, Debug, Clone)]
+#[butane(pg_enum)]
+enum TicketKind {
+    Bug,
+    Feature,
+    #[butane(rename = "other")]
+    Other,
+}
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Ticket {
+    id: i64,
+    status: TicketStatus,
+    priority: Priority,
+    kind: TicketKind,
+    previous_status: Option<TicketStatus>,
+}
+impl Ticket {
+    fn new(id: i64, status: TicketStatus, priority: Priority, kind: TicketKind) -> Self {
+        Ticket {
+            id,
+            status,
+            priority,
+            kind,
+            previous_status: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn roundtrip_enums(conn: Connection) {
+    let mut ticket = Ticket::new(
+        1,
+        TicketStatus::InProgress,
+        Priority::High,
+        TicketKind::Other,
+    );
+    ticket.previous_status = Some(TicketStatus::Open);
+    ticket.save(&conn).unwrap();
+    let ticket2 = Ticket::get(&conn, 1).unwrap();
+    assert_eq!(ticket, ticket2);
+
+    // Variants are stored by their (possibly renamed) name or discriminant
+    let matched = conn
+        .raw_execute(
+            "UPDATE Ticket SET id = id WHERE status = 'in-progress' AND priority = 10 AND kind = 'other'",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(matched, 1);
+}
+testall!(roundtrip_enums);
+
+fn query_enums(conn: Connection) {
+    Ticket::new(1, TicketStatus::Open, Priority::Low, TicketKind::Bug)
+        .save(&conn)
+        .unwrap();
+    Ticket::new(
+        2,
+        TicketStatus::Closed,
+        Priority::Medium,
+        TicketKind::Feature,
+    )
+    .save(&conn)
+    .unwrap();
+    Ticket::new(3, TicketStatus::InProgress, Priority::High, TicketKind::Bug)
+        .save(&conn)
+        .unwrap();
+
+    let ids = |q: butane::query::Query<Ticket>| -> Vec<i64> {
+        let mut ids: Vec<i64> = q.load(&conn).unwrap().iter().map(|t| t.id).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(
+        ids(query!(Ticket, status == { TicketStatus::InProgress })),
+        [3]
+    );
+    assert_eq!(ids(query!(Ticket, kind == { TicketKind::Bug })), [1, 3]);
+    assert_eq!(
+        ids(query!(
+            Ticket,
+            kind != { TicketKind::Bug } && status != { TicketStatus::Open }
+        )),
+        [2]
+    );
+    // Integer representations compare by discriminant
+    assert_eq!(
+        ids(query!(Ticket, priority >= { Priority::Medium })),
+        [2, 3]
+    );
+}
+testall!(query_enums);
+
+#[derive(FieldType, PartialEq, Eq, Debug, Clone)]
+#[butane(pg_enum)]
+enum HTTPMethod {
+    Get,
+    Post,
+}
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct ApiCall {
+    id: i64,
+    method: HTTPMethod,
+}
+
+fn pg_enum_name(conn: Connection) {
+    let call = ApiCall {
+        id: 1,
+        method: HTTPMethod::Post,
+        state: ObjectState::default(),
+    };
+    let mut saved = call.clone();
+    saved.save(&conn).unwrap();
+    assert_eq!(ApiCall::get(&conn, 1).unwrap(), call);
+    let found = query!(ApiCall, method == { HTTPMethod::Post })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    if conn.backend_name() == "pg" {
+        // A run of capitals in the type name is one word
+        conn.raw_execute("SELECT CAST('Get' AS http_method)", &[])
+            .unwrap();
+    }
+}
+testall!(pg_enum_name);
@@ -0,0 +1,123 @@
+use butane::db::{Connection, ConnectionMethods};
+use butane::prelude::*;
+use butane::{colname, model, query, ObjectState};
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Snowflake {
+    id: u64,
+    hash: u128,
+    delta: i128,
+    shard: i16,
+    parent: Option<u64>,
+}
+impl Snowflake {
+    fn new(id: u64, delta: i128) -> Self {
+        Snowflake {
+            id,
+            hash: 0,
+            delta,
+            shard: 0,
+            parent: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn roundtrip_wide_integers(conn: Connection) {
+    let mut flake = Snowflake::new(u64::MAX, i128::MIN);
+    flake.hash = u128::MAX;
+    flake.shard = i16::MIN;
+    flake.parent = Some(1 << 63);
+    flake.save(&conn).unwrap();
+    assert_eq!(Snowflake::get(&conn, u64::MAX).unwrap(), flake);
+
+    flake.delta = i128::MAX;
+    flake.hash = 10_000_000_000_000_000_000_000_000_000_000_000_000;
+    flake.shard = i16::MAX;
+    flake.parent = Some(0);
+    flake.save(&conn).unwrap();
+    assert_eq!(Snowflake::get(&conn, u64::MAX).unwrap(), flake);
+}
+testall!(roundtrip_wide_integers);
+
+fn query_wide_integers(conn: Connection) {
+    let deltas = [
+        i128::MIN,
+        -(1 << 64),
+        -10_000,
+        -1,
+        0,
+        1,
+        9_999,
+        1 << 64,
+        i128::MAX,
+    ];
+    // Saved out of order, so that the ids give the expected order
+    for (i, delta) in deltas.iter().enumerate().rev() {
+        let mut flake = Snowflake::new(u64::MAX - i as u64, *delta);
+        flake.hash = u128::MAX - i as u128;
+        flake.shard = i as i16 - 4;
+        flake.save(&conn).unwrap();
+    }
+
+    let positions = |q: butane::query::Query<Snowflake>| -> Vec<u64> {
+        q.order_asc(colname!(Snowflake, delta))
+            .load(&conn)
+            .unwrap()
+            .iter()
+            .map(|f| u64::MAX - f.id)
+            .collect()
+    };
+    let all: Vec<u64> = (0..deltas.len() as u64).collect();
+    assert_eq!(positions(Snowflake::query()), all);
+    let zero: i128 = 0;
+    assert_eq!(positions(query!(Snowflake, delta < { zero })), [0, 1, 2, 3]);
+    let big: u128 = u128::MAX - 2;
+    assert_eq!(positions(query!(Snowflake, hash > { big })), [0, 1]);
+    let low_id = u64::MAX - 1;
+    assert_eq!(positions(query!(Snowflake, id >= { low_id })), [0, 1]);
+    let shard: i16 = 2;
+    assert_eq!(positions(query!(Snowflake, shard >= { shard })), [6, 7, 8]);
+}
+testall!(query_wide_integers);
+
+fn wide_integer_out_of_range(conn: Connection) {
+    Snowflake::new(7, 0).save(&conn).unwrap();
+    // One more than i128::MAX, which fits in the column on Postgres
+    let (column, value) = if conn.backend_name() == "pg" {
+        ("delta", "170141183460469231731687303715884105728")
+    } else {
+        ("shard", "40000")
+    };
+    conn.raw_execute(&format!("UPDATE Snowflake SET {} = {}", column, value), &[])
+        .unwrap();
+    assert!(matches!(
+        Snowflake::get(&conn, 7),
+        Err(butane::Error::OutOfRange)
+    ));
+}
+testall!(wide_integer_out_of_range);
+
+fn i16_in_int_column(conn: Connection) {
+    // i16 columns created before SmallInt were INTEGER on Postgres
+    if conn.backend_name() == "pg" {
+        conn.raw_execute("ALTER TABLE Snowflake ALTER COLUMN shard TYPE INTEGER", &[])
+            .unwrap();
+    }
+    let mut flake = Snowflake::new(8, 0);
+    flake.shard = i16::MIN;
+    flake.save(&conn).unwrap();
+    assert_eq!(Snowflake::get(&conn, 8).unwrap(), flake);
+    let shard = i16::MIN;
+    assert_eq!(
+        query!(Snowflake, shard == { shard })
+            .load(&conn)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+testall!(i16_in_int_column);
@@ -0,0 +1,120 @@
+use butane::db::Connection;
+use butane::prelude::*;
+use butane::{butane_type, model, query, Json, ObjectState};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+mod common;
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct Dimensions {
+    width: i32,
+    height: i32,
+    unit: String,
+    tags: Vec<String>,
+}
+
+#[butane_type(Json)]
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+enum Finish {
+    Matte,
+    Gloss { level: i32 },
+}
+
+#[model]
+#[derive(PartialEq, Debug, Clone)]
+struct Painting {
+    id: i64,
+    dimensions: Json<Dimensions>,
+    finish: Finish,
+    notes: Option<Json<HashMap<String, String>>>,
+}
+impl Painting {
+    fn new(id: i64, width: i32, height: i32, unit: &str) -> Self {
+        Painting {
+            id,
+            dimensions: Json(Dimensions {
+                width,
+                height,
+                unit: unit.to_string(),
+                tags: Vec::new(),
+            }),
+            finish: Finish::Matte,
+            notes: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn roundtrip_json(conn: Connection) {
+    let mut painting = Painting::new(1, 30, 40, "cm");
+    painting.dimensions.tags.push("portrait".to_string());
+    painting.finish = Finish::Gloss { level: 2 };
+    let mut notes = HashMap::new();
+    notes.insert("artist".to_string(), "O'Keeffe".to_string());
+    painting.notes = Some(Json(notes));
+    painting.save(&conn).unwrap();
+
+    let mut painting2 = Painting::get(&conn, 1).unwrap();
+    assert_eq!(painting, painting2);
+
+    painting2.dimensions.width = 35;
+    painting2.notes = None;
+    painting2.save(&conn).unwrap();
+    let painting3 = Painting::get(&conn, 1).unwrap();
+    assert_eq!(painting2, painting3);
+}
+testall!(roundtrip_json);
+
+fn query_json_path(conn: Connection) {
+    let mut painting = Painting::new(1, 30, 40, "cm");
+    painting.dimensions.tags.push("portrait".to_string());
+    painting.save(&conn).unwrap();
+    let mut painting = Painting::new(2, 100, 60, "cm");
+    painting.dimensions.tags.push("landscape".to_string());
+    painting.finish = Finish::Gloss { level: 3 };
+    painting.save(&conn).unwrap();
+    Painting::new(3, 12, 16, "in").save(&conn).unwrap();
+
+    let ids = |q: butane::query::Query<Painting>| -> Vec<i64> {
+        let mut ids: Vec<i64> = q.load(&conn).unwrap().iter().map(|p| p.id).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids(query!(Painting, dimensions.path("width") == 30)), [1]);
+    // Numbers compare numerically
+    assert_eq!(ids(query!(Painting, dimensions.path("width") > 20)), [1, 2]);
+    assert_eq!(
+        ids(query!(Painting, dimensions.path("unit") == "cm")),
+        [1, 2]
+    );
+    // Array elements are indexed by number
+    assert_eq!(
+        ids(query!(Painting, dimensions.path("tags.0") == "landscape")),
+        [2]
+    );
+    assert_eq!(
+        ids(query!(
+            Painting,
+            finish.path("Gloss.level") >= 2 && dimensions.path("unit") != "in"
+        )),
+        [2]
+    );
+    // Values of other types are not equal
+    assert!(ids(query!(Painting, dimensions.path("width") == "30")).is_empty());
+    let width = 16;
+    assert_eq!(
+        ids(query!(Painting, dimensions.path("height") == { width })),
+        [3]
+    );
+}
+testall!(query_json_path);
+
+#[test]
+fn json_serialize_error() {
+    // JSON object keys must be strings
+    let mut map: HashMap<(i32, i32), i32> = HashMap::new();
+    map.insert((1, 2), 3);
+    assert!(butane::json::try_to_sql(&map).is_err());
+    assert!(butane::json::try_to_sql(&HashMap::from([("a", 1)])).is_ok());
+}
@@ -1,9 +1,16 @@
+use butane::db::{Connection, ConnectionMethods};
+use butane::migrations::adb::{
+    self, AColumn, ATable, DeferredSqlType, TypeIdentifier, TypeKey, ADB,
+};
+use butane::migrations::lint::{self, Severity};
 use butane::migrations::{
-    adb::DeferredSqlType, adb::TypeIdentifier, adb::TypeKey, MemMigrations, Migration,
-    MigrationMut, Migrations, MigrationsMut,
+    applied_migrations, MemMigrations, Migration, MigrationMut, Migrations, MigrationsMut,
+};
+use butane::query::{BoolExpr, Expr};
+use butane::{prelude::*, Result, SqlType, SqlVal};
+use butane_core::codegen::{
+    butane_type_with_migrations, derive_field_type_with_migrations, model_with_migrations,
 };
-use butane::{db::Connection, prelude::*, SqlType, SqlVal};
-use butane_core::codegen::{butane_type_with_migrations, model_with_migrations};
 use proc_macro2::TokenStream;
 use quote::quote;
 
@@ -391,3 +398,1065 @@ fn migration_delete_table(conn: &mut Connection, expected_up_sql: &str, expected
         m.downgrade(conn).unwrap();
     }
 }
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_history_sqlite() {
+    migration_history(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_history_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_history(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_mark_applied_sqlite() {
+    migration_mark_applied(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_mark_applied_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_mark_applied(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_history_legacy_table_sqlite() {
+    migration_history_legacy_table(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_history_legacy_table_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_history_legacy_table(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_history_same_second_sqlite() {
+    migration_history_same_second(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_history_same_second_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_history_same_second(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_fulltext_sqlite() {
+    migration_fulltext(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_fulltext_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_fulltext(&mut conn);
+}
+
+fn migration_fulltext(conn: &mut Connection) {
+    let backend = conn.backend();
+    let mut ms = MemMigrations::new();
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                #[fulltext]
+                body: String,
+                extra: i32,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "init", None).unwrap());
+    ms.apply_all(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, body, extra) VALUES (1, 'The tiger sleeps', 0);")
+        .unwrap();
+
+    let search = |conn: &Connection, text: &str| -> Result<usize> {
+        let filter = BoolExpr::MatchesText {
+            tbl: Some("Foo".into()),
+            col: "body",
+            query: Expr::Val(text.into()),
+        };
+        let columns = [butane::db::Column::new("id", SqlType::BigInt)];
+        let mut rows = conn.query("Foo", &columns, Some(filter), None, None, None)?;
+        let mut count = 0;
+        while rows.next()?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    };
+    assert_eq!(search(conn, "tigers").unwrap(), 1);
+
+    // Removing another column rewrites the table on sqlite, after
+    // which the index must still be maintained
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                #[fulltext]
+                body: String,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backend, "v2", ms.latest().as_ref())
+        .unwrap());
+    ms.apply_all(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, body) VALUES (2, 'Another tiger');")
+        .unwrap();
+    assert_eq!(search(conn, "tiger").unwrap(), 2);
+
+    // Removing the attribute removes the index
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                body: String,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backend, "v3", ms.latest().as_ref())
+        .unwrap());
+    let v3 = ms.latest().unwrap();
+    let lints = lint::lint(
+        &backend,
+        &adb::diff(
+            &ms.get_migration("v2").unwrap().db().unwrap(),
+            &v3.db().unwrap(),
+        ),
+    );
+    assert!(lints.is_empty(), "{:?}", lints);
+    ms.apply_all(conn).unwrap();
+    assert!(search(conn, "tiger").is_err());
+    conn.execute("INSERT INTO Foo (id, body) VALUES (3, 'A third tiger');")
+        .unwrap();
+
+    v3.downgrade(conn).unwrap();
+    assert_eq!(search(conn, "tiger").unwrap(), 3);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_enum_sqlite() {
+    migration_enum(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_enum_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_enum(&mut conn);
+}
+
+fn migration_enum(conn: &mut Connection) {
+    let backend = conn.backend();
+    let mut ms = MemMigrations::new();
+    let migrate =
+        |conn: &mut Connection, ms: &mut MemMigrations, variants: TokenStream, name: &str| {
+            derive_field_type_with_migrations(
+                quote! {
+                    #[butane(pg_enum)]
+                    enum Mood { #variants }
+                },
+                ms,
+            );
+            model_with_migrations(
+                quote! {
+                    struct Pet {
+                        id: i64,
+                        mood: Mood,
+                    }
+                },
+                ms,
+            );
+            assert!(ms
+                .create_migration(&backend, name, ms.latest().as_ref())
+                .unwrap());
+            let m = ms.latest().unwrap();
+            let lints = lint::lint(
+                &backend,
+                &adb::diff(
+                    &m.migration_from()
+                        .unwrap()
+                        .map(|from| ms.get_migration(&from).unwrap().db().unwrap())
+                        .unwrap_or_default(),
+                    &m.db().unwrap(),
+                ),
+            );
+            ms.apply_all(conn).unwrap();
+            lints
+        };
+    let count = |conn: &Connection, mood: &str| -> usize {
+        conn.raw_execute(
+            &format!("UPDATE Pet SET id = id WHERE mood = '{}'", mood),
+            &[],
+        )
+        .unwrap()
+    };
+
+    migrate(conn, &mut ms, quote!(Happy, Sad), "init");
+    conn.execute("INSERT INTO Pet (id, mood) VALUES (1, 'Happy');")
+        .unwrap();
+
+    // Adding a value alters the type in place
+    let lints = migrate(conn, &mut ms, quote!(Happy, Meh, Sad), "v2");
+    assert!(lints.is_empty(), "{:?}", lints);
+    conn.execute("INSERT INTO Pet (id, mood) VALUES (2, 'Meh');")
+        .unwrap();
+    assert_eq!(count(conn, "Meh"), 1);
+
+    // Removing a value recreates the type on postgres
+    let lints = migrate(conn, &mut ms, quote!(Happy, Meh), "v3");
+    if backend.name() == "pg" {
+        assert_eq!(lint::max_severity(&lints), Severity::Locking);
+        assert!(conn
+            .execute("INSERT INTO Pet (id, mood) VALUES (3, 'Sad');")
+            .is_err());
+    } else {
+        assert!(lints.is_empty(), "{:?}", lints);
+    }
+    assert_eq!(count(conn, "Happy"), 1);
+    assert_eq!(count(conn, "Meh"), 1);
+
+    ms.latest().unwrap().downgrade(conn).unwrap();
+    conn.execute("INSERT INTO Pet (id, mood) VALUES (4, 'Sad');")
+        .unwrap();
+    assert_eq!(count(conn, "Sad"), 1);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_enum_deleted_sqlite() {
+    migration_enum_deleted(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_enum_deleted_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_enum_deleted(&mut conn);
+}
+
+fn migration_enum_deleted(conn: &mut Connection) {
+    let backend = conn.backend();
+    let mut ms = MemMigrations::new();
+    derive_field_type_with_migrations(
+        quote! {
+            #[butane(pg_enum)]
+            enum Shade { Light, Dark }
+        },
+        &mut ms,
+    );
+    model_with_migrations(
+        quote! {
+            struct Lamp {
+                id: i64,
+                shade: Option<Shade>,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "init", None).unwrap());
+    ms.apply_all(conn).unwrap();
+
+    // The enum is deleted along with the field using it, but its
+    // definition remains in the current migration.
+    model_with_migrations(
+        quote! {
+            struct Lamp {
+                id: i64,
+                watts: Option<i32>,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backend, "v2", ms.latest().as_ref())
+        .unwrap());
+    let db = ms.latest().unwrap().db().unwrap();
+    assert!(db.get_enum("shade").is_none());
+    assert!(ms.current().db().unwrap().get_enum("shade").is_none());
+    ms.apply_all(conn).unwrap();
+    if backend.name() == "pg" {
+        assert!(conn.execute("SELECT CAST('Light' AS shade);").is_err());
+    }
+}
+
+fn create_init_migration(conn: &Connection) -> MemMigrations {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&conn.backend(), "init", None).unwrap());
+    ms
+}
+
+fn migration_history(conn: &mut Connection) {
+    let mut ms = create_init_migration(conn);
+    assert!(applied_migrations(conn).unwrap().is_empty());
+    for m in ms.unapplied_migrations(conn).unwrap() {
+        m.apply(conn).unwrap();
+    }
+
+    let history = applied_migrations(conn).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "init");
+    assert!(history[0].applied_at.is_some());
+    let m = ms.get_migration("init").unwrap();
+    assert_eq!(
+        history[0].checksum,
+        m.checksum(conn.backend_name()).unwrap()
+    );
+    ms.verify_checksums(conn).unwrap();
+
+    // Editing the migration after it has been applied must be detected
+    let mut edited = m.clone();
+    let up_sql = m.up_sql(conn.backend_name()).unwrap().unwrap();
+    let down_sql = m.down_sql(conn.backend_name()).unwrap().unwrap();
+    edited
+        .add_sql(
+            conn.backend_name(),
+            &format!("{}\n-- edited", up_sql),
+            &down_sql,
+        )
+        .unwrap();
+    ms.add_migration(edited).unwrap();
+    assert!(ms.verify_checksums(conn).is_err());
+}
+
+fn migration_mark_applied(conn: &mut Connection) {
+    let mut ms = create_init_migration(conn);
+    for m in ms.unapplied_migrations(conn).unwrap() {
+        m.mark_applied(conn).unwrap();
+    }
+    assert!(ms.unapplied_migrations(conn).unwrap().is_empty());
+    // The migration was not actually run. Postgres folds the unquoted
+    // table name to lower case, so has_table cannot be used to check.
+    assert!(conn.execute("SELECT id FROM Foo;").is_err());
+
+    let history = applied_migrations(conn).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "init");
+    let m = ms.get_migration("init").unwrap();
+    assert!(history[0].checksum.is_some());
+    assert_eq!(
+        history[0].checksum,
+        m.checksum(conn.backend_name()).unwrap()
+    );
+    ms.verify_checksums(conn).unwrap();
+
+    // Edits to a migration marked applied are detected too
+    let mut edited = m.clone();
+    let up_sql = m.up_sql(conn.backend_name()).unwrap().unwrap();
+    let down_sql = m.down_sql(conn.backend_name()).unwrap().unwrap();
+    edited
+        .add_sql(
+            conn.backend_name(),
+            &format!("{}\n-- edited", up_sql),
+            &down_sql,
+        )
+        .unwrap();
+    ms.add_migration(edited).unwrap();
+    assert!(ms.verify_checksums(conn).is_err());
+}
+
+fn migration_history_legacy_table(conn: &mut Connection) {
+    // Older versions of butane recorded only migration names
+    conn.execute("CREATE TABLE butane_migrations (name TEXT NOT NULL PRIMARY KEY);")
+        .unwrap();
+    conn.execute("INSERT INTO butane_migrations (name) VALUES ('legacy');")
+        .unwrap();
+    let history = applied_migrations(conn).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "legacy");
+    assert_eq!(history[0].applied_at, None);
+    assert_eq!(history[0].checksum, None);
+
+    let ms = create_init_migration(conn);
+    for m in ms.unapplied_migrations(conn).unwrap() {
+        m.apply(conn).unwrap();
+    }
+    let history = applied_migrations(conn).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].name, "legacy");
+    assert_eq!(history[1].name, "init");
+    assert!(history[1].applied_at.is_some());
+    assert!(history[1].checksum.is_some());
+    ms.verify_checksums(conn).unwrap();
+}
+
+fn migration_history_same_second(conn: &mut Connection) {
+    // Recorded out of order, within the same second
+    conn.execute(
+        "CREATE TABLE butane_migrations (name TEXT NOT NULL PRIMARY KEY, applied_at BIGINT, checksum TEXT, seq BIGINT);",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO butane_migrations (name, applied_at, seq) VALUES ('second', 100, 2);",
+    )
+    .unwrap();
+    conn.execute("INSERT INTO butane_migrations (name, applied_at, seq) VALUES ('first', 100, 1);")
+        .unwrap();
+
+    let ms = create_init_migration(conn);
+    for m in ms.unapplied_migrations(conn).unwrap() {
+        m.apply(conn).unwrap();
+    }
+    let names: Vec<String> = applied_migrations(conn)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+    assert_eq!(names, ["first", "second", "init"]);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_apply_all_concurrent_sqlite() {
+    let path = std::env::temp_dir().join(format!(
+        "butane-migration-{}.db",
+        uuid_for_test::Uuid::new_v4()
+    ));
+    let connstr = path.to_str().unwrap().to_string();
+    migration_apply_all_concurrent("sqlite", &connstr);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_apply_all_concurrent_pg() {
+    let data = common::pg_setup();
+    migration_apply_all_concurrent("pg", &common::pg_connstr(&data));
+}
+
+fn migration_apply_all_concurrent(backend_name: &str, connstr: &str) {
+    let backend = butane::db::get_backend(backend_name).unwrap();
+    let conn = backend.connect(connstr).unwrap();
+    let ms = create_init_migration(&conn);
+
+    // Several processes starting at once must apply each migration exactly once
+    let barrier = std::sync::Barrier::new(4);
+    let applied: usize = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    let backend = butane::db::get_backend(backend_name).unwrap();
+                    let mut conn = backend.connect(connstr).unwrap();
+                    barrier.wait();
+                    ms.apply_all(&mut conn).unwrap().len()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    });
+    assert_eq!(applied, 1);
+
+    let history = applied_migrations(&conn).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].name, "init");
+    conn.execute("SELECT id FROM Foo;").unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_merge_sqlite() {
+    migration_merge(&mut common::sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_merge_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_merge(&mut conn);
+}
+
+fn migration_merge(conn: &mut Connection) {
+    let backend = conn.backend();
+    let root = std::env::temp_dir().join(format!(
+        "butane-migrations-{}",
+        uuid_for_test::Uuid::new_v4()
+    ));
+    let mut ms = butane::migrations::from_root(&root);
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "init", None).unwrap());
+    let init = ms.get_migration("init").unwrap();
+
+    // Two branches each add a migration based on init
+    model_with_migrations(
+        quote! {
+            struct Bar {
+                id: i64,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "a", Some(&init)).unwrap());
+    model_with_migrations(
+        quote! {
+            struct Baz {
+                id: i64,
+            }
+        },
+        &mut ms,
+    );
+    let mut b_db = init.db().unwrap();
+    b_db.replace_table(ms.current().db().unwrap().get_table("Baz").unwrap().clone());
+    assert!(ms
+        .create_migration_to(&backend, "b", Some(&init), b_db)
+        .unwrap());
+    let heads = ms.heads().unwrap();
+    assert_eq!(heads.len(), 2);
+
+    // The database has been migrated on one branch
+    assert_eq!(ms.apply_all(conn).unwrap().len(), 2);
+
+    ms.create_merge_migration(&backend, "merge", &heads)
+        .unwrap();
+    let heads = ms.heads().unwrap();
+    assert_eq!(heads.len(), 1);
+    assert_eq!(heads[0].name(), "merge");
+    assert_eq!(ms.latest().unwrap().name(), "merge");
+    let names = |migrations: Vec<butane::migrations::FsMigration>| -> Vec<String> {
+        migrations.iter().map(|m| m.name().to_string()).collect()
+    };
+    assert_eq!(
+        names(ms.all_migrations().unwrap()),
+        vec!["init", "a", "b", "merge"]
+    );
+    assert_eq!(
+        names(ms.migrations_since(&init).unwrap()),
+        vec!["a", "b", "merge"]
+    );
+    let merged = ms.latest().unwrap().db().unwrap();
+    for table in ["Foo", "Bar", "Baz"] {
+        assert!(merged.get_table(table).is_some());
+    }
+
+    assert_eq!(
+        names(ms.unapplied_migrations(conn).unwrap()),
+        vec!["b", "merge"]
+    );
+    assert_eq!(ms.apply_all(conn).unwrap().len(), 2);
+    assert!(ms.unapplied_migrations(conn).unwrap().is_empty());
+    conn.execute("SELECT id FROM Baz;").unwrap();
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn migration_merge_three_way() {
+    let column =
+        |name: &str, ty: SqlType| AColumn::new(name, known(ty), true, false, false, false, None);
+    let mut base = ADB::new();
+    let mut foo = ATable::new("Foo".to_string());
+    foo.add_column(column("id", SqlType::BigInt));
+    foo.add_column(column("old", SqlType::Text));
+    base.replace_table(foo.clone());
+    base.replace_table(ATable::new("Gone".to_string()));
+
+    // One branch adds a table, the other removes a table and a column
+    let mut ours = base.clone();
+    ours.replace_table(ATable::new("Bar".to_string()));
+    let mut theirs = base.clone();
+    theirs.remove_table("Gone");
+    let mut their_foo = foo.clone();
+    their_foo.remove_column("old");
+    theirs.replace_table(their_foo);
+
+    let mut merged = ours.clone();
+    merged.merge(&base, &theirs).unwrap();
+    assert!(merged.get_table("Bar").is_some());
+    assert!(merged.get_table("Gone").is_none());
+    let merged_foo = merged.get_table("Foo").unwrap();
+    assert!(merged_foo.column("id").is_some());
+    assert!(merged_foo.column("old").is_none());
+
+    // Changing a column removed on the other branch is a conflict
+    let mut our_foo = foo;
+    our_foo.replace_column(column("old", SqlType::Int));
+    ours.replace_table(our_foo);
+    assert!(ours.clone().merge(&base, &theirs).is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_merge_same_table() {
+    use butane::db::BackendRows;
+    let sqlite = butane::db::get_backend("sqlite").unwrap();
+    let mut ms = MemMigrations::new();
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&sqlite, "init", None).unwrap());
+    let init = ms.get_migration("init").unwrap();
+    let branch_db = |change: &dyn Fn(&mut ATable)| {
+        let mut db = init.db().unwrap();
+        let mut foo = db.get_table("Foo").unwrap().clone();
+        change(&mut foo);
+        db.replace_table(foo);
+        db
+    };
+    let mut branch = |name: &str, change: &dyn Fn(&mut ATable)| {
+        let db = branch_db(change);
+        assert!(ms
+            .create_migration_to(&sqlite, name, Some(&init), db)
+            .unwrap());
+        ms.get_migration(name).unwrap()
+    };
+    let a = branch("a", &|foo| {
+        foo.add_column(AColumn::new_simple("x", known(SqlType::BigInt)))
+    });
+    let b = branch("b", &|foo| {
+        foo.add_column(AColumn::new_simple("y", known(SqlType::BigInt)))
+    });
+
+    // Changing a column may rebuild the table without the other
+    // branch's columns, so cannot be merged
+    let changed =
+        branch_db(&|foo| foo.replace_column(AColumn::new_simple("bar", known(SqlType::BigInt))));
+    assert!(a
+        .db()
+        .unwrap()
+        .merge(&init.db().unwrap(), &changed)
+        .is_err());
+
+    // Columns added on both branches survive either order
+    ms.create_merge_migration(&sqlite, "merge", &[a.clone(), b.clone()])
+        .unwrap();
+    let merge = ms.get_migration("merge").unwrap();
+    for (first, second) in [(&a, &b), (&b, &a)] {
+        let mut conn = common::sqlite_connection();
+        init.apply(&mut conn).unwrap();
+        first.apply(&mut conn).unwrap();
+        conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'one');")
+            .unwrap();
+        conn.execute(&format!(
+            "UPDATE Foo SET {} = 7;",
+            if *first == a { "x" } else { "y" }
+        ))
+        .unwrap();
+        second.apply(&mut conn).unwrap();
+        merge.apply(&mut conn).unwrap();
+        assert!(ms.unapplied_migrations(&conn).unwrap().is_empty());
+        let rows: Vec<(Option<i64>, Option<i64>)> = conn
+            .query(
+                "Foo",
+                &[
+                    butane::db::Column::new("x", SqlType::BigInt),
+                    butane::db::Column::new("y", SqlType::BigInt),
+                ],
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+            .mapped(|row| {
+                Ok((
+                    butane::FromSql::from_sql_ref(row.get(0, SqlType::BigInt)?)?,
+                    butane::FromSql::from_sql_ref(row.get(1, SqlType::BigInt)?)?,
+                ))
+            })
+            .collect()
+            .unwrap();
+        let expected = if *first == a { (7, 0) } else { (0, 7) };
+        assert_eq!(rows, vec![(Some(expected.0), Some(expected.1))]);
+    }
+}
+
+fn known(ty: SqlType) -> DeferredSqlType {
+    DeferredSqlType::KnownId(TypeIdentifier::Ty(ty))
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_lint_safe() {
+    let sqlite = butane::db::get_backend("sqlite").unwrap();
+    let mut old = ADB::new();
+    let mut foo = ATable::new("Foo".to_string());
+    foo.add_column(AColumn::new(
+        "id",
+        known(SqlType::BigInt),
+        false,
+        true,
+        false,
+        false,
+        None,
+    ));
+    old.replace_table(foo.clone());
+
+    let mut new = old.clone();
+    foo.add_column(AColumn::new(
+        "bar",
+        known(SqlType::Text),
+        true,
+        false,
+        false,
+        false,
+        None,
+    ));
+    foo.add_column(AColumn::new(
+        "baz",
+        known(SqlType::Int),
+        false,
+        false,
+        false,
+        false,
+        Some(SqlVal::Int(1)),
+    ));
+    new.replace_table(foo);
+    new.replace_table(ATable::new("Bar".to_string()));
+
+    let lints = lint::lint(&sqlite, &adb::diff(&old, &new));
+    assert!(lints.is_empty(), "{:?}", lints);
+    assert_eq!(lint::max_severity(&lints), Severity::Safe);
+}
+
+#[cfg(all(feature = "sqlite", feature = "pg"))]
+#[test]
+fn migration_lint_destructive() {
+    let sqlite = butane::db::get_backend("sqlite").unwrap();
+    let pg = butane::db::get_backend("pg").unwrap();
+    let mut old = ADB::new();
+    let mut foo = ATable::new("Foo".to_string());
+    foo.add_column(AColumn::new(
+        "id",
+        known(SqlType::BigInt),
+        false,
+        true,
+        false,
+        false,
+        None,
+    ));
+    foo.add_column(AColumn::new_simple("bar", known(SqlType::Text)));
+    foo.add_column(AColumn::new_simple("baz", known(SqlType::BigInt)));
+    old.replace_table(foo.clone());
+    old.replace_table(ATable::new("Old".to_string()));
+
+    // Adding a non-nullable column without a default fills existing
+    // rows with a placeholder
+    let mut new = old.clone();
+    let mut added = foo.clone();
+    added.add_column(AColumn::new_simple("qux", known(SqlType::Int)));
+    new.replace_table(added);
+    let lints = lint::lint(&sqlite, &adb::diff(&old, &new));
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lint::max_severity(&lints), Severity::DataLosing);
+
+    // Widening a column is not data-losing, but rewrites the table
+    let mut new = old.clone();
+    let mut widened = foo.clone();
+    widened.replace_column(AColumn::new_simple("baz", known(SqlType::Text)));
+    new.replace_table(widened);
+    let lints = lint::lint(&sqlite, &adb::diff(&old, &new));
+    assert_eq!(lint::max_severity(&lints), Severity::Locking);
+
+    // Postgres makes a column nullable in place, sqlite copies the table
+    let mut new = old.clone();
+    let mut relaxed = foo.clone();
+    relaxed.replace_column(AColumn::new(
+        "bar",
+        known(SqlType::Text),
+        true,
+        false,
+        false,
+        false,
+        None,
+    ));
+    new.replace_table(relaxed);
+    let ops = adb::diff(&old, &new);
+    assert!(lint::lint(&pg, &ops).is_empty());
+    assert_eq!(
+        lint::max_severity(&lint::lint(&sqlite, &ops)),
+        Severity::Locking
+    );
+
+    // Narrowing a column, dropping a column and dropping a table all lose data
+    let mut new = old.clone();
+    new.remove_table("Old");
+    foo.remove_column("bar");
+    foo.replace_column(AColumn::new_simple("baz", known(SqlType::Int)));
+    new.replace_table(foo);
+    let lints = lint::lint(&sqlite, &adb::diff(&old, &new));
+    assert_eq!(lint::max_severity(&lints), Severity::DataLosing);
+    let data_losing: Vec<String> = lints
+        .iter()
+        .filter(|l| l.severity == Severity::DataLosing)
+        .map(|l| l.to_string())
+        .collect();
+    assert_eq!(data_losing.len(), 3, "{:?}", data_losing);
+    assert!(data_losing.iter().any(|l| l.contains("Foo.bar")));
+    assert!(data_losing.iter().any(|l| l.contains("Foo.baz")));
+    assert!(data_losing.iter().any(|l| l.contains("table Old")));
+}
+
+#[cfg(all(feature = "sqlite", feature = "pg"))]
+#[test]
+fn migration_lint_wide_int() {
+    let sqlite = butane::db::get_backend("sqlite").unwrap();
+    let pg = butane::db::get_backend("pg").unwrap();
+    let mut old = ADB::new();
+    let mut foo = ATable::new("Foo".to_string());
+    foo.add_column(AColumn::new_simple("count", known(SqlType::BigInt)));
+    old.replace_table(foo.clone());
+
+    let mut new = old.clone();
+    foo.replace_column(AColumn::new_simple("count", known(SqlType::HugeInt)));
+    new.replace_table(foo);
+    let ops = adb::diff(&old, &new);
+    // Postgres converts the values, sqlite leaves them as INTEGER
+    // rather than the blobs of HugeInt
+    assert_eq!(
+        lint::max_severity(&lint::lint(&pg, &ops)),
+        Severity::Locking
+    );
+    let lints = lint::lint(&sqlite, &ops);
+    assert_eq!(lint::max_severity(&lints), Severity::DataLosing);
+    assert!(lints.iter().any(|l| l.to_string().contains("Foo.count")));
+}
+
+#[cfg(all(feature = "decimal", feature = "pg"))]
+#[test]
+fn migration_lint_numeric() {
+    let pg = butane::db::get_backend("pg").unwrap();
+    let numeric = |precision, scale| known(SqlType::Numeric { precision, scale });
+    let mut old = ADB::new();
+    let mut foo = ATable::new("Foo".to_string());
+    foo.add_column(AColumn::new_simple("amount", numeric(10, 2)));
+    old.replace_table(foo.clone());
+
+    let change_to = |precision, scale| {
+        let mut new = old.clone();
+        let mut changed = foo.clone();
+        changed.replace_column(AColumn::new_simple("amount", numeric(precision, scale)));
+        new.replace_table(changed);
+        lint::max_severity(&lint::lint(&pg, &adb::diff(&old, &new)))
+    };
+    // Adding digits on either side of the point keeps every value
+    assert_eq!(change_to(12, 2), Severity::Locking);
+    assert_eq!(change_to(11, 3), Severity::Locking);
+    // Removing them may not
+    assert_eq!(change_to(10, 3), Severity::DataLosing);
+    assert_eq!(change_to(12, 1), Severity::DataLosing);
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_alter_column_pg() {
+    let (mut conn, data) = common::pg_connection();
+    let mut client =
+        postgres::Client::connect(&common::pg_connstr(&data), postgres::NoTls).unwrap();
+    let backend = conn.backend();
+    let mut ms = MemMigrations::new();
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                a: i32,
+                b: Option<String>,
+                c: String,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "init", None).unwrap());
+    ms.apply_all(&mut conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, a, b, c) VALUES (1, 2, 'x', 'y');")
+        .unwrap();
+    conn.execute("CREATE INDEX foo_c_idx ON Foo (c);").unwrap();
+
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                a: i64,
+                b: String,
+                #[unique]
+                c: String,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backend, "v2", ms.latest().as_ref())
+        .unwrap());
+    let v2 = ms.latest().unwrap();
+    let up_sql = v2.up_sql(backend.name()).unwrap().unwrap();
+    assert!(!up_sql.contains("__butane_tmp"), "{}", up_sql);
+    ms.apply_all(&mut conn).unwrap();
+
+    // The altered table matches one created from scratch, as the
+    // table copy approach would have done
+    let create_reference = |conn: &Connection, name: &str, db: &ADB| {
+        let mut table = db.get_table("Foo").unwrap().clone();
+        table.name = name.to_string();
+        let sql = backend
+            .create_migration_sql(&ADB::new(), vec![adb::Operation::AddTable(table)])
+            .unwrap();
+        conn.execute(&sql).unwrap();
+    };
+    create_reference(&conn, "FooV2", &v2.db().unwrap());
+    assert_eq!(
+        pg_table_schema(&mut client, "foo"),
+        pg_table_schema(&mut client, "foov2")
+    );
+
+    // Unlike a copy, the data and indexes are retained in place
+    let row = client.query_one("SELECT a, b FROM Foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 2);
+    assert_eq!(row.get::<_, String>(1), "x");
+    let indexes = client
+        .query(
+            "SELECT 1 FROM pg_indexes WHERE indexname = 'foo_c_idx'",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(indexes.len(), 1);
+
+    // The unique constraint is found even if it has been renamed
+    conn.execute("ALTER TABLE Foo RENAME CONSTRAINT foo_c_key TO foo_c_renamed;")
+        .unwrap();
+    v2.downgrade(&mut conn).unwrap();
+    let init = ms.get_migration("init").unwrap();
+    create_reference(&conn, "FooInit", &init.db().unwrap());
+    assert_eq!(
+        pg_table_schema(&mut client, "foo"),
+        pg_table_schema(&mut client, "fooinit")
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_uuid_from_blob_sqlite() {
+    // Uuids were previously stored as blobs, as they still are here
+    test_migrate(
+        &mut common::sqlite_connection(),
+        quote! {
+            struct Foo {
+                id: i64,
+                key: Vec<u8>,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                key: Uuid,
+            }
+        },
+        "",
+        "",
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_uuid_from_blob_pg() {
+    let (mut conn, data) = common::pg_connection();
+    let mut client =
+        postgres::Client::connect(&common::pg_connstr(&data), postgres::NoTls).unwrap();
+    let backend = conn.backend();
+    let mut ms = MemMigrations::new();
+    // Uuids were previously stored as BYTEA
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                key: Vec<u8>,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backend, "init", None).unwrap());
+    ms.apply_all(&mut conn).unwrap();
+    let key = uuid_for_test::Uuid::new_v4();
+    client
+        .execute(
+            "INSERT INTO Foo (id, key) VALUES (1, $1)",
+            &[&key.as_bytes().as_slice()],
+        )
+        .unwrap();
+
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                key: Uuid,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backend, "v2", ms.latest().as_ref())
+        .unwrap());
+    ms.apply_all(&mut conn).unwrap();
+    let row = client
+        .query_one("SELECT pg_typeof(key)::text, key::text FROM Foo", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, String>(0), "uuid");
+    assert_eq!(row.get::<_, String>(1), key.to_string());
+
+    ms.latest().unwrap().downgrade(&mut conn).unwrap();
+    let row = client.query_one("SELECT key FROM Foo", &[]).unwrap();
+    assert_eq!(row.get::<_, Vec<u8>>(0), key.as_bytes());
+}
+
+/// Columns (name, type, nullability) and uniquely constrained columns of a table.
+#[cfg(feature = "pg")]
+fn pg_table_schema(
+    client: &mut postgres::Client,
+    table: &str,
+) -> (Vec<(String, String, String)>, Vec<String>) {
+    let mut columns: Vec<(String, String, String)> = client
+        .query(
+            "SELECT column_name::text, data_type::text, is_nullable::text FROM information_schema.columns WHERE table_name = $1",
+            &[&table],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2)))
+        .collect();
+    columns.sort();
+    let mut unique: Vec<String> = client
+        .query(
+            "SELECT k.column_name::text FROM information_schema.table_constraints c JOIN information_schema.key_column_usage k ON c.constraint_name = k.constraint_name WHERE c.table_name = $1 AND c.constraint_type = 'UNIQUE'",
+            &[&table],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    unique.sort();
+    (columns, unique)
+}
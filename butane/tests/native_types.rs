@@ -0,0 +1,99 @@
+use butane::db::{Connection, ConnectionMethods};
+use butane::prelude::*;
+use butane::{model, query, ObjectState};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use uuid_for_test::Uuid;
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Debug, Clone)]
+struct Device {
+    id: Uuid,
+    addr: IpAddr,
+    fallback: Option<IpAddr>,
+    ports: Vec<i32>,
+    names: Vec<String>,
+    counters: std::vec::Vec<i64>,
+    weights: Vec<f64>,
+    flags: Vec<bool>,
+}
+impl Device {
+    fn new(addr: IpAddr) -> Self {
+        Device {
+            id: Uuid::new_v4(),
+            addr,
+            fallback: None,
+            ports: Vec::new(),
+            names: Vec::new(),
+            counters: Vec::new(),
+            weights: Vec::new(),
+            flags: Vec::new(),
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn roundtrip_native_types(conn: Connection) {
+    let mut device = Device::new(Ipv6Addr::LOCALHOST.into());
+    device.fallback = Some(Ipv4Addr::new(192, 168, 1, 20).into());
+    device.ports = vec![22, 443, -1];
+    device.names = vec![
+        "eth0".to_string(),
+        "with \"quotes\", commas and 'apostrophes'".to_string(),
+        "".to_string(),
+    ];
+    device.counters = vec![i64::MAX, i64::MIN];
+    device.weights = vec![0.5, -2.25];
+    device.flags = vec![true, false];
+    device.save(&conn).unwrap();
+
+    let device2 = Device::get(&conn, device.id).unwrap();
+    assert_eq!(device, device2);
+
+    // Empty arrays are distinct from null
+    let empty = Device::new(Ipv4Addr::LOCALHOST.into());
+    empty.clone().save(&conn).unwrap();
+    assert_eq!(Device::get(&conn, empty.id).unwrap(), empty);
+}
+testall!(roundtrip_native_types);
+
+fn query_native_types(conn: Connection) {
+    let local: IpAddr = Ipv4Addr::LOCALHOST.into();
+    let mut a = Device::new(local);
+    a.save(&conn).unwrap();
+    let mut b = Device::new(Ipv4Addr::new(10, 0, 0, 1).into());
+    b.fallback = Some(local);
+    b.ports = vec![80];
+    b.save(&conn).unwrap();
+
+    let found = query!(Device, addr == { local }).load(&conn).unwrap();
+    assert_eq!(found, [a.clone()]);
+    let found = query!(Device, fallback == { Some(local) })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(found, [b.clone()]);
+    let id = b.id;
+    let found = query!(Device, id == { id }).load(&conn).unwrap();
+    assert_eq!(found, [b.clone()]);
+    let ports = vec![80];
+    let found = query!(Device, ports == { ports }).load(&conn).unwrap();
+    assert_eq!(found, [b]);
+}
+testall!(query_native_types);
+
+fn native_column_types(conn: Connection) {
+    Device::new(Ipv4Addr::LOCALHOST.into()).save(&conn).unwrap();
+    let matched = conn
+        .raw_execute(
+            "UPDATE Device SET addr = addr WHERE pg_typeof(id) = 'uuid'::regtype \
+             AND pg_typeof(addr) = 'inet'::regtype AND pg_typeof(ports) = 'integer[]'::regtype \
+             AND pg_typeof(names) = 'text[]'::regtype AND pg_typeof(counters) = 'bigint[]'::regtype \
+             AND pg_typeof(weights) = 'double precision[]'::regtype \
+             AND pg_typeof(flags) = 'boolean[]'::regtype",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(matched, 1);
+}
+maketest_pg!(native_column_types);
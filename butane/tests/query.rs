@@ -1,8 +1,8 @@
-use butane::db::Connection;
+use butane::db::{Connection, ConnectionMethods};
 use butane::prelude::*;
-use butane::query::BoolExpr;
-use butane::{colname, filter, find, query, Many};
-use chrono::{TimeZone, Utc};
+use butane::query::{BoolExpr, Cursor, Order, OrderDirection, Page, PreparedQuery};
+use butane::{colname, filter, find, model, order, query, ForeignKey, Many, ObjectState, SqlVal};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
 use paste;
 use serde_json;
 
@@ -10,6 +10,24 @@ mod common;
 use common::blog;
 use common::blog::{Blog, Post, PostMetadata, Tag};
 
+#[model]
+#[derive(Debug)]
+struct Comment {
+    id: i64,
+    text: String,
+    post: Option<ForeignKey<Post>>,
+}
+impl Comment {
+    fn new(id: i64, text: &str, post: Option<&Post>) -> Self {
+        Comment {
+            id,
+            text: text.to_string(),
+            post: post.map(ForeignKey::from),
+            state: ObjectState::default(),
+        }
+    }
+}
+
 fn equality(conn: Connection) {
     blog::setup_blog(&conn);
     let mut posts = query!(Post, published == true).load(&conn).unwrap();
@@ -230,3 +248,747 @@ fn offset(conn: Connection) {
     assert_eq!(posts[1].title, "The Tiger");
 }
 testall!(offset);
+
+fn prepared(conn: Connection) {
+    blog::setup_blog(&conn);
+    let q: PreparedQuery<Post, (bool, i32)> = query!(
+        Post,
+        published == param!(published) && likes > param!(likes)
+    )
+    .order_asc(colname!(Post, title))
+    .prepare(&["published", "likes"])
+    .unwrap();
+    let posts = q.load(&conn, (true, 5)).unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Sir Charles");
+
+    // Execute again with different values, reusing the statement.
+    let posts = q.load(&conn, (true, 15)).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+    let posts = q.load(&conn, (false, 15)).unwrap();
+    assert!(posts.is_empty());
+
+    let post = q.load_first(&conn, (true, 0)).unwrap().unwrap();
+    assert_eq!(post.title, "Mount Doom");
+}
+testall!(prepared);
+
+fn prepared_like(conn: Connection) {
+    blog::setup_blog(&conn);
+    let q: PreparedQuery<Post, (&str,)> = query!(Post, title.like(param!(pattern)))
+        .prepare(&["pattern"])
+        .unwrap();
+    let mut posts = q.load(&conn, ("M%",)).unwrap();
+    posts.sort_by(|p1, p2| p1.id.partial_cmp(&p2.id).unwrap());
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Mt. Everest");
+    let posts = q.load(&conn, ("S%",)).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+}
+testall!(prepared_like);
+
+fn prepared_many(conn: Connection) {
+    blog::setup_blog(&conn);
+    // More distinct statements than are cached, so that the earliest
+    // are evicted and must be prepared again.
+    let queries: Vec<PreparedQuery<Post, (i32,)>> = (1..=300)
+        .map(|limit| {
+            query!(Post, likes > param!(likes))
+                .limit(limit)
+                .prepare(&["likes"])
+                .unwrap()
+        })
+        .collect();
+    for _ in 0..2 {
+        for (i, q) in queries.iter().enumerate() {
+            let posts = q.load(&conn, (-1,)).unwrap();
+            assert_eq!(posts.len(), (i + 1).min(4));
+        }
+    }
+}
+testall!(prepared_many);
+
+fn prepared_invalid_params(conn: Connection) {
+    blog::setup_blog(&conn);
+    // A query with parameters cannot be loaded directly.
+    assert!(matches!(
+        query!(Post, likes > param!(likes)).load(&conn),
+        Err(butane::Error::InvalidQueryParams(_))
+    ));
+    // Every parameter must be named.
+    assert!(matches!(
+        query!(Post, likes > param!(likes)).prepare::<(i32,)>(&["views"]),
+        Err(butane::Error::InvalidQueryParams(_))
+    ));
+    // And there must be a value for every name.
+    assert!(matches!(
+        query!(Post, likes > param!(likes)).prepare::<(i32, i32)>(&["likes"]),
+        Err(butane::Error::InvalidQueryParams(_))
+    ));
+    // Every name must be used by the query.
+    assert!(matches!(
+        query!(Post, likes > param!(likes)).prepare::<(i32, i32)>(&["likes", "views"]),
+        Err(butane::Error::InvalidQueryParams(_))
+    ));
+    // Null never compares equal, so it is not accepted as a value.
+    let q = query!(Post, likes == param!(likes))
+        .prepare::<(Option<i32>,)>(&["likes"])
+        .unwrap();
+    assert!(matches!(
+        q.load(&conn, (None,)),
+        Err(butane::Error::InvalidQueryParams(_))
+    ));
+    assert_eq!(q.load(&conn, (Some(-1),)).unwrap().len(), 0);
+}
+testall!(prepared_invalid_params);
+
+fn raw_query(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts: Vec<PostMetadata> = conn
+        .raw_query(
+            "SELECT id, title, pub_time FROM Post WHERE likes > ? AND title <> '?' ORDER BY title",
+            &[SqlVal::Int(5)],
+        )
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Sir Charles");
+
+    // The columns must match those of the result type.
+    let result = conn.raw_query::<PostMetadata>("SELECT id, title FROM Post", &[]);
+    assert!(matches!(result, Err(butane::Error::RawQueryColumns(_))));
+    let result = conn.raw_query::<PostMetadata>("SELECT id, body, pub_time FROM Post", &[]);
+    assert!(matches!(result, Err(butane::Error::RawQueryColumns(_))));
+}
+testall!(raw_query);
+
+fn raw_query_pg_syntax(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Only the final ? outside comments, dollar quotes and jsonb
+    // operators is a placeholder
+    let posts: Vec<PostMetadata> = conn
+        .raw_query(
+            "SELECT id, title, pub_time FROM Post /* likes > ? /* nested ? */ */ \
+             WHERE title <> $$?$$ AND title <> $tag$ it's ? $tag$ \
+             AND '{\"a\": 1}'::jsonb ?? 'a' AND '{\"a\": 1}'::jsonb ?| array['a'] \
+             AND '{\"a\": 1}'::jsonb ?& array['a'] AND likes > ? ORDER BY title",
+            &[SqlVal::Int(5)],
+        )
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+}
+maketest_pg!(raw_query_pg_syntax);
+
+fn raw_execute(conn: Connection) {
+    blog::setup_blog(&conn);
+    let cnt = conn
+        .raw_execute(
+            "UPDATE Post SET likes = likes + ? WHERE published = ?",
+            &[SqlVal::Int(1), SqlVal::Bool(true)],
+        )
+        .unwrap();
+    assert_eq!(cnt, 3);
+    let post = find!(Post, title == "Sir Charles", &conn).unwrap();
+    assert_eq!(post.likes, 21);
+}
+testall!(raw_execute);
+
+fn iter(mut conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .iter(&mut conn)
+        .unwrap();
+    assert_eq!(posts.next().unwrap().unwrap().title, "Mount Doom");
+    assert_eq!(posts.next().unwrap().unwrap().title, "Sir Charles");
+    assert_eq!(posts.next().unwrap().unwrap().title, "The Tiger");
+    assert!(posts.next().unwrap().is_none());
+    drop(posts);
+
+    // Stop part way through, then use the connection again.
+    let mut posts = Post::query().iter(&mut conn).unwrap();
+    assert!(posts.next().unwrap().is_some());
+    drop(posts);
+    let titles: Vec<String> = Post::query()
+        .order_desc(colname!(Post, title))
+        .iter(&mut conn)
+        .unwrap()
+        .map(|post| Ok(post.title))
+        .collect()
+        .unwrap();
+    assert_eq!(
+        titles,
+        vec!["The Tiger", "Sir Charles", "Mt. Everest", "Mount Doom"]
+    );
+
+    // Loaded results don't hold the connection, so it can be queried
+    // again while they are in use.
+    for post in Post::query().load(&conn).unwrap() {
+        let blog = post.blog.load(&conn).unwrap();
+        assert!(Blog::get(&conn, blog.id).is_ok());
+    }
+}
+testall!(iter);
+
+fn page(conn: Connection) {
+    blog::setup_blog(&conn);
+    let titles = |page: &Page<Post>| -> Vec<String> {
+        page.items.iter().map(|post| post.title.clone()).collect()
+    };
+    let query = || Post::query().order_asc(colname!(Post, title));
+
+    let first = query().page(&conn, None, 3).unwrap();
+    assert_eq!(
+        titles(&first),
+        vec!["Mount Doom", "Mt. Everest", "Sir Charles"]
+    );
+    assert!(first.previous.is_none());
+
+    // Cursors survive a round trip through a string.
+    let next: Cursor = first.next.unwrap().to_string().parse().unwrap();
+    let second = query().page(&conn, Some(&next), 3).unwrap();
+    assert_eq!(titles(&second), vec!["The Tiger"]);
+    assert!(second.next.is_none());
+
+    let back = query().page(&conn, second.previous.as_ref(), 3).unwrap();
+    assert_eq!(
+        titles(&back),
+        vec!["Mount Doom", "Mt. Everest", "Sir Charles"]
+    );
+    assert!(back.previous.is_none());
+    assert!(back.next.is_some());
+
+    // Rows inserted before the cursor do not shift later pages.
+    let blog = find!(Blog, name == "Cats", &conn).unwrap();
+    let mut post = Post::new(5, "A Cat", "Meow", &blog);
+    post.save(&conn).unwrap();
+    let second = query().page(&conn, Some(&next), 3).unwrap();
+    assert_eq!(titles(&second), vec!["The Tiger"]);
+}
+testall!(page);
+
+fn page_filtered_descending(conn: Connection) {
+    blog::setup_blog(&conn);
+    let query = || query!(Post, published == true).order_desc(colname!(Post, likes));
+    let first = query().page(&conn, None, 2).unwrap();
+    assert_eq!(first.items.len(), 2);
+    assert_eq!(first.items[0].title, "Sir Charles");
+    assert_eq!(first.items[1].title, "Mount Doom");
+    let second = query().page(&conn, first.next.as_ref(), 2).unwrap();
+    assert_eq!(second.items.len(), 1);
+    assert_eq!(second.items[0].title, "The Tiger");
+    assert!(second.next.is_none());
+    let back = query().page(&conn, second.previous.as_ref(), 2).unwrap();
+    assert_eq!(back.items[0].title, "Sir Charles");
+    assert_eq!(back.items[1].title, "Mount Doom");
+    assert!(back.previous.is_none());
+
+    // A cursor is rejected by a query with a different ordering.
+    let other = Post::query().order_asc(colname!(Post, likes));
+    assert!(matches!(
+        other.page(&conn, first.next.as_ref(), 2),
+        Err(butane::Error::InvalidCursor(_))
+    ));
+    let all = query().page(&conn, None, i32::MAX).unwrap();
+    assert_eq!(all.items.len(), 3);
+    assert!(all.next.is_none());
+    assert!(query().page(&conn, None, 0).is_err());
+}
+testall!(page_filtered_descending);
+
+fn select_columns(conn: Connection) {
+    blog::setup_blog(&conn);
+    let titles: Vec<String> = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .select(Post::fields().title())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(titles, vec!["Mount Doom", "Sir Charles", "The Tiger"]);
+
+    let posts: Vec<(i64, String, i32)> = query!(Post, likes > 5)
+        .order_desc(colname!(Post, likes))
+        .select((
+            Post::fields().id(),
+            Post::fields().title(),
+            Post::fields().likes(),
+        ))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(
+        posts,
+        vec![
+            (2, "Sir Charles".to_string(), 20),
+            (3, "Mount Doom".to_string(), 10)
+        ]
+    );
+
+    let times: Vec<Option<NaiveDateTime>> = query!(Post, published == false)
+        .select(Post::fields().pub_time())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(times, vec![None]);
+}
+testall!(select_columns);
+
+fn select_distinct(conn: Connection) {
+    blog::setup_blog(&conn);
+    let published: Vec<bool> = Post::query()
+        .order_asc(colname!(Post, published))
+        .select(Post::fields().published())
+        .distinct()
+        .load(&conn)
+        .unwrap();
+    assert_eq!(published, vec![false, true]);
+
+    let mut blogs: Vec<(ForeignKey<Blog>, bool)> = Post::query()
+        .select((Post::fields().blog(), Post::fields().published()))
+        .distinct()
+        .load(&conn)
+        .unwrap();
+    blogs.sort_by_key(|(blog, published)| (blog.pk(), *published));
+    let blogs: Vec<(i64, bool)> = blogs
+        .into_iter()
+        .map(|(blog, published)| (blog.pk(), published))
+        .collect();
+    assert_eq!(blogs, vec![(1, true), (2, false), (2, true)]);
+}
+testall!(select_distinct);
+
+fn join(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts: Vec<(Post, Blog)> = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .join(Post::fields().blog())
+        .load(&conn)
+        .unwrap();
+    let pairs: Vec<(&str, &str)> = posts
+        .iter()
+        .map(|(post, blog)| (post.title.as_str(), blog.name.as_str()))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("Mount Doom", "Mountains"),
+            ("Sir Charles", "Cats"),
+            ("The Tiger", "Cats")
+        ]
+    );
+    // Unfiltered, and with columns of the same name in both tables.
+    let posts = Post::query()
+        .order_desc(colname!(Post, id))
+        .limit(1)
+        .join(Post::fields().blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].0.id, 4);
+    assert_eq!(posts[0].1.id, 2);
+}
+testall!(join);
+
+fn left_join(conn: Connection) {
+    blog::setup_blog(&conn);
+    let post = find!(Post, title == "The Tiger", &conn).unwrap();
+    Comment::new(1, "Rawr", Some(&post)).save(&conn).unwrap();
+    Comment::new(2, "Lost", None).save(&conn).unwrap();
+
+    let comments: Vec<(Comment, Option<Post>)> = Comment::query()
+        .order_asc(colname!(Comment, id))
+        .left_join(Comment::fields().post())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[0].0.text, "Rawr");
+    assert_eq!(comments[0].1.as_ref().unwrap().title, "The Tiger");
+    assert_eq!(comments[1].0.text, "Lost");
+    assert!(comments[1].1.is_none());
+
+    // An inner join omits the comment without a post.
+    let comments = query!(Comment, id > 0)
+        .join(Comment::fields().post())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].1.title, "The Tiger");
+}
+testall!(left_join);
+
+fn not(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = query!(Post, !(published == true && likes > 5))
+        .load(&conn)
+        .unwrap();
+    posts.sort_by(|p1, p2| p1.id.partial_cmp(&p2.id).unwrap());
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "The Tiger");
+    assert_eq!(posts[1].title, "Mt. Everest");
+
+    // Grouping of || and && is preserved
+    let posts = query!(Post, (likes == 4 || likes == 20) && published == true)
+        .order_asc(colname!(Post, title))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "The Tiger");
+}
+testall!(not);
+
+fn is_in(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, title.is_in(["The Tiger", "Mount Doom", "Nope"]))
+        .order_asc(colname!(Post, title))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "The Tiger");
+
+    let ids: Vec<i64> = vec![2, 4];
+    let posts = query!(Post, id.is_in({ ids }))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "Mt. Everest");
+
+    let none: Vec<i64> = Vec::new();
+    assert!(query!(Post, id.is_in({ none.clone() }))
+        .load(&conn)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        query!(Post, !id.is_in({ none })).load(&conn).unwrap().len(),
+        4
+    );
+}
+testall!(is_in);
+
+fn between(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, likes.between(4, 10))
+        .order_asc(colname!(Post, likes))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "The Tiger");
+    assert_eq!(posts[1].title, "Mount Doom");
+}
+testall!(between);
+
+fn is_null(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, pub_time.is_some()).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "The Tiger");
+    let posts = query!(Post, pub_time.is_none()).load(&conn).unwrap();
+    assert_eq!(posts.len(), 3);
+}
+testall!(is_null);
+
+fn ilike(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = query!(Post, title.ilike("m%")).load(&conn).unwrap();
+    posts.sort_by(|p1, p2| p1.id.partial_cmp(&p2.id).unwrap());
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Mt. Everest");
+
+    let q: PreparedQuery<Post, (&str,)> = query!(Post, title.ilike(param!(pattern)))
+        .prepare(&["pattern"])
+        .unwrap();
+    let posts = q.load(&conn, ("%CHARLES",)).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+}
+testall!(ilike);
+
+fn compare_fields(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut post = find!(Post, title == "Sir Charles", &conn).unwrap();
+    post.body = post.title.clone();
+    post.save(&conn).unwrap();
+
+    let posts = query!(Post, title == body).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+    let posts = query!(Post, body != title).load(&conn).unwrap();
+    assert_eq!(posts.len(), 3);
+}
+testall!(compare_fields);
+
+fn arithmetic(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, likes * 2 > 15)
+        .order_asc(colname!(Post, likes))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Sir Charles");
+
+    let n = 6;
+    let posts = query!(Post, (likes - 4) % 10 == { n } && 20 > likes)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+}
+testall!(arithmetic);
+
+fn functions(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, title.lower() == "the tiger")
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "The Tiger");
+    let posts = query!(
+        Post,
+        title.upper() == "MOUNT DOOM" || (likes - 30).abs() == 10
+    )
+    .order_asc(colname!(Post, id))
+    .load(&conn)
+    .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "Mount Doom");
+    let posts = query!(Post, title.length() > 10).load(&conn).unwrap();
+    assert_eq!(posts.len(), 2);
+
+    let posts = Post::query()
+        .order_by(
+            Post::fields().title().value().length(),
+            OrderDirection::Descending,
+        )
+        .order_asc(colname!(Post, title))
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        ["Mt. Everest", "Sir Charles", "Mount Doom", "The Tiger"]
+    );
+}
+testall!(functions);
+
+fn date_functions(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut post = find!(Post, title == "The Tiger", &conn).unwrap();
+    post.pub_time = Some(
+        NaiveDate::from_ymd_opt(2020, 3, 15)
+            .unwrap()
+            .and_hms_opt(10, 30, 45)
+            .unwrap(),
+    );
+    post.save(&conn).unwrap();
+
+    let posts = query!(
+        Post,
+        pub_time.extract("month") == 3 && pub_time.extract("year") == 2020
+    )
+    .load(&conn)
+    .unwrap();
+    assert_eq!(posts.len(), 1);
+    let posts = query!(Post, pub_time.extract("second") == 45)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+
+    let march = NaiveDate::from_ymd_opt(2020, 3, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let posts = query!(Post, pub_time.date_trunc("month") == { Some(march) })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "The Tiger");
+
+    let posts = query!(Post, pub_time.coalesce({ march }) == { march })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+}
+testall!(date_functions);
+
+fn order_macro(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = Post::query()
+        .order_with(order!(Post, likes desc))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[3].title, "Mt. Everest");
+
+    let posts = Post::query()
+        .order_with(order!(Post, title.length() desc))
+        .order_with(order!(Post, title))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts[0].title, "Mt. Everest");
+    assert_eq!(posts[3].title, "The Tiger");
+}
+testall!(order_macro);
+
+fn order_related(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Mountains sorts before Cats
+    let posts = query!(Post, published == true)
+        .order_with(order!(Post, blog.name desc))
+        .order_with(order!(Post, likes))
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(titles, ["Mount Doom", "The Tiger", "Sir Charles"]);
+
+    let posts: Vec<(Post, Blog)> = Post::query()
+        .order_with(order!(Post, blog.name))
+        .order_with(order!(Post, title desc))
+        .join(Post::fields().blog())
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|(p, _)| p.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        ["The Tiger", "Sir Charles", "Mt. Everest", "Mount Doom"]
+    );
+}
+testall!(order_related);
+
+fn order_nulls(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = Post::query()
+        .order_with(order!(Post, pub_time nulls first))
+        .order_with(order!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[3].title, "The Tiger");
+
+    let posts = Post::query()
+        .order_with(order!(Post, pub_time desc nulls last))
+        .order_with(order!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts[0].title, "The Tiger");
+    assert_eq!(posts[1].title, "Sir Charles");
+
+    let posts = Post::query()
+        .order_with(Order::asc(Post::fields().pub_time()).nulls_last())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts[0].title, "The Tiger");
+}
+testall!(order_nulls);
+
+fn not_contains(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, !tags.contains("danger")).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+
+    let mut posts = query!(Post, !tags.contains(tag == "asia") && published == true)
+        .load(&conn)
+        .unwrap();
+    posts.sort_by(|p1, p2| p1.id.partial_cmp(&p2.id).unwrap());
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "Mount Doom");
+}
+testall!(not_contains);
+
+fn backref_exists(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blogs = query!(Blog, Post::blog.contains(likes > 15))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Cats");
+
+    // Blogs with no unpublished posts
+    let blogs = query!(Blog, !Post::blog.contains(published == false))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Cats");
+
+    let blogs = query!(Blog, Post::blog.contains(tags.contains("asia")))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Cats");
+}
+testall!(backref_exists);
+
+fn fulltext_search(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, body.matches_text("tiger"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "The Tiger");
+
+    // Words are stemmed
+    let posts = query!(Post, body.matches_text("eagles"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+
+    let posts = query!(
+        Post,
+        body.matches_text("\"very many\"") && published == false
+    )
+    .load(&conn)
+    .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+
+    // The index follows updates
+    let mut post = Post::get(&conn, 2).unwrap();
+    post.body = "Sir Charles has retired to a tiger sanctuary".into();
+    post.save(&conn).unwrap();
+    let mut posts = query!(Post, body.matches_text("tiger"))
+        .load(&conn)
+        .unwrap();
+    posts.sort_by_key(|p| p.id);
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(titles, ["The Tiger", "Sir Charles"]);
+
+    // and deletions
+    Post::get(&conn, 1).unwrap().delete(&conn).unwrap();
+    let posts = query!(Post, body.matches_text("tiger"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+}
+testall!(fulltext_search);
+
+fn fulltext_rank(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut post = Post::get(&conn, 2).unwrap();
+    post.body = "Sir Charles dreams of Everest, Everest and more Everest".into();
+    post.save(&conn).unwrap();
+    let posts = query!(Post, body.matches_text("everest"))
+        .order_with(order!(Post, body.text_rank("everest") desc))
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(titles, ["Sir Charles", "Mt. Everest"]);
+
+    let q: PreparedQuery<Post, (&str,)> = query!(Post, body.matches_text(param!(text)))
+        .prepare(&["text"])
+        .unwrap();
+    let posts = q.load(&conn, ("ring",)).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+}
+testall!(fulltext_rank);
@@ -0,0 +1,109 @@
+#![cfg(feature = "time")]
+use butane::db::Connection;
+use butane::prelude::*;
+use butane::{model, query, ObjectState};
+use time_for_test as time;
+use time_for_test::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Shift {
+    id: i64,
+    day: time::Date,
+    clock_in: time::Time,
+    scheduled: PrimitiveDateTime,
+    started: OffsetDateTime,
+    length: time::Duration,
+    ended: Option<OffsetDateTime>,
+}
+impl Shift {
+    fn new(id: i64, scheduled: PrimitiveDateTime, length: time::Duration) -> Self {
+        Shift {
+            id,
+            day: scheduled.date(),
+            clock_in: scheduled.time(),
+            scheduled,
+            started: scheduled.assume_offset(UtcOffset::from_hms(2, 0, 0).unwrap()),
+            length,
+            ended: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn may_17(hour: u8, minute: u8, second: u8, micro: u32) -> PrimitiveDateTime {
+    let date = Date::from_calendar_date(2022, Month::May, 17).unwrap();
+    date.with_hms_micro(hour, minute, second, micro).unwrap()
+}
+
+fn roundtrip_time(conn: Connection) {
+    let mut shift = Shift::new(
+        1,
+        may_17(9, 30, 15, 0),
+        time::Duration::minutes(45) + time::Duration::microseconds(7),
+    );
+    shift.clock_in = Time::from_hms_micro(9, 31, 2, 250_000).unwrap();
+    shift.started = may_17(9, 31, 2, 250_000).assume_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+    shift.ended = Some(may_17(18, 0, 0, 0).assume_utc());
+    shift.save(&conn).unwrap();
+
+    let shift2 = Shift::get(&conn, 1).unwrap();
+    assert_eq!(shift, shift2);
+    // Timestamps with an offset are stored in UTC
+    assert_eq!(shift2.started.offset(), UtcOffset::UTC);
+    assert_eq!(
+        shift2.started.time(),
+        Time::from_hms_micro(7, 31, 2, 250_000).unwrap()
+    );
+}
+testall!(roundtrip_time);
+
+fn query_time(conn: Connection) {
+    let start = may_17(9, 0, 0, 0);
+    Shift::new(1, start, time::Duration::minutes(30))
+        .save(&conn)
+        .unwrap();
+    Shift::new(
+        2,
+        start + time::Duration::hours(3),
+        time::Duration::hours(2),
+    )
+    .save(&conn)
+    .unwrap();
+    Shift::new(
+        3,
+        start + time::Duration::days(1),
+        time::Duration::minutes(90),
+    )
+    .save(&conn)
+    .unwrap();
+
+    let ids = |q: butane::query::Query<Shift>| -> Vec<i64> {
+        let mut ids: Vec<i64> = q.load(&conn).unwrap().iter().map(|s| s.id).collect();
+        ids.sort();
+        ids
+    };
+    let day = start.date();
+    assert_eq!(ids(query!(Shift, day == { day })), [1, 2]);
+    assert_eq!(ids(query!(Shift, day > { day })), [3]);
+    let noon = Time::from_hms(12, 0, 0).unwrap();
+    assert_eq!(ids(query!(Shift, clock_in >= { noon })), [2]);
+    let cutoff = start + time::Duration::hours(1);
+    assert_eq!(ids(query!(Shift, scheduled > { cutoff })), [2, 3]);
+    // Shifts start at +02:00, so this is between the first two
+    let utc_cutoff = may_17(8, 0, 0, 0).assume_utc();
+    assert_eq!(ids(query!(Shift, started < { utc_cutoff })), [1]);
+    let hour = time::Duration::hours(1);
+    assert_eq!(ids(query!(Shift, length > { hour })), [2, 3]);
+    assert_eq!(ids(query!(Shift, ended.is_none())), [1, 2, 3]);
+}
+testall!(query_time);
+
+fn duration_out_of_range(conn: Connection) {
+    // Durations are stored as a number of microseconds in an i64
+    let mut shift = Shift::new(1, may_17(9, 0, 0, 0), time::Duration::MAX);
+    assert!(shift.save(&conn).is_err());
+}
+testall!(duration_out_of_range);
@@ -1,9 +1,12 @@
+use butane::migrations::adb::{self, ADB};
+use butane::migrations::lint::{self, Severity};
 use butane::migrations::{
-    copy_migration, FsMigrations, MemMigrations, Migration, MigrationMut, Migrations, MigrationsMut,
+    copy_migration, FsMigration, FsMigrations, MemMigrations, Migration, MigrationMut, Migrations,
+    MigrationsMut,
 };
 use butane::query::BoolExpr;
 use butane::{db, db::Connection, db::ConnectionMethods, migrations};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use clap::{Arg, ArgMatches};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
@@ -38,12 +41,30 @@ fn main() {
                 .about("Create a new migration")
                 .arg(
                     Arg::with_name("NAME")
-                        .required(true)
+                        .required_unless("merge")
                         .index(1)
                         .help("Name to use for the migration"),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .help("Create a migration merging migrations which have diverged, such as those created on separate branches."),
+                )
+                .arg(
+                    Arg::with_name("allow-destructive")
+                        .long("allow-destructive")
+                        .help("Create the migration even if it may lose existing data, such as by dropping a table or column."),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("migrate")
+                .about("Apply migrations")
+                .arg(
+                    Arg::with_name("fake")
+                        .long("fake")
+                        .help("Mark migrations as applied without running them. Use when the database schema already matches."),
                 ),
         )
-        .subcommand(clap::SubCommand::with_name("migrate").about("Apply migrations"))
         .subcommand(clap::SubCommand::with_name("list").about("List migrations"))
 				.subcommand(clap::SubCommand::with_name("collapse").about("Replace all migrations with a single migration representing the current model state.").arg(
                     Arg::with_name("NAME")
@@ -90,7 +111,7 @@ fn main() {
     match args.subcommand() {
         ("init", sub_args) => handle_error(init(sub_args)),
         ("makemigration", sub_args) => handle_error(make_migration(sub_args)),
-        ("migrate", _) => handle_error(migrate()),
+        ("migrate", sub_args) => handle_error(migrate(sub_args)),
         ("rollback", sub_args) => handle_error(rollback(sub_args)),
         ("embed", _) => handle_error(embed()),
         ("list", _) => handle_error(list_migrations()),
@@ -155,9 +176,11 @@ fn init(args: Option<&ArgMatches>) -> Result<()> {
 }
 
 fn make_migration(args: Option<&ArgMatches>) -> Result<()> {
+    let merge = args.unwrap().is_present("merge");
     let name_arg = args.and_then(|a| a.value_of("NAME"));
     let name = match name_arg {
         Some(name) => format!("{}_{}", default_name(), name),
+        None if merge => format!("{}_merge", default_name()),
         None => default_name(),
     };
     let mut ms = get_migrations()?;
@@ -167,6 +190,39 @@ fn make_migration(args: Option<&ArgMatches>) -> Result<()> {
     }
     let spec = load_connspec()?;
     let backend = spec.get_backend()?;
+    let heads = ms.heads()?;
+    if merge {
+        if heads.len() < 2 {
+            println!("No diverged migrations to merge");
+            return Ok(());
+        }
+        ms.create_merge_migration(&backend, &name, &heads)?;
+        if CliState::load()?.embedded {
+            embed()?;
+        }
+        println!(
+            "Created migration {} merging {}",
+            name,
+            head_names(&heads).join(", ")
+        );
+        return Ok(());
+    }
+    check_heads(&heads)?;
+    let from_db = match ms.latest() {
+        Some(m) => m.db()?,
+        None => ADB::new(),
+    };
+    let lints = lint::lint(&backend, &adb::diff(&from_db, &ms.current().db()?));
+    for l in &lints {
+        eprintln!("warning: {}", l);
+    }
+    if lint::max_severity(&lints) >= Severity::DataLosing
+        && !args.unwrap().is_present("allow-destructive")
+    {
+        return Err(anyhow::anyhow!(
+            "Migration may lose data. Re-run with --allow-destructive to create it anyway."
+        ));
+    }
     let created = ms.create_migration(&backend, &name, ms.latest().as_ref())?;
     if created {
         let cli_state = CliState::load()?;
@@ -181,14 +237,41 @@ fn make_migration(args: Option<&ArgMatches>) -> Result<()> {
     Ok(())
 }
 
-fn migrate() -> Result<()> {
+fn migrate(args: Option<&ArgMatches>) -> Result<()> {
+    let fake = args.unwrap().is_present("fake");
     let spec = load_connspec()?;
     let mut conn = db::connect(&spec)?;
-    let to_apply = get_migrations()?.unapplied_migrations(&conn)?;
+    let ms = get_migrations()?;
+    check_heads(&ms.heads()?)?;
+    ms.verify_checksums(&conn)?;
+    if !fake {
+        let applied = ms.apply_all(&mut conn)?;
+        println!("Applied {} migrations", applied.len());
+        for m in applied {
+            println!("  {}", m.name());
+        }
+        return Ok(());
+    }
+    let to_apply = ms.unapplied_migrations(&conn)?;
     println!("{} migrations to apply", to_apply.len());
     for m in to_apply {
-        println!("Applying migration {}", m.name());
-        m.apply(&mut conn)?;
+        println!("Marking migration {} as applied", m.name());
+        m.mark_applied(&conn)?;
+    }
+    Ok(())
+}
+
+fn head_names(heads: &[FsMigration]) -> Vec<String> {
+    heads.iter().map(|m| m.name().to_string()).collect()
+}
+
+/// Fail if migrations have diverged and need to be merged first.
+fn check_heads(heads: &[FsMigration]) -> Result<()> {
+    if heads.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "Migrations have diverged ({}). Run `butane makemigration --merge` to merge them.",
+            head_names(heads).join(", ")
+        ));
     }
     Ok(())
 }
@@ -290,13 +373,34 @@ fn list_migrations() -> Result<()> {
     let conn = db::connect(&spec)?;
     let ms = get_migrations()?;
     let unapplied = ms.unapplied_migrations(&conn)?;
+    let history = migrations::applied_migrations(&conn)?;
     let all = ms.all_migrations()?;
     for m in all {
-        let m_state = match unapplied.contains(&m) {
-            true => "not applied",
-            false => "applied",
+        if unapplied.contains(&m) {
+            println!("Migration '{}' (not applied)", m.name());
+            continue;
+        }
+        let applied = history.iter().find(|h| h.name == m.name());
+        let applied_at = match applied.and_then(|h| h.applied_at) {
+            Some(t) => DateTime::<Utc>::from(t)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            None => "unknown time".to_string(),
+        };
+        let checksum = match applied.and_then(|h| h.checksum.as_ref()) {
+            Some(recorded)
+                if Some(recorded) != m.checksum(spec.get_backend()?.name())?.as_ref() =>
+            {
+                ", modified since applied"
+            }
+            _ => "",
         };
-        println!("Migration '{}' ({})", m.name(), m_state);
+        println!(
+            "Migration '{}' (applied {}{})",
+            m.name(),
+            applied_at,
+            checksum
+        );
     }
     Ok(())
 }
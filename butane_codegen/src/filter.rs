@@ -1,13 +1,60 @@
 use super::*;
 use proc_macro2::Span;
 use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::TokenTree;
 use quote::{quote, quote_spanned, ToTokens};
-use syn::{spanned::Spanned, BinOp, Expr, ExprBinary, ExprMethodCall, ExprPath, Ident, LitStr};
+use syn::{
+    spanned::Spanned, BinOp, Expr, ExprBinary, ExprMethodCall, ExprPath, ExprUnary, Ident, LitStr,
+    UnOp,
+};
 
 pub fn for_expr(dbres: &Ident, expr: &Expr) -> TokenStream2 {
     handle_expr(&quote!(<#dbres as butane::DataResult>::DBO::fields()), expr)
 }
 
+/// The `Order` for the tokens of an `order!` macro: an expression
+/// optionally followed by `asc` or `desc` and by `nulls first` or
+/// `nulls last`.
+pub fn for_order(dbres: &Ident, mut tokens: Vec<TokenTree>) -> TokenStream2 {
+    let is_ident = |tt: Option<&TokenTree>, names: &[&str]| match tt {
+        Some(TokenTree::Ident(ident)) => names.iter().any(|n| ident == n),
+        _ => false,
+    };
+    let mut nulls = None;
+    let n = tokens.len();
+    if n > 2
+        && is_ident(tokens.get(n - 2), &["nulls"])
+        && is_ident(tokens.get(n - 1), &["first", "last"])
+    {
+        nulls = Some(ident(&format!("nulls_{}", tokens[n - 1])));
+        tokens.truncate(n - 2);
+    }
+    let mut direction = ident("asc");
+    let n = tokens.len();
+    if n > 1 && is_ident(tokens.last(), &["asc", "desc"]) {
+        direction = ident(&tokens[n - 1].to_string());
+        tokens.truncate(n - 1);
+    }
+    let expr: Expr = match syn::parse2(tokens.into_iter().collect()) {
+        Ok(expr) => expr,
+        Err(err) => return err.to_compile_error(),
+    };
+    let fields = quote!(<#dbres as butane::DataResult>::DBO::fields());
+    let value = match &expr {
+        // A field of a related object
+        Expr::Field(field) => match (field.base.as_ref(), &field.member) {
+            (Expr::Path(fkey), syn::Member::Named(name)) => {
+                let fex = fieldexpr(&fields, &fkey.path);
+                quote!(#fex.related(#fex.fields().#name()))
+            }
+            _ => return make_compile_error!(field.span()=> "Unsupported related field"),
+        },
+        _ => value_expr(&fields, &expr),
+    };
+    let nulls = nulls.map(|nulls| quote!(.#nulls()));
+    quote!(butane::query::Order::#direction(#value)#nulls)
+}
+
 pub fn handle_expr(fields: &impl ToTokens, expr: &Expr) -> TokenStream2 {
     match expr {
         Expr::Binary(binop) => handle_bin_op(fields, binop),
@@ -16,6 +63,8 @@ pub fn handle_expr(fields: &impl ToTokens, expr: &Expr) -> TokenStream2 {
         Expr::Lit(lit) => lit.lit.clone().into_token_stream(),
         Expr::Block(block) => handle_block(&block.block),
         Expr::Group(group) => handle_expr(fields, group.expr.as_ref()),
+        Expr::Paren(paren) => handle_expr(fields, paren.expr.as_ref()),
+        Expr::Unary(unary) => handle_unary(fields, unary),
         _ => {
             let lit = LitStr::new(
                 &format!(
@@ -40,7 +89,27 @@ fn handle_block(block: &syn::Block) -> TokenStream2 {
 }
 
 fn handle_bin_op(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
+    if is_comparison(&binop.op)
+        && (is_arith(&binop.left) || is_func(&binop.left) || refers_to_field(&binop.right))
+    {
+        return handle_value_cmp(fields, binop);
+    }
     let left = handle_expr(fields, &binop.left);
+    if let Some(name) = param_name(&binop.right) {
+        let name = match name {
+            Ok(name) => name,
+            Err(err) => return err.to_compile_error(),
+        };
+        return match binop.op {
+            BinOp::Eq(_) => quote!(#left.eq_param(#name)),
+            BinOp::Ne(_) => quote!(#left.ne_param(#name)),
+            BinOp::Lt(_) => quote!(#left.lt_param(#name)),
+            BinOp::Gt(_) => quote!(#left.gt_param(#name)),
+            BinOp::Le(_) => quote!(#left.le_param(#name)),
+            BinOp::Ge(_) => quote!(#left.ge_param(#name)),
+            _ => quote!(compile_error!("Unsupported binary operator for param!")),
+        };
+    }
     let right = handle_expr(fields, &binop.right);
     match binop.op {
         BinOp::Eq(_) => quote!(#left.eq(&#right)),
@@ -55,24 +124,228 @@ fn handle_bin_op(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
     }
 }
 
+/// Comparison involving more than a single field and a value, such
+/// as `a > b`, `a + b > 10` or `a.lower() == "x"`, using `ValueExpr`.
+fn handle_value_cmp(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
+    let left = value_expr(fields, &binop.left);
+    let right = value_expr(fields, &binop.right);
+    match binop.op {
+        BinOp::Eq(_) => quote!(#left.eq(#right)),
+        BinOp::Ne(_) => quote!(#left.ne(#right)),
+        BinOp::Lt(_) => quote!(#left.lt(#right)),
+        BinOp::Gt(_) => quote!(#left.gt(#right)),
+        BinOp::Le(_) => quote!(#left.le(#right)),
+        BinOp::Ge(_) => quote!(#left.ge(#right)),
+        _ => quote!(compile_error!("Unsupported binary operator")),
+    }
+}
+
+fn value_expr(fields: &impl ToTokens, expr: &Expr) -> TokenStream2 {
+    match expr {
+        Expr::Binary(binop) if is_arith(expr) => {
+            let left = value_expr(fields, &binop.left);
+            let right = value_expr(fields, &binop.right);
+            let op = &binop.op;
+            quote!((#left #op #right))
+        }
+        Expr::MethodCall(mcall) if is_func(expr) => handle_func(fields, mcall),
+        Expr::Path(path) if !path.path.is_ident("None") => {
+            let fex = fieldexpr(fields, &path.path);
+            quote!(#fex.value())
+        }
+        Expr::Lit(lit) => {
+            let lit = &lit.lit;
+            quote!(butane::query::ValueExpr::val(#lit))
+        }
+        Expr::Block(block) => {
+            let stmts = handle_block(&block.block);
+            quote!(butane::query::ValueExpr::val({ #stmts }))
+        }
+        Expr::Group(group) => value_expr(fields, &group.expr),
+        Expr::Paren(paren) => value_expr(fields, &paren.expr),
+        _ => make_compile_error!(expr.span()=> "Unsupported operand '{}'", expr.to_token_stream()),
+    }
+}
+
+/// SQL functions which may be applied to fields with method syntax,
+/// and the number of arguments each takes.
+const FUNCS: &[(&str, usize)] = &[
+    ("lower", 0),
+    ("upper", 0),
+    ("length", 0),
+    ("abs", 0),
+    ("coalesce", 1),
+    ("extract", 1),
+    ("date_trunc", 1),
+    ("text_rank", 1),
+];
+
+fn handle_func(fields: &impl ToTokens, mcall: &ExprMethodCall) -> TokenStream2 {
+    let method = &mcall.method;
+    let name = method.to_string();
+    let nargs = FUNCS
+        .iter()
+        .find(|(func, _)| *func == name)
+        .map_or(0, |(_, nargs)| *nargs);
+    if mcall.args.len() != nargs {
+        return make_compile_error!(mcall.span()=> "expected {} arguments to '{}'", nargs, name);
+    }
+    if name == "text_rank" {
+        // Applies to the full-text index of a field, not its value
+        let fex = fieldexpr(fields, &mcall.receiver);
+        let query = handle_expr(fields, &mcall.args[0]);
+        return quote!(#fex.text_rank(#query));
+    }
+    let receiver = value_expr(fields, &mcall.receiver);
+    match name.as_str() {
+        "coalesce" => {
+            let default = value_expr(fields, &mcall.args[0]);
+            quote!(#receiver.coalesce(#default))
+        }
+        "extract" | "date_trunc" => match date_part(&mcall.args[0]) {
+            Ok(part) => quote!(#receiver.#method(butane::query::DatePart::#part)),
+            Err(err) => err.to_compile_error(),
+        },
+        _ => quote!(#receiver.#method()),
+    }
+}
+
+/// The `DatePart` variant named by a string literal such as `"month"`.
+fn date_part(expr: &Expr) -> syn::Result<Ident> {
+    if let Expr::Lit(syn::ExprLit {
+        lit: syn::Lit::Str(lit),
+        ..
+    }) = expr
+    {
+        let part = match lit.value().as_str() {
+            "year" => "Year",
+            "month" => "Month",
+            "day" => "Day",
+            "hour" => "Hour",
+            "minute" => "Minute",
+            "second" => "Second",
+            _ => "",
+        };
+        if !part.is_empty() {
+            return Ok(Ident::new(part, lit.span()));
+        }
+    }
+    Err(syn::Error::new(
+        expr.span(),
+        "expected one of \"year\", \"month\", \"day\", \"hour\", \"minute\" or \"second\"",
+    ))
+}
+
+fn is_comparison(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Eq(_) | BinOp::Ne(_) | BinOp::Lt(_) | BinOp::Gt(_) | BinOp::Le(_) | BinOp::Ge(_)
+    )
+}
+
+/// Whether `expr` is an arithmetic operation.
+fn is_arith(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary(binop) => matches!(
+            binop.op,
+            BinOp::Add(_) | BinOp::Sub(_) | BinOp::Mul(_) | BinOp::Div(_) | BinOp::Rem(_)
+        ),
+        Expr::Group(group) => is_arith(&group.expr),
+        Expr::Paren(paren) => is_arith(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Whether `expr` is a call of one of the `FUNCS`.
+fn is_func(expr: &Expr) -> bool {
+    match expr {
+        Expr::MethodCall(mcall) => {
+            let name = mcall.method.to_string();
+            FUNCS.iter().any(|(func, _)| *func == name)
+        }
+        Expr::Group(group) => is_func(&group.expr),
+        Expr::Paren(paren) => is_func(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Whether `expr` refers to a field, as a bare identifier or within
+/// arithmetic or a function.
+fn refers_to_field(expr: &Expr) -> bool {
+    match expr {
+        Expr::Path(path) => !path.path.is_ident("None"),
+        Expr::Binary(binop) if is_arith(expr) => {
+            refers_to_field(&binop.left) || refers_to_field(&binop.right)
+        }
+        Expr::MethodCall(mcall) if is_func(expr) => refers_to_field(&mcall.receiver),
+        Expr::Group(group) => refers_to_field(&group.expr),
+        Expr::Paren(paren) => refers_to_field(&paren.expr),
+        _ => false,
+    }
+}
+
+fn handle_unary(fields: &impl ToTokens, unary: &ExprUnary) -> TokenStream2 {
+    let inner = handle_expr(fields, &unary.expr);
+    match unary.op {
+        UnOp::Not(_) => quote!(butane::query::BoolExpr::Not(Box::new(#inner))),
+        _ => quote!(compile_error!("Unsupported unary operator")),
+    }
+}
+
 fn handle_call(fields: &impl ToTokens, mcall: &ExprMethodCall) -> TokenStream2 {
     let method = mcall.method.to_string();
-    match method.as_str() {
-        "contains" | "matches" => {
-            if mcall.args.len() != 1 {
-                return make_compile_error!(mcall.span()=> "expected one argument to '{}'", method);
-            };
-        }
-        _ => (),
+    let nargs = match method.as_str() {
+        "contains" | "matches" | "like" | "ilike" | "is_in" | "matches_text" | "path" => 1,
+        "between" => 2,
+        "is_none" | "is_some" => 0,
+        _ => return make_compile_error!("Unknown method call {}", method),
     };
+    if mcall.args.len() != nargs {
+        return make_compile_error!(mcall.span()=> "expected {} arguments to '{}'", nargs, method);
+    }
+    let args: Vec<&Expr> = mcall.args.iter().collect();
     match method.as_str() {
-        "matches" => handle_in(fields, &mcall.receiver, mcall.args.first().unwrap()),
-        "contains" => handle_contains(fields, &mcall.receiver, mcall.args.first().unwrap()),
-        "like" => handle_like(fields, &mcall.receiver, mcall.args.first().unwrap()),
+        "matches" => handle_in(fields, &mcall.receiver, args[0]),
+        "contains" => handle_contains(fields, &mcall.receiver, args[0]),
+        "like" => handle_like(fields, &mcall.receiver, args[0], ident("like")),
+        "ilike" => handle_like(fields, &mcall.receiver, args[0], ident("ilike")),
+        "matches_text" => handle_like(fields, &mcall.receiver, args[0], ident("matches_text")),
+        "is_in" => handle_is_in(fields, &mcall.receiver, args[0]),
+        "between" => {
+            let fex = fieldexpr(fields, &mcall.receiver);
+            let low = handle_expr(fields, args[0]);
+            let high = handle_expr(fields, args[1]);
+            quote!(#fex.between(&#low, &#high))
+        }
+        "path" => {
+            // A value within a JSON field, to be compared
+            let fex = fieldexpr(fields, &mcall.receiver);
+            let path = args[0];
+            quote!(#fex.path(#path))
+        }
+        "is_none" => {
+            let fex = fieldexpr(fields, &mcall.receiver);
+            quote!(#fex.is_null())
+        }
+        "is_some" => {
+            let fex = fieldexpr(fields, &mcall.receiver);
+            quote!(#fex.is_not_null())
+        }
         _ => make_compile_error!("Unknown method call {}", method),
     }
 }
 
+fn handle_is_in(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    let vals = match expr {
+        // A literal list of values
+        Expr::Array(_) => expr.to_token_stream(),
+        _ => handle_expr(fields, expr),
+    };
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.is_in(#vals))
+}
+
 fn handle_in(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
     let fex = fieldexpr(fields, receiver);
     match expr {
@@ -90,6 +363,11 @@ fn handle_in(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStrea
 }
 
 fn handle_contains(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+    if let Expr::Path(path) = receiver {
+        if path.path.segments.len() == 2 {
+            return handle_backref(&path.path, expr);
+        }
+    }
     let fex = fieldexpr(fields, receiver);
     match expr {
         Expr::Lit(lit) => {
@@ -105,8 +383,34 @@ fn handle_contains(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> Toke
     }
 }
 
-fn handle_like(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+/// `Type::field.contains(expr)`, where `field` is a foreign key of
+/// `Type` referring to the object being filtered.
+fn handle_backref(path: &syn::Path, expr: &Expr) -> TokenStream2 {
+    let ty = &path.segments[0].ident;
+    let field = &path.segments[1].ident;
+    if let Expr::Lit(_) = expr {
+        return make_compile_error!(expr.span()=> "Expected a filter expression on {}", ty);
+    }
+    let fields = quote!(<#ty as butane::DataResult>::DBO::fields());
+    let q = handle_expr(&fields, expr);
+    let span = path.span();
+    quote_spanned!(span=> #fields.#field().referenced_by::<<#ty as butane::DataResult>::DBO>(#q))
+}
+
+fn handle_like(
+    fields: &impl ToTokens,
+    receiver: &Expr,
+    expr: &Expr,
+    method: Ident,
+) -> TokenStream2 {
     let fex = fieldexpr(fields, receiver);
+    if let Some(name) = param_name(expr) {
+        let method_param = ident(&format!("{}_param", method));
+        return match name {
+            Ok(name) => quote!(#fex.#method_param(#name)),
+            Err(err) => err.to_compile_error(),
+        };
+    }
     match expr {
         Expr::Binary(_) => make_compile_error!("Unexpected binary expression as parameter to like"),
         Expr::Call(_) => make_compile_error!("Unexpected call expression as parameter to like"),
@@ -114,11 +418,25 @@ fn handle_like(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStr
             // Arbitrary expression
             let q = handle_expr(fields, expr);
             let span = receiver.span();
-            quote_spanned!(span=> #fex.like(#q))
+            quote_spanned!(span=> #fex.#method(#q))
         }
     }
 }
 
+/// If `expr` is a `param!(name)` placeholder for a named query
+/// parameter, the name as a string literal.
+fn param_name(expr: &Expr) -> Option<syn::Result<LitStr>> {
+    match expr {
+        Expr::Macro(mac) if mac.mac.path.is_ident("param") => Some(
+            mac.mac
+                .parse_body::<Ident>()
+                .map(|name| LitStr::new(&name.to_string(), name.span())),
+        ),
+        Expr::Group(group) => param_name(&group.expr),
+        _ => None,
+    }
+}
+
 fn handle_path(fields: &impl ToTokens, expr: &ExprPath) -> TokenStream2 {
     if expr.path.is_ident("None") {
         return quote!(None);
@@ -33,6 +33,14 @@ mod filter;
 ///    (perhaps implemented as the SQL UNIQUE constraint by some backends).
 /// * `[default]` should be used on fields added by later migrations to avoid errors on existing objects.
 ///     Unnecessary if the new field is an `Option<>`
+/// * `#[fulltext]` on a text field indexes it for full-text search
+///   with `matches_text` in `filter!`. Migrations maintain an FTS5
+///   table with triggers on SQLite and a generated `tsvector` column
+///   with a GIN index on Postgres.
+/// * `#[numeric(precision = 12, scale = 2)]` on a `Decimal` field (with
+///   the `decimal` feature) gives its number of significant digits and
+///   of digits after the decimal point. Without it, a `Decimal` column
+///   holds any `Decimal` exactly.
 ///
 /// For example
 /// ```ignore
@@ -81,42 +89,69 @@ pub fn dataresult(args: TokenStream, input: TokenStream) -> TokenStream {
 
 #[proc_macro]
 pub fn filter(input: TokenStream) -> TokenStream {
+    let (tyid, args) = match type_and_args(input, "filter!(Type, expression)") {
+        Ok(parsed) => parsed,
+        Err(err) => return err.into(),
+    };
+    let expr: TokenStream2 = args.into_iter().collect();
+    let expr: Expr = match syn::parse2(expr) {
+        Ok(expr) => expr,
+        Err(_) => {
+            return make_compile_error!(
+                "Expected filter!(Type, expression) but could not parse expression"
+            )
+            .into()
+        }
+    };
+    filter::for_expr(&tyid, &expr).into()
+}
+
+#[proc_macro]
+pub fn order(input: TokenStream) -> TokenStream {
+    match type_and_args(
+        input,
+        "order!(Type, expression [asc|desc] [nulls first|last])",
+    ) {
+        Ok((tyid, args)) => filter::for_order(&tyid, args).into(),
+        Err(err) => err.into(),
+    }
+}
+
+/// Splits the input of a macro like `filter!(Type, ...)` into the
+/// model type and the tokens following the comma.
+fn type_and_args(
+    input: TokenStream,
+    usage: &str,
+) -> std::result::Result<(Ident, Vec<TokenTree>), TokenStream2> {
     let input: TokenStream2 = input.into();
-    let args: Vec<TokenTree> = input.into_iter().collect();
+    let mut args: Vec<TokenTree> = input.into_iter().collect();
     if args.len() < 2 {
-        return make_compile_error!("Expected filter!(Type, expression)").into();
+        return Err(make_compile_error!("Expected {}", usage));
     }
     let tyid: Ident = match &args[0] {
         TokenTree::Ident(tyid) => tyid.clone(),
         TokenTree::Group(g) => match syn::parse2::<Ident>(g.stream()) {
             Ok(ident) => ident,
             Err(_) => {
-                return make_compile_error!("Unexpected tokens in database object type {:?}", &g)
-                    .into()
+                return Err(make_compile_error!(
+                    "Unexpected tokens in database object type {:?}",
+                    &g
+                ))
             }
         },
         _ => {
-            return make_compile_error!("Unexpected tokens in database object type {:?}", &args[0])
-                .into()
+            return Err(make_compile_error!(
+                "Unexpected tokens in database object type {:?}",
+                &args[0]
+            ))
         }
     };
 
     if let TokenTree::Punct(_) = args[1] {
     } else {
-        return make_compile_error!("Expected filter!(Type, expression)").into();
+        return Err(make_compile_error!("Expected {}", usage));
     }
-
-    let expr: TokenStream2 = args.into_iter().skip(2).collect();
-    let expr: Expr = match syn::parse2(expr) {
-        Ok(expr) => expr,
-        Err(_) => {
-            return make_compile_error!(
-                "Expected filter!(Type, expression) but could not parse expression"
-            )
-            .into()
-        }
-    };
-    filter::for_expr(&tyid, &expr).into()
+    Ok((tyid, args.split_off(2)))
 }
 
 /// Attribute macro which marks a type as being available to butane
@@ -126,6 +161,19 @@ pub fn filter(input: TokenStream) -> TokenStream {
 /// on type aliases, it must be given a parameter specifying the
 /// SqlType it can be converted to.
 ///
+/// With `#[butane_type(Json)]`, the type is stored as JSON using its
+/// serde `Serialize` and `Deserialize` implementations, and
+/// implementations of `ToSql`, `FromSql` and `FieldType` are
+/// generated. Otherwise they must be written by hand.
+///
+/// `#[butane_type(Custom(NAME))]` declares a type stored natively by
+/// Postgres as the type `NAME`. Adding `sqlite = AFFINITY`, one of
+/// `Integer`, `Real`, `Text` or `Blob`, declares how Sqlite stores it,
+/// as in `#[butane_type(Custom(POINT, sqlite = Text))]`, and
+/// `#[butane_type(Custom(sqlite = AFFINITY))]` declares a type for
+/// Sqlite only. See the `custom` module for the values such types
+/// convert to.
+///
 /// E.g.
 /// ```ignore
 /// #[butane_type]
@@ -155,6 +203,50 @@ pub fn butane_type(args: TokenStream, input: TokenStream) -> TokenStream {
         .into()
 }
 
+/// Derive macro implementing [`FieldType`], `ToSql` and `FromSql`
+/// for an enum whose variants have no fields, so that it may be used
+/// as the type of a model field.
+///
+/// By default each variant is stored as text, using its name.
+///
+/// ## Helper Attributes
+/// * `#[butane(rename = "NAME")]` on a variant stores it as `NAME`
+///   instead of the variant's name.
+/// * `#[butane(int)]` on the enum stores each variant as an integer,
+///   its discriminant. Discriminants, if given, must be integer
+///   literals.
+/// * `#[butane(pg_enum)]` or `#[butane(pg_enum = "NAME")]` on the enum
+///   stores it as a native enum type on Postgres, named `NAME` or the
+///   snake-cased name of the enum. Migrations create the type and add
+///   or remove its values as the enum changes. Other backends store
+///   the values as text.
+///
+/// For example
+/// ```ignore
+/// #[derive(FieldType)]
+/// #[butane(pg_enum = "post_status")]
+/// pub enum PostStatus {
+///   Draft,
+///   #[butane(rename = "live")]
+///   Published,
+///   Archived,
+/// }
+///
+/// #[derive(FieldType)]
+/// #[butane(int)]
+/// pub enum Priority {
+///   Low = 1,
+///   Medium,
+///   High,
+/// }
+/// ```
+///
+/// [`FieldType`]: butane_core::FieldType
+#[proc_macro_derive(FieldType, attributes(butane))]
+pub fn derive_field_type(input: TokenStream) -> TokenStream {
+    codegen::derive_field_type_with_migrations(input.into(), &mut migrations_for_dir()).into()
+}
+
 fn migrations_for_dir() -> migrations::FsMigrations {
     migrations::from_root(&migrations_dir())
 }
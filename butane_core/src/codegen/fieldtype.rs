@@ -0,0 +1,266 @@
+//! Implementation of `#[derive(FieldType)]` for enums of unit variants.
+
+use super::*;
+use crate::migrations::adb::AEnum;
+use quote::quote_spanned;
+use std::convert::TryFrom;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Expr, ExprLit, ExprUnary, UnOp};
+
+/// How the variants of an enum are stored.
+enum Repr {
+    /// As text, using the (possibly renamed) name of each variant.
+    Text,
+    /// As an integer, using the discriminant of each variant.
+    Int,
+    /// As a native Postgres enum type with the given name, stored as
+    /// text on other backends.
+    PgEnum(String),
+}
+
+pub fn derive_field_type_with_migrations<M>(
+    input: TokenStream2,
+    ms: &mut impl MigrationsMut<M = M>,
+) -> TokenStream2
+where
+    M: MigrationMut,
+{
+    let input: DeriveInput = match syn::parse2(input) {
+        Ok(input) => input,
+        Err(e) => return e.to_compile_error(),
+    };
+    match derive_field_type(&input, ms) {
+        Ok(tokens) => tokens,
+        Err(e) => e,
+    }
+}
+
+fn derive_field_type<M>(
+    input: &DeriveInput,
+    ms: &mut impl MigrationsMut<M = M>,
+) -> std::result::Result<TokenStream2, TokenStream2>
+where
+    M: MigrationMut,
+{
+    let ident = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(make_compile_error!(ident.span()=>
+                "FieldType can only be derived for enums"
+            ))
+        }
+    };
+    let repr = repr_from_attributes(&input.attrs, ident)?;
+
+    let mut names: Vec<String> = Vec::new();
+    let mut discriminants: Vec<i32> = Vec::new();
+    let mut next_discriminant: i64 = 0;
+    for variant in &data.variants {
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            return Err(make_compile_error!(variant.span()=>
+                "FieldType can only be derived for enums whose variants have no fields"
+            ));
+        }
+        if let Some((_, expr)) = &variant.discriminant {
+            next_discriminant = discriminant_value(expr)?;
+        }
+        let discriminant = i32::try_from(next_discriminant).map_err(
+            |_| make_compile_error!(variant.span()=> "discriminant does not fit in an i32"),
+        )?;
+        discriminants.push(discriminant);
+        next_discriminant += 1;
+        names.push(variant_name(variant)?);
+    }
+    let variants: Vec<&Ident> = data.variants.iter().map(|v| &v.ident).collect();
+
+    let (sqltype, typeid) = match &repr {
+        Repr::Text => (quote!(Text), TypeIdentifier::Ty(SqlType::Text)),
+        Repr::Int => (quote!(Int), TypeIdentifier::Ty(SqlType::Int)),
+        Repr::PgEnum(name) => (quote!(Text), TypeIdentifier::Name(name.clone())),
+    };
+    let (to_sql, from_sql) = match repr {
+        Repr::Int => (
+            quote!(butane::SqlValRef::Int(match self {
+                #(Self::#variants => #discriminants,)*
+            })),
+            quote!(if let butane::SqlValRef::Int(i) = valref {
+                match i {
+                    #(#discriminants => return Ok(Self::#variants),)*
+                    _ => (),
+                }
+            }),
+        ),
+        Repr::Text | Repr::PgEnum(_) => {
+            let variant = if let Repr::PgEnum(_) = repr {
+                quote!(Enum)
+            } else {
+                quote!(Text)
+            };
+            (
+                quote!(butane::SqlValRef::#variant(match self {
+                    #(Self::#variants => #names,)*
+                })),
+                quote!(if let butane::SqlValRef::Text(s) | butane::SqlValRef::Enum(s) = valref {
+                    match s {
+                        #(#names => return Ok(Self::#variants),)*
+                        _ => (),
+                    }
+                }),
+            )
+        }
+    };
+
+    let mut write_migration = || -> crate::Result<()> {
+        if let TypeIdentifier::Name(name) = &typeid {
+            ms.current()
+                .write_enum(&AEnum::new(name.clone(), names.clone()))?;
+        }
+        add_custom_type(ms, ident.to_string(), typeid.clone().into())
+    };
+    write_migration()
+        .map_err(|e| make_compile_error!(ident.span()=> "unable to save type: {}", e))?;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote!(
+        impl #impl_generics butane::ToSql for #ident #ty_generics #where_clause {
+            fn to_sql(&self) -> butane::SqlVal {
+                self.to_sql_ref().into()
+            }
+            fn to_sql_ref(&self) -> butane::SqlValRef<'_> {
+                #to_sql
+            }
+        }
+        impl #impl_generics butane::FromSql for #ident #ty_generics #where_clause {
+            fn from_sql_ref(valref: butane::SqlValRef) -> butane::Result<Self> {
+                #from_sql
+                Err(butane::Error::CannotConvertSqlVal(
+                    butane::SqlType::#sqltype,
+                    valref.into(),
+                ))
+            }
+        }
+        impl #impl_generics butane::FieldType for #ident #ty_generics #where_clause {
+            const SQLTYPE: butane::SqlType = butane::SqlType::#sqltype;
+            type RefType = Self;
+        }
+    ))
+}
+
+/// Reads the representation from a `#[butane(...)]` attribute on the
+/// enum: `int`, `pg_enum` or `pg_enum = "NAME"`. Text is the default.
+fn repr_from_attributes(
+    attrs: &[Attribute],
+    ident: &Ident,
+) -> std::result::Result<Repr, TokenStream2> {
+    let mut repr = Repr::Text;
+    for meta in butane_attribute_metas(attrs)? {
+        repr = match &meta {
+            Meta::Path(path) if path.is_ident("int") => Repr::Int,
+            Meta::Path(path) if path.is_ident("pg_enum") => {
+                Repr::PgEnum(snake_case(&ident.to_string()))
+            }
+            Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(name),
+                ..
+            }) if path.is_ident("pg_enum") => Repr::PgEnum(name.value()),
+            _ => {
+                return Err(make_compile_error!(meta.span()=>
+                    "expected `int`, `pg_enum` or `pg_enum = \"NAME\"`"
+                ))
+            }
+        };
+    }
+    Ok(repr)
+}
+
+/// The name a variant is stored as, which may be given by
+/// `#[butane(rename = "NAME")]`.
+fn variant_name(variant: &syn::Variant) -> std::result::Result<String, TokenStream2> {
+    let mut name = variant.ident.to_string();
+    for meta in butane_attribute_metas(&variant.attrs)? {
+        match &meta {
+            Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(rename),
+                ..
+            }) if path.is_ident("rename") => name = rename.value(),
+            _ => {
+                return Err(make_compile_error!(meta.span()=>
+                    "expected `rename = \"NAME\"`"
+                ))
+            }
+        }
+    }
+    Ok(name)
+}
+
+/// The items of all the `#[butane(...)]` attributes in `attrs`.
+fn butane_attribute_metas(attrs: &[Attribute]) -> std::result::Result<Vec<Meta>, TokenStream2> {
+    let mut metas = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("butane")) {
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(meta) => metas.push(meta),
+                        NestedMeta::Lit(lit) => {
+                            return Err(make_compile_error!(lit.span()=>
+                                "unexpected literal in butane attribute"
+                            ))
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(make_compile_error!(attr.span()=>
+                    "malformed butane attribute"
+                ))
+            }
+        }
+    }
+    Ok(metas)
+}
+
+/// The value of an explicit discriminant, which must be an integer
+/// literal.
+fn discriminant_value(expr: &Expr) -> std::result::Result<i64, TokenStream2> {
+    let value = match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse::<i64>().ok(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => match expr.as_ref() {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(lit), ..
+            }) => lit.base10_parse::<i64>().ok().map(|v| -v),
+            _ => None,
+        },
+        _ => None,
+    };
+    value
+        .ok_or_else(|| make_compile_error!(expr.span()=> "discriminant must be an integer literal"))
+}
+
+/// Converts a type name such as `OrderStatus` to `order_status`. A
+/// run of capitals is kept together as one word, so `HTTPStatus`
+/// becomes `http_status`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = matches!(chars.get(i + 1), Some(n) if n.is_lowercase());
+            if !prev.is_uppercase() || next_lower {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
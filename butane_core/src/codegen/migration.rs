@@ -43,15 +43,25 @@ fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable
             .expect("db object fields must be named")
             .to_string();
         if is_row_field(f) {
-            let col = AColumn::new(
+            let sqltype = get_field_sql_type(f).expect("Malformed numeric attribute");
+            let default = get_default(f).expect("Malformed default attribute");
+            #[cfg(feature = "decimal")]
+            let default = match sqltype {
+                DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric { .. })) => {
+                    default.map(numeric_default)
+                }
+                _ => default,
+            };
+            let mut col = AColumn::new(
                 name,
-                get_deferred_sql_type(&f.ty),
+                sqltype,
                 is_nullable(f),
                 f == &pk,
                 is_auto(f),
                 is_unique(f),
-                get_default(f).expect("Malformed default attribute"),
+                default,
             );
+            col.set_fulltext(is_fulltext(f));
             table.add_column(col);
         } else if is_many_to_many(f) {
             result.push(many_table(&table.name, f, &pk));
@@ -1,6 +1,7 @@
+use crate::custom::{SqlTypeCustom, SqliteAffinity};
 use crate::migrations::adb::{DeferredSqlType, TypeIdentifier, TypeKey};
 use crate::migrations::{MigrationMut, MigrationsMut};
-use crate::{SqlType, SqlVal};
+use crate::{ArrayElement, SqlType, SqlVal};
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro2::{Ident, Span, TokenTree};
 use quote::{quote, ToTokens};
@@ -24,8 +25,11 @@ macro_rules! make_compile_error {
 }
 
 mod dbobj;
+mod fieldtype;
 mod migration;
 
+pub use fieldtype::derive_field_type_with_migrations;
+
 pub fn model_with_migrations<M>(
     input: TokenStream2,
     ms: &mut impl MigrationsMut<M = M>,
@@ -131,21 +135,53 @@ fn parse_butane_type_args(args: TokenStream2) -> std::result::Result<TypeIdentif
             }
         });
     } else if tyid == "Custom" {
-        let customerr = quote!(compile_error!("Unexpected tokens custom in butane_type. Expected butane_type(Custom(name))."););
+        let customerr = quote!(compile_error!("Unexpected tokens custom in butane_type. Expected butane_type(Custom(name)), butane_type(Custom(name, sqlite = affinity)) or butane_type(Custom(sqlite = affinity))."););
         return match args.get(1) {
-            Some(TokenTree::Group(g)) if !g.stream().is_empty() => {
-                let customid = g.stream().into_iter().nth(0).unwrap();
-                match customid {
-                    TokenTree::Ident(tyid) => Ok(TypeIdentifier::Name(tyid.to_string())),
-                    _ => Err(customerr),
-                }
-            }
+            Some(TokenTree::Group(g)) => parse_custom_type_args(g.stream()).ok_or(customerr),
             _ => Err(customerr),
         };
     }
     Err(quote!(compile_error!("Unexpected tokens in butane_type");))
 }
 
+/// Parses the arguments of `Custom` in `#[butane_type(Custom(...))]`:
+/// the name of the type for backends supporting it natively and/or
+/// `sqlite = affinity`.
+fn parse_custom_type_args(args: TokenStream2) -> Option<TypeIdentifier> {
+    let args: Vec<TokenTree> = args.into_iter().collect();
+    let mut name: Option<String> = None;
+    let mut sqlite: Option<SqliteAffinity> = None;
+    for arg in args.split(|tt| matches!(tt, TokenTree::Punct(p) if p.as_char() == ',')) {
+        match arg {
+            [TokenTree::Ident(id)] if name.is_none() && sqlite.is_none() => {
+                name = Some(id.to_string())
+            }
+            [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Ident(affinity)]
+                if key == "sqlite" && eq.as_char() == '=' && sqlite.is_none() =>
+            {
+                sqlite = Some(match affinity.to_string().as_str() {
+                    "Integer" => SqliteAffinity::Integer,
+                    "Real" => SqliteAffinity::Real,
+                    "Text" => SqliteAffinity::Text,
+                    "Blob" => SqliteAffinity::Blob,
+                    _ => return None,
+                })
+            }
+            // Trailing comma
+            [] => (),
+            _ => return None,
+        }
+    }
+    match (name, sqlite) {
+        (Some(name), None) => Some(TypeIdentifier::Name(name)),
+        (Some(name), Some(sqlite)) => Some(TypeIdentifier::Portable { name, sqlite }),
+        (None, Some(sqlite)) => Some(TypeIdentifier::Ty(SqlType::Custom(SqlTypeCustom::Sqlite(
+            sqlite,
+        )))),
+        (None, None) => None,
+    }
+}
+
 pub fn butane_type_with_migrations<M>(
     args: TokenStream2,
     input: TokenStream2,
@@ -155,6 +191,7 @@ where
     M: MigrationMut,
 {
     let mut tyinfo: Option<CustomTypeInfo> = None;
+    let mut impls = TokenStream2::new();
     let type_alias: syn::Result<ItemType> = syn::parse2(input.clone());
     if let Ok(type_alias) = type_alias {
         tyinfo = Some(CustomTypeInfo {
@@ -169,12 +206,19 @@ where
             Ok(sqltype) => sqltype,
             Err(t) => return t,
         };
+        let json = sqltype == TypeIdentifier::Ty(SqlType::Json);
         if let Ok(item) = syn::parse2::<ItemStruct>(input.clone()) {
+            if json {
+                impls = impl_json_type(&item.ident, &item.generics);
+            }
             tyinfo = Some(CustomTypeInfo {
                 name: item.ident.to_string(),
                 ty: sqltype.into(),
             });
         } else if let Ok(item) = syn::parse2::<ItemEnum>(input.clone()) {
+            if json {
+                impls = impl_json_type(&item.ident, &item.generics);
+            }
             tyinfo = Some(CustomTypeInfo {
                 name: item.ident.to_string(),
                 ty: sqltype.into(),
@@ -184,7 +228,7 @@ where
 
     match tyinfo {
         Some(tyinfo) => match add_custom_type(ms, tyinfo.name, tyinfo.ty) {
-            Ok(()) => input,
+            Ok(()) => quote!(#input #impls),
             Err(e) => {
                 eprintln!("unable to save type {}", e);
                 quote!(compile_error!("unable to save type");)
@@ -196,6 +240,32 @@ where
     }
 }
 
+/// Implementations of the traits for a type stored as JSON
+/// (`#[butane_type(Json)]`), using its serde implementations.
+fn impl_json_type(ident: &Ident, generics: &syn::Generics) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote!(
+        impl #impl_generics butane::ToSql for #ident #ty_generics #where_clause {
+            fn to_sql(&self) -> butane::SqlVal {
+                butane::json::to_sql(self)
+            }
+            fn to_sql_ref(&self) -> butane::SqlValRef<'_> {
+                butane::json::to_sql_ref(self)
+            }
+        }
+        impl #impl_generics butane::FromSql for #ident #ty_generics #where_clause {
+            fn from_sql_ref(valref: butane::SqlValRef) -> butane::Result<Self> {
+                butane::json::from_sql_ref(valref)
+            }
+        }
+        impl #impl_generics butane::FieldType for #ident #ty_generics #where_clause {
+            const SQLTYPE: butane::SqlType = butane::SqlType::Json;
+            type RefType = Self;
+        }
+        impl #impl_generics butane::json::JsonType for #ident #ty_generics #where_clause {}
+    )
+}
+
 fn make_ident_literal_str(ident: &Ident) -> LitStr {
     let as_str = format!("{}", ident);
     LitStr::new(&as_str, Span::call_site())
@@ -243,6 +313,8 @@ fn remove_helper_field_attributes(
                         && !a.path.is_ident("sqltype")
                         && !a.path.is_ident("default")
                         && !a.path.is_ident("unique")
+                        && !a.path.is_ident("fulltext")
+                        && !a.path.is_ident("numeric")
                 });
             }
             Ok(fields)
@@ -294,6 +366,13 @@ fn is_unique(field: &Field) -> bool {
     field.attrs.iter().any(|attr| attr.path.is_ident("unique"))
 }
 
+fn is_fulltext(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("fulltext"))
+}
+
 fn fields(ast_struct: &ItemStruct) -> impl Iterator<Item = &Field> {
     ast_struct
         .fields
@@ -400,6 +479,89 @@ fn get_default(field: &Field) -> std::result::Result<Option<SqlVal>, CompilerErr
     Ok(Some(sqlval_from_lit(lit)?))
 }
 
+/// The type of the column for a field, which for a decimal field may
+/// be given by a `#[numeric(precision = 12, scale = 2)]` attribute.
+fn get_field_sql_type(field: &Field) -> std::result::Result<DeferredSqlType, CompilerErrorMsg> {
+    let ty = get_deferred_sql_type(&field.ty);
+    let attr = match field.attrs.iter().find(|a| a.path.is_ident("numeric")) {
+        None => return Ok(ty),
+        Some(attr) => attr,
+    };
+    #[cfg(feature = "decimal")]
+    {
+        if !matches!(
+            ty,
+            DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric { .. }))
+        ) {
+            return Err(make_compile_error!("#[numeric] requires a Decimal field").into());
+        }
+        let mut precision: Option<u8> = None;
+        let mut scale: Option<u8> = None;
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => return Err(make_compile_error!("malformed numeric attribute").into()),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Int(lit),
+                    ..
+                })) if path.is_ident("precision") || path.is_ident("scale") => {
+                    let value = lit.base10_parse::<u8>().map_err(|e| e.to_compile_error())?;
+                    if path.is_ident("precision") {
+                        precision = Some(value);
+                    } else {
+                        scale = Some(value);
+                    }
+                }
+                _ => {
+                    return Err(make_compile_error!(
+                        "expected #[numeric(precision = P, scale = S)]"
+                    )
+                    .into())
+                }
+            }
+        }
+        let precision =
+            precision.ok_or_else(|| make_compile_error!("numeric precision is required"))?;
+        let scale = scale.unwrap_or(0);
+        if precision == 0
+            || precision > crate::decimal::DEFAULT_PRECISION
+            || scale > precision
+            || scale > crate::decimal::DEFAULT_SCALE
+        {
+            return Err(make_compile_error!(
+                "numeric precision must be between 1 and {} and scale between 0 and the lesser of the precision and {}",
+                crate::decimal::DEFAULT_PRECISION,
+                crate::decimal::DEFAULT_SCALE
+            )
+            .into());
+        }
+        Ok(DeferredSqlType::KnownId(TypeIdentifier::Ty(
+            SqlType::Numeric { precision, scale },
+        )))
+    }
+    #[cfg(not(feature = "decimal"))]
+    {
+        let _ = attr;
+        Err(make_compile_error!("#[numeric] requires the decimal feature").into())
+    }
+}
+
+/// Converts a numeric literal given as the default of a decimal
+/// column to a decimal, as literals are otherwise integers or floats.
+#[cfg(feature = "decimal")]
+fn numeric_default(default: SqlVal) -> SqlVal {
+    match &default {
+        SqlVal::Int(_) | SqlVal::Real(_) => default
+            .to_string()
+            .parse::<rust_decimal::Decimal>()
+            .map_or(default, SqlVal::Numeric),
+        _ => default,
+    }
+}
+
 fn some_id(ty: SqlType) -> Option<TypeIdentifier> {
     Some(TypeIdentifier::Ty(ty))
 }
@@ -412,43 +574,145 @@ fn some_known(ty: SqlType) -> Option<DeferredSqlType> {
 fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
     if *ty == parse_quote!(bool) {
         return some_known(SqlType::Bool);
+    } else if *ty == parse_quote!(i16) {
+        return some_known(SqlType::SmallInt);
     } else if *ty == parse_quote!(u8)
         || *ty == parse_quote!(i8)
         || *ty == parse_quote!(u16)
-        || *ty == parse_quote!(i16)
-        || *ty == parse_quote!(u16)
         || *ty == parse_quote!(i32)
     {
         return some_known(SqlType::Int);
     } else if *ty == parse_quote!(u32) || *ty == parse_quote!(i64) {
-        // Future improvement: better support unsigned integers
-        // here. Sqlite has no u64, though Postgres does
         return some_known(SqlType::BigInt);
+    } else if *ty == parse_quote!(u64) {
+        return some_known(SqlType::UBigInt);
+    } else if *ty == parse_quote!(i128) {
+        return some_known(SqlType::HugeInt);
+    } else if *ty == parse_quote!(u128) {
+        return some_known(SqlType::UHugeInt);
     } else if *ty == parse_quote!(f32) || *ty == parse_quote!(f64) {
         return some_known(SqlType::Real);
     } else if *ty == parse_quote!(String) {
         return some_known(SqlType::Text);
     } else if *ty == parse_quote!(Vec<u8>) {
         return some_known(SqlType::Blob);
+    } else if *ty == parse_quote!(IpAddr) || *ty == parse_quote!(std::net::IpAddr) {
+        return some_known(SqlType::Inet);
+    } else if let Some(elem) = get_array_element(ty) {
+        return some_known(SqlType::Array(elem));
+    } else if is_json_type(ty) {
+        return some_known(SqlType::Json);
     }
 
     #[cfg(feature = "datetime")]
     {
         if *ty == parse_quote!(NaiveDateTime) {
             return some_known(SqlType::Timestamp);
+        } else if *ty == parse_quote!(NaiveDate) {
+            return some_known(SqlType::Date);
+        } else if *ty == parse_quote!(NaiveTime) {
+            return some_known(SqlType::Time);
+        } else if *ty == parse_quote!(DateTime<Utc>) || *ty == parse_quote!(DateTime<FixedOffset>) {
+            return some_known(SqlType::TimestampTz);
+        } else if *ty == parse_quote!(chrono::Duration)
+            || *ty == parse_quote!(TimeDelta)
+            || *ty == parse_quote!(chrono::TimeDelta)
+        {
+            // A bare Duration could as well be std::time::Duration
+            return some_known(SqlType::Interval);
+        }
+    }
+
+    #[cfg(feature = "decimal")]
+    {
+        if *ty == parse_quote!(Decimal) || *ty == parse_quote!(rust_decimal::Decimal) {
+            return some_known(SqlType::Numeric {
+                precision: crate::decimal::DEFAULT_PRECISION,
+                scale: crate::decimal::DEFAULT_SCALE,
+            });
+        }
+    }
+
+    #[cfg(feature = "time")]
+    {
+        if *ty == parse_quote!(PrimitiveDateTime) || *ty == parse_quote!(time::PrimitiveDateTime) {
+            return some_known(SqlType::Timestamp);
+        } else if *ty == parse_quote!(OffsetDateTime) || *ty == parse_quote!(time::OffsetDateTime) {
+            return some_known(SqlType::TimestampTz);
+        } else if *ty == parse_quote!(time::Date) {
+            // Bare Date, Time and Duration are too common to assume
+            // they come from time
+            return some_known(SqlType::Date);
+        } else if *ty == parse_quote!(time::Time) {
+            return some_known(SqlType::Time);
+        } else if *ty == parse_quote!(time::Duration) {
+            return some_known(SqlType::Interval);
         }
     }
 
     #[cfg(feature = "uuid")]
     {
         if *ty == parse_quote!(Uuid) || *ty == parse_quote!(uuid::Uuid) {
-            return some_known(SqlType::Blob);
+            return some_known(SqlType::Uuid);
         }
     }
 
     None
 }
 
+/// The type of the elements of `ty` if it is a `Vec` (possibly with
+/// the path `std::vec::Vec` or `alloc::vec::Vec`) stored as an array.
+fn get_array_element(ty: &syn::Type) -> Option<ArrayElement> {
+    let path = match ty {
+        syn::Type::Path(path) => &path.path,
+        _ => return None,
+    };
+    let idents: Vec<String> = path
+        .segments
+        .iter()
+        .map(|seg| seg.ident.to_string())
+        .collect();
+    if !matches!(
+        idents.iter().map(String::as_str).collect::<Vec<&str>>()[..],
+        ["Vec"] | ["std", "vec", "Vec"] | ["alloc", "vec", "Vec"]
+    ) {
+        return None;
+    }
+    let seg = path.segments.last()?;
+    let elem = match &seg.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            match args.args.first()? {
+                syn::GenericArgument::Type(syn::Type::Path(elem)) => &elem.path,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(if elem.is_ident("bool") {
+        ArrayElement::Bool
+    } else if elem.is_ident("i32") {
+        ArrayElement::Int
+    } else if elem.is_ident("i64") {
+        ArrayElement::BigInt
+    } else if elem.is_ident("f64") {
+        ArrayElement::Real
+    } else if elem.is_ident("String") {
+        ArrayElement::Text
+    } else {
+        return None;
+    })
+}
+
+/// Whether `ty` is `Json<T>` (possibly with a path, such as `butane::Json<T>`).
+fn is_json_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map_or(false, |seg| {
+            seg.ident == "Json" && matches!(seg.arguments, syn::PathArguments::AngleBracketed(_))
+        }),
+        _ => false,
+    }
+}
+
 fn has_derive_serialize(attrs: &[Attribute]) -> bool {
     for attr in attrs {
         if let Ok(Meta::List(ml)) = attr.parse_meta() {
@@ -503,12 +767,28 @@ fn sqltype_from_name(name: &Ident) -> Option<TypeIdentifier> {
     match name.as_ref() {
         "Bool" => return some_id(SqlType::Bool),
         "Int" => return some_id(SqlType::Int),
+        "SmallInt" => return some_id(SqlType::SmallInt),
         "BigInt" => return some_id(SqlType::BigInt),
+        "UBigInt" => return some_id(SqlType::UBigInt),
+        "HugeInt" => return some_id(SqlType::HugeInt),
+        "UHugeInt" => return some_id(SqlType::UHugeInt),
         "Real" => return some_id(SqlType::Real),
         "Text" => return some_id(SqlType::Text),
         #[cfg(feature = "datetime")]
         "Timestamp" => return some_id(SqlType::Timestamp),
+        #[cfg(feature = "datetime")]
+        "Date" => return some_id(SqlType::Date),
+        #[cfg(feature = "datetime")]
+        "Time" => return some_id(SqlType::Time),
+        #[cfg(feature = "datetime")]
+        "TimestampTz" => return some_id(SqlType::TimestampTz),
+        #[cfg(feature = "datetime")]
+        "Interval" => return some_id(SqlType::Interval),
         "Blob" => return some_id(SqlType::Blob),
+        "Json" => return some_id(SqlType::Json),
+        #[cfg(feature = "uuid")]
+        "Uuid" => return some_id(SqlType::Uuid),
+        "Inet" => return some_id(SqlType::Inet),
         _ => (),
     }
     if let Some(custom_name) = Regex::new(r"^Custom\((.*)\)$").unwrap().captures(&name) {
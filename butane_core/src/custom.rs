@@ -1,22 +1,86 @@
-//! For supporting additional types with the Pg backend (or other
-//! future backends).
+//! For supporting additional types with the Pg and Sqlite backends.
 //!
-//! For an example of usage, see `butane/tests/custom_pg.rs` in the
-//! source repository. Not supported for the Sqlite backend as Sqlite
-//! supports a very limited set of types to begin with.
+//! Postgres stores a custom type natively, as a
+//! [postgres::types::Type]. Sqlite has only a few storage classes, so
+//! a custom type is stored there with one of them, as a
+//! [SqliteValue] encoded and decoded by the type's
+//! [ToSql](crate::ToSql) and [FromSql](crate::FromSql)
+//! implementations.
+//!
+//! A type may declare a representation for each backend, in which
+//! case it works with both. `#[butane_type(Custom(POINT, sqlite =
+//! Text))]` creates `POINT` columns with Postgres and `TEXT` columns
+//! with Sqlite. Its `ToSql` implementation returns
+//! [SqlValRefCustom::Portable], and its `FromSql` implementation
+//! accepts both [SqlValRefCustom::PgBytes] and
+//! [SqlValRefCustom::Sqlite]. `#[butane_type(Custom(sqlite = Text))]`
+//! declares a type for Sqlite only.
+//!
+//! For examples of usage, see `butane/tests/custom_pg.rs` and
+//! `butane/tests/custom.rs` in the source repository.
 
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
 
+/// The storage class used by the Sqlite backend for a custom type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SqliteAffinity {
+    Integer,
+    Real,
+    Text,
+    Blob,
+}
+impl SqliteAffinity {
+    /// The name of the affinity, as used in a column definition.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SqliteAffinity::Integer => "INTEGER",
+            SqliteAffinity::Real => "REAL",
+            SqliteAffinity::Text => "TEXT",
+            SqliteAffinity::Blob => "BLOB",
+        }
+    }
+}
+
+/// A value of a custom type as stored by the Sqlite backend.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SqliteValue {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+impl SqliteValue {
+    pub fn affinity(&self) -> SqliteAffinity {
+        match self {
+            SqliteValue::Integer(_) => SqliteAffinity::Integer,
+            SqliteValue::Real(_) => SqliteAffinity::Real,
+            SqliteValue::Text(_) => SqliteAffinity::Text,
+            SqliteValue::Blob(_) => SqliteAffinity::Blob,
+        }
+    }
+}
+
 /// For use with [SqlType::Custom](crate::SqlType)
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SqlTypeCustom {
     #[cfg(feature = "pg")]
     Pg(#[serde(with = "pgtypeser")] postgres::types::Type),
+    /// A type supported only by the Sqlite backend.
+    Sqlite(SqliteAffinity),
+    /// A type stored natively by the Pg backend and with the given
+    /// affinity by the Sqlite backend.
+    #[cfg(feature = "pg")]
+    Portable {
+        #[serde(with = "pgtypeser")]
+        pg: postgres::types::Type,
+        sqlite: SqliteAffinity,
+    },
 }
 
 /// For use with [SqlVal::Custom](crate::SqlVal)
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SqlValCustom {
     #[cfg(feature = "pg")]
     Pg {
@@ -24,6 +88,15 @@ pub enum SqlValCustom {
         ty: postgres::types::Type,
         data: Vec<u8>,
     },
+    Sqlite(SqliteValue),
+    /// A value with a representation for both the Pg and Sqlite backends.
+    #[cfg(feature = "pg")]
+    Portable {
+        #[serde(with = "pgtypeser")]
+        ty: postgres::types::Type,
+        data: Vec<u8>,
+        sqlite: SqliteValue,
+    },
 }
 
 impl SqlValCustom {
@@ -34,8 +107,13 @@ impl SqlValCustom {
                 ty: ty.clone(),
                 data: data.as_ref(),
             },
-            #[cfg(not(feature = "pg"))]
-            _ => panic!("SqlValCustom unsupported"),
+            SqlValCustom::Sqlite(v) => SqlValRefCustom::Sqlite(v.clone()),
+            #[cfg(feature = "pg")]
+            SqlValCustom::Portable { ty, data, sqlite } => SqlValRefCustom::PortableBytes {
+                ty: ty.clone(),
+                data: data.as_ref(),
+                sqlite: sqlite.clone(),
+            },
         }
     }
 }
@@ -47,8 +125,11 @@ impl fmt::Display for SqlValCustom {
             SqlValCustom::Pg { ty, .. } => {
                 f.write_str(&format!("<custom PG value of type {}>", ty))
             }
-            #[cfg(not(feature = "pg"))]
-            _ => f.write_str("<unknown custom value>"),
+            SqlValCustom::Sqlite(v) => f.write_str(&format!("<custom Sqlite value {:?}>", v)),
+            #[cfg(feature = "pg")]
+            SqlValCustom::Portable { ty, .. } => {
+                f.write_str(&format!("<custom value of PG type {}>", ty))
+            }
         }
     }
 }
@@ -65,7 +146,7 @@ impl postgres::types::ToSql for SqlValCustom {
     > {
         use bytes::BufMut;
         match self {
-            SqlValCustom::Pg { ty, data } => {
+            SqlValCustom::Pg { ty, data } | SqlValCustom::Portable { ty, data, .. } => {
                 if ty != wanted_ty {
                     return Err(Box::new(crate::Error::Internal(format!(
                         "postgres type mismatch. Wanted {} but have {}",
@@ -74,6 +155,12 @@ impl postgres::types::ToSql for SqlValCustom {
                 }
                 out.put(data.as_ref())
             }
+            SqlValCustom::Sqlite(_) => {
+                return Err(Box::new(crate::Error::IncompatibleCustom(
+                    self.clone(),
+                    crate::db::pg::BACKEND_NAME,
+                )))
+            }
         }
         Ok(postgres::types::IsNull::No)
     }
@@ -104,18 +191,39 @@ pub enum SqlValRefCustom<'a> {
         ty: postgres::types::Type,
         data: &'a [u8],
     },
+    /// The Sqlite backend will return SqlValRef instances of this
+    /// type for custom types. Also used by [ToSql](crate::ToSql)
+    /// implementations of types supported only by the Sqlite backend.
+    Sqlite(SqliteValue),
+    /// Used by [ToSql](crate::ToSql) implementations of types
+    /// supported by both backends. The Pg backend uses `ty` and
+    /// `tosql` as for `PgToSql`, and the Sqlite backend uses `sqlite`.
+    #[cfg(feature = "pg")]
+    Portable {
+        ty: postgres::types::Type,
+        tosql: &'a (dyn postgres::types::ToSql + Sync),
+        sqlite: SqliteValue,
+    },
+    /// Like `Portable`, with the Postgres value already serialized.
+    #[cfg(feature = "pg")]
+    PortableBytes {
+        ty: postgres::types::Type,
+        data: &'a [u8],
+        sqlite: SqliteValue,
+    },
     #[cfg(not(feature = "pg"))]
     Phantom(std::marker::PhantomData<&'a ()>),
 }
 
-impl From<SqlValRefCustom<'_>> for SqlValCustom {
-    fn from(r: SqlValRefCustom) -> SqlValCustom {
-        match r {
+/// Fails if a Postgres value cannot be serialized.
+impl TryFrom<SqlValRefCustom<'_>> for SqlValCustom {
+    type Error = crate::Error;
+    fn try_from(r: SqlValRefCustom) -> crate::Result<SqlValCustom> {
+        Ok(match r {
             #[cfg(feature = "pg")]
             SqlValRefCustom::PgToSql { ty, tosql } => {
                 let mut b = bytes::BytesMut::new();
-                // TODO avoid unwrap
-                tosql.to_sql_checked(&ty, &mut b).unwrap();
+                tosql.to_sql_checked(&ty, &mut b)?;
                 SqlValCustom::Pg {
                     ty,
                     data: b.to_vec(),
@@ -126,11 +234,28 @@ impl From<SqlValRefCustom<'_>> for SqlValCustom {
                 ty,
                 data: data.into(),
             },
+            SqlValRefCustom::Sqlite(v) => SqlValCustom::Sqlite(v),
+            #[cfg(feature = "pg")]
+            SqlValRefCustom::Portable { ty, tosql, sqlite } => {
+                let mut b = bytes::BytesMut::new();
+                tosql.to_sql_checked(&ty, &mut b)?;
+                SqlValCustom::Portable {
+                    ty,
+                    data: b.to_vec(),
+                    sqlite,
+                }
+            }
+            #[cfg(feature = "pg")]
+            SqlValRefCustom::PortableBytes { ty, data, sqlite } => SqlValCustom::Portable {
+                ty,
+                data: data.into(),
+                sqlite,
+            },
             #[cfg(not(feature = "pg"))]
             SqlValRefCustom::Phantom(_) => {
                 panic!("phantom SqlValRefCustom should not be instantiated")
             }
-        }
+        })
     }
 }
 
@@ -1,8 +1,10 @@
 //! Not expected to be called directly by most users. Used by code
 //! generated by `#[model]`, `query!`, and other macros.
 
-use crate::query::{BoolExpr, Expr, Order};
-use crate::{Result, SqlType, SqlVal, SqlValRef};
+use super::Backend;
+use crate::query::{self, BoolExpr, Expr, Join, Order};
+use crate::{DataResult, Result, SqlType, SqlVal, SqlValRef};
+use fallible_iterator::FallibleIterator;
 use std::ops::{Deref, DerefMut};
 use std::vec::Vec;
 
@@ -21,6 +23,96 @@ pub trait ConnectionMethods {
         offset: Option<i32>,
         sort: Option<&[Order]>,
     ) -> Result<RawQueryResult<'a>>;
+    /// Like `query`, but rows may be read from the database as they
+    /// are reached rather than all at once. The connection is
+    /// borrowed until the result is dropped, so cannot be used for
+    /// anything else in the meantime.
+    fn query_iter<'a, 'b, 'c: 'a>(
+        &'c mut self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        let this: &'c Self = self;
+        this.query(table, columns, expr, limit, offset, sort)
+    }
+    /// Like `query`, but returns only distinct rows.
+    fn query_distinct<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>>;
+    /// Like `query`, but loads `columns` from the rows of `table`
+    /// joined with other tables by `joins`. `expr` and `sort` refer
+    /// to columns of `table`.
+    #[allow(clippy::too_many_arguments)]
+    fn query_join<'a, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &[query::Column],
+        joins: Vec<Join>,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>>;
+    /// Like `query`, but binds `params` to the named parameters in
+    /// `expr`. The prepared statement is cached by the connection and
+    /// reused by later calls with the same query.
+    #[allow(clippy::too_many_arguments)]
+    fn query_prepared<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+        params: &[(&'static str, SqlVal)],
+    ) -> Result<RawQueryResult<'a>>;
+    /// Executes the select query `sql` with positional parameters
+    /// `params`, checking that it returns `columns`. Placeholders
+    /// are written as `?` for every backend. Most users will want
+    /// [raw_query][ConnectionMethods::raw_query] instead.
+    fn raw_query_rows<'a, 'b, 'c: 'a>(
+        &'c self,
+        sql: &str,
+        params: &[SqlVal],
+        columns: &'b [Column],
+    ) -> Result<RawQueryResult<'a>>;
+    /// Executes the select query `sql` with positional parameters
+    /// `params`, loading the results as `T`. Placeholders are written
+    /// as `?` for every backend; with Postgres, the jsonb `?`
+    /// operator is written as `??`. The query must return the columns
+    /// of `T` in order.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let posts: Vec<Post> = conn.raw_query(
+    ///     "SELECT id, title, blog FROM Post WHERE likes > ? ORDER BY likes",
+    ///     &[SqlVal::Int(10)],
+    /// )?;
+    /// ```
+    fn raw_query<T: DataResult>(&self, sql: &str, params: &[SqlVal]) -> Result<Vec<T>>
+    where
+        Self: Sized,
+    {
+        self.raw_query_rows(sql, params, T::COLUMNS)?
+            .mapped(T::from_row)
+            .collect()
+    }
+    /// Executes the statement `sql` with positional parameters
+    /// `params`, returning the number of rows affected. Placeholders
+    /// are written as `?` for every backend, as for
+    /// [raw_query][ConnectionMethods::raw_query].
+    fn raw_execute(&self, sql: &str, params: &[SqlVal]) -> Result<usize>;
     fn insert_returning_pk(
         &self,
         table: &str,
@@ -53,6 +145,19 @@ pub trait ConnectionMethods {
     fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize>;
     /// Tests if a table exists in the database.
     fn has_table(&self, table: &str) -> Result<bool>;
+    /// Tests if a column exists in the given table. The default
+    /// implementation assumes that it does, as for tables created by
+    /// this version of butane. Backends override it so that tables
+    /// created by older versions can be upgraded.
+    fn has_column(&self, _table: &str, _column: &str) -> Result<bool> {
+        Ok(true)
+    }
+    /// The backend whose sql this executes, if known. Migrations use
+    /// it when only `ConnectionMethods` are available. The default
+    /// implementation returns `None`.
+    fn sql_backend(&self) -> Option<Box<dyn Backend>> {
+        None
+    }
 }
 
 /// Represents a database column. Most users do not need to use this
@@ -132,6 +237,7 @@ pub type QueryResult<T> = Vec<T>;
 pub trait ConnectionMethodWrapper {
     type Wrapped: ConnectionMethods;
     fn wrapped_connection_methods(&self) -> Result<&Self::Wrapped>;
+    fn wrapped_connection_methods_mut(&mut self) -> Result<&mut Self::Wrapped>;
 }
 
 pub(crate) struct VecRows<T> {
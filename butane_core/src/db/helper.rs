@@ -5,18 +5,90 @@
 
 use super::Column;
 use crate::migrations::adb::{AColumn, TypeIdentifier};
-use crate::query::Expr::{Condition, Placeholder, Val};
-use crate::query::{BoolExpr::*, Expr, Join, Order, OrderDirection};
+use crate::query::Expr::{Condition, Param, Placeholder, Val};
+use crate::query::{
+    ArithOp, BoolExpr::*, CmpOp, DatePart, Expr, Func, Join, NullsOrder, Order, OrderDirection,
+};
 use crate::Error;
 use crate::{query, Result, SqlType, SqlVal};
 use std::borrow::Cow;
 use std::fmt::Write;
 
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
+#[cfg(feature = "datetime")]
+use chrono::{DateTime, Duration, Utc};
+
+/// Format of timestamps with a time zone in SQL literals and, on
+/// backends without a native type, in storage. Values are always in
+/// UTC, so that they sort chronologically as text.
+#[cfg(feature = "datetime")]
+pub const TIMESTAMPTZ_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f+00:00";
+#[cfg(feature = "datetime")]
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+#[cfg(feature = "datetime")]
+pub const TIME_FORMAT: &str = "%H:%M:%S%.f";
 
 pub trait PlaceholderSource {
     fn next_placeholder(&mut self) -> Cow<str>;
+    /// The value bound to the named query parameter `name`, if any.
+    fn param(&self, _name: &str) -> Option<SqlVal> {
+        None
+    }
+}
+
+/// Wraps another `PlaceholderSource`, supplying values for named
+/// query parameters.
+pub struct ParamPlaceholderSource<'p, P> {
+    inner: P,
+    params: &'p [(&'static str, SqlVal)],
+}
+impl<'p, P> ParamPlaceholderSource<'p, P> {
+    pub fn new(inner: P, params: &'p [(&'static str, SqlVal)]) -> Self {
+        ParamPlaceholderSource { inner, params }
+    }
+}
+impl<P: PlaceholderSource> PlaceholderSource for ParamPlaceholderSource<'_, P> {
+    fn next_placeholder(&mut self) -> Cow<'_, str> {
+        self.inner.next_placeholder()
+    }
+    fn param(&self, name: &str) -> Option<SqlVal> {
+        self.params
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.clone())
+    }
+}
+
+/// Checks that `params` gives a value for exactly the named
+/// parameters used in `expr`. Null values are rejected, as sql
+/// comparisons with null never match.
+pub fn check_params(
+    expr: Option<&query::BoolExpr>,
+    params: &[(&'static str, SqlVal)],
+) -> Result<()> {
+    let used = expr.map(query::params).unwrap_or_default();
+    if let Some(name) = used.iter().find(|u| !params.iter().any(|(n, _)| n == *u)) {
+        return Err(Error::InvalidQueryParams(format!(
+            "no value given for parameter {}",
+            name
+        )));
+    }
+    for (name, val) in params {
+        if !used.contains(name) {
+            return Err(Error::InvalidQueryParams(format!(
+                "unknown parameter {}",
+                name
+            )));
+        }
+        if *val == SqlVal::Null {
+            return Err(Error::InvalidQueryParams(format!(
+                "parameter {} is null, which never compares equal to anything",
+                name
+            )));
+        }
+    }
+    Ok(())
 }
 
 /// Writes to `w` the SQL to express the expression given in `expr`. Values contained in `expr` are rendered
@@ -41,8 +113,86 @@ where
             }
         },
         Placeholder => w.write_str(&pls.next_placeholder()),
+        // Unlike values, parameters are never written inline, so
+        // that the sql is the same whatever their values. Values are
+        // checked with check_params first; one which is missing is
+        // left unbound, so that the statement fails.
+        Param(name) => {
+            if let Some(v) = pls.param(name) {
+                values.push(v);
+            }
+            w.write_str(&pls.next_placeholder())
+        }
+        Expr::Arith(a, op, b) => {
+            w.write_char('(').unwrap();
+            f(*a, values, pls, w);
+            let op = match op {
+                ArithOp::Add => " + ",
+                ArithOp::Sub => " - ",
+                ArithOp::Mul => " * ",
+                ArithOp::Div => " / ",
+                ArithOp::Rem => " % ",
+            };
+            w.write_str(op).unwrap();
+            f(*b, values, pls, w);
+            w.write_char(')')
+        }
+        Expr::Func(func, args) => {
+            match func {
+                Func::Lower => w.write_str("lower("),
+                Func::Upper => w.write_str("upper("),
+                Func::Length => w.write_str("length("),
+                Func::Abs => w.write_str("abs("),
+                Func::Coalesce => w.write_str("coalesce("),
+                // EXTRACT gives a (possibly fractional) number
+                Func::Extract(part) => {
+                    write!(w, "CAST(FLOOR(EXTRACT({} FROM ", date_part_name(part))
+                }
+                Func::DateTrunc(part) => write!(w, "date_trunc('{}', ", date_part_name(part)),
+            }
+            .unwrap();
+            args.into_iter().fold("", |sep, arg| {
+                w.write_str(sep).unwrap();
+                f(arg, values, pls, w);
+                ", "
+            });
+            match func {
+                Func::Extract(_) => w.write_str(")) AS INTEGER)"),
+                _ => w.write_char(')'),
+            }
+        }
+        Expr::Related {
+            tbl,
+            col,
+            tbl2,
+            tbl2_pk,
+            col2,
+        } => {
+            // Aliased in case tbl2 is the same table as tbl
+            write!(
+                w,
+                "(SELECT butane_related.{} FROM {} AS butane_related WHERE butane_related.{} = ",
+                col2, tbl2, tbl2_pk
+            )
+            .unwrap();
+            if let Some(tbl) = tbl {
+                write!(w, "{}.", tbl).unwrap();
+            }
+            write!(w, "{})", col)
+        }
+        // The value as text. Backends with other JSON support render
+        // this themselves.
+        Expr::JsonPath { col, path } => write!(w, "{} #>> {}", col, json_path_array(&path)),
+        // Full-text search has no common syntax
+        Expr::TextRank { .. } => unreachable!("backends render full-text search themselves"),
         Condition(c) => match *c {
             True => write!(w, "TRUE"),
+            Cmp(a, op, b) => {
+                f(a, values, pls, w);
+                w.write_str(cmp_op_sql(op)).unwrap();
+                f(b, values, pls, w);
+                Ok(())
+            }
             Eq(col, ex) => match ex {
                 Expr::Val(SqlVal::Null) => write!(w, "{} IS NULL", col),
                 _ => write!(w, "{} = ", col).and_then(|_| Ok(f(ex, values, pls, w))),
@@ -56,11 +206,22 @@ where
             Le(col, ex) => write!(w, "{} <= ", col).and_then(|_| Ok(f(ex, values, pls, w))),
             Ge(col, ex) => write!(w, "{} >= ", col).and_then(|_| Ok(f(ex, values, pls, w))),
             Like(col, ex) => write!(w, "{} like ", col).and_then(|_| Ok(f(ex, values, pls, w))),
+            // Backends with a native case-insensitive LIKE render
+            // this themselves.
+            ILike(col, ex) => {
+                write!(w, "lower({}) like lower(", col).unwrap();
+                f(ex, values, pls, w);
+                write!(w, ")")
+            }
+            // Operands of the logical operators are parenthesized
+            // as they may themselves be compound conditions.
             AllOf(conds) => {
                 let mut remaining = conds.len();
                 for cond in conds {
                     // future perf improvement: figure out a clean way to avoid the extra boxing
+                    w.write_char('(').unwrap();
                     f(Condition(Box::new(cond)), values, pls, w);
+                    w.write_char(')').unwrap();
                     if remaining > 1 {
                         write!(w, " AND ").unwrap();
                         remaining -= 1;
@@ -69,18 +230,24 @@ where
                 Ok(())
             }
             And(a, b) => {
+                w.write_char('(').unwrap();
                 f(Condition(a), values, pls, w);
-                write!(w, " AND ").unwrap();
+                write!(w, ") AND (").unwrap();
                 f(Condition(b), values, pls, w);
-                Ok(())
+                w.write_char(')')
             }
             Or(a, b) => {
+                w.write_char('(').unwrap();
                 f(Condition(a), values, pls, w);
-                write!(w, " OR ").unwrap();
+                write!(w, ") OR (").unwrap();
                 f(Condition(b), values, pls, w);
-                Ok(())
+                w.write_char(')')
+            }
+            Not(a) => {
+                write!(w, "NOT (").unwrap();
+                f(Condition(a), values, pls, w);
+                w.write_char(')')
             }
-            Not(a) => write!(w, "NOT ").and_then(|_| Ok(f(Condition(a), values, pls, w))),
             Subquery {
                 col,
                 tbl2,
@@ -109,6 +276,27 @@ where
                 write!(w, ")").unwrap();
                 Ok(())
             }
+            Exists {
+                col,
+                tbl2,
+                col2,
+                expr,
+            } => {
+                // EXISTS (SELECT 1 FROM <tbl2> WHERE <tbl2>.<col2> = <col> AND (<expr>))
+                let name = query::exists_table(&col, &tbl2);
+                write!(w, "EXISTS (SELECT 1 FROM {}", tbl2).unwrap();
+                if name != tbl2 {
+                    write!(w, " AS {}", name).unwrap();
+                }
+                write!(w, " WHERE {}.{} = ", name, col2).unwrap();
+                sql_column(col, w);
+                w.write_str(" AND (").unwrap();
+                f(Expr::Condition(expr), values, pls, w);
+                write!(w, "))")
+            }
+            MatchesText { .. } => unreachable!("backends render full-text search themselves"),
+            // An empty IN list is a syntax error
+            In(_, vals) if vals.is_empty() => write!(w, "FALSE"),
             In(col, vals) => {
                 write!(w, "{} IN (", col).unwrap();
                 let mut remaining = vals.len();
@@ -126,10 +314,101 @@ where
     .unwrap()
 }
 
-pub fn sql_select(columns: &[Column], table: &str, w: &mut impl Write) {
+pub fn sql_select(columns: &[Column], table: &str, order: Option<&[Order]>, w: &mut impl Write) {
     write!(w, "SELECT ").unwrap();
     list_columns(columns, w);
+    write!(w, " FROM ").unwrap();
+    sql_from(table, order, w);
+}
+
+pub fn sql_select_distinct(
+    columns: &[Column],
+    table: &str,
+    order: Option<&[Order]>,
+    w: &mut impl Write,
+) {
+    write!(w, "SELECT DISTINCT ").unwrap();
+    list_columns(columns, w);
+    write!(w, " FROM ").unwrap();
+    sql_from(table, order, w);
+}
+
+/// Writes `table` as the source of a select ordered by `order`.
+/// Fields of related objects sorted by are added to the table with a
+/// LEFT JOIN, as columns named by `related_order_column`, rather than
+/// looked up with a subquery for each row.
+fn sql_from(table: &str, order: Option<&[Order]>, w: &mut impl Write) {
+    let related: Vec<(usize, RelatedOrder)> = order
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, o)| related_order(table, o).map(|rel| (i, rel)))
+        .collect();
+    if related.is_empty() {
+        write!(w, "{}", table).unwrap();
+        return;
+    }
+    write!(w, "(SELECT {}.*", table).unwrap();
+    for (i, rel) in &related {
+        write!(
+            w,
+            ", butane_related{}.{} AS {}",
+            i,
+            rel.col2,
+            related_order_column(*i)
+        )
+        .unwrap();
+    }
     write!(w, " FROM {}", table).unwrap();
+    for (i, rel) in &related {
+        // Aliased in case tbl2 is the same table as table
+        write!(
+            w,
+            " LEFT JOIN {tbl2} AS butane_related{i} ON butane_related{i}.{pk} = {table}.{col}",
+            tbl2 = rel.tbl2,
+            i = i,
+            pk = rel.tbl2_pk,
+            table = table,
+            col = rel.col
+        )
+        .unwrap();
+    }
+    write!(w, ") AS {}", table).unwrap();
+}
+
+/// A field of a related object sorted by.
+struct RelatedOrder {
+    col: &'static str,
+    tbl2: &'static str,
+    tbl2_pk: &'static str,
+    col2: &'static str,
+}
+
+/// The related field `o` sorts by, if it sorts by one directly.
+/// Related fields within other expressions are looked up with a
+/// subquery instead.
+fn related_order(table: &str, o: &Order) -> Option<RelatedOrder> {
+    match o.expr() {
+        Expr::Related {
+            tbl,
+            col,
+            tbl2,
+            tbl2_pk,
+            col2,
+        } if tbl.is_none() || tbl.as_deref() == Some(table) => Some(RelatedOrder {
+            col,
+            tbl2,
+            tbl2_pk,
+            col2,
+        }),
+        _ => None,
+    }
+}
+
+/// The column added by `sql_from` for the related field sorted by
+/// the order term at `index`.
+fn related_order_column(index: usize) -> String {
+    format!("butane_order{}", index)
 }
 
 pub fn sql_insert_with_placeholders(
@@ -168,6 +447,39 @@ pub fn sql_update_with_placeholders(
     write!(w, " WHERE {} = {}", pkcol.name(), pls.next_placeholder()).unwrap();
 }
 
+/// The SQL for a comparison operator, with surrounding spaces.
+pub fn cmp_op_sql(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => " = ",
+        CmpOp::Ne => " <> ",
+        CmpOp::Lt => " < ",
+        CmpOp::Gt => " > ",
+        CmpOp::Le => " <= ",
+        CmpOp::Ge => " >= ",
+    }
+}
+
+/// `path` into a JSON document as a Postgres text array literal, as
+/// used with the `#>` and `#>>` operators.
+pub fn json_path_array(path: &str) -> String {
+    let segments: Vec<String> = crate::json::path_segments(path)
+        .map(|seg| format!("\"{}\"", seg.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("'{{{}}}'", segments.join(",").replace('\'', "''"))
+}
+
+/// The SQL standard name of a date part, as used with EXTRACT.
+pub fn date_part_name(part: DatePart) -> &'static str {
+    match part {
+        DatePart::Year => "YEAR",
+        DatePart::Month => "MONTH",
+        DatePart::Day => "DAY",
+        DatePart::Hour => "HOUR",
+        DatePart::Minute => "MINUTE",
+        DatePart::Second => "SECOND",
+    }
+}
+
 pub fn sql_limit(limit: i32, w: &mut impl Write) {
     write!(w, " LIMIT {}", limit).unwrap();
 }
@@ -176,18 +488,109 @@ pub fn sql_offset(offset: i32, w: &mut impl Write) {
     write!(w, " OFFSET {}", offset).unwrap();
 }
 
-pub fn sql_order(order: &[Order], w: &mut impl Write) {
-    write!(w, " ORDER BY ").unwrap();
-    order.iter().fold("", |sep, o| {
-        let sql_dir = match o.direction {
-            OrderDirection::Ascending => "ASC",
-            OrderDirection::Descending => "DESC",
+/// How a backend orders null values, for `sql_order`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NullsSyntax {
+    /// NULLS FIRST and NULLS LAST are supported.
+    Native,
+    /// Null values are placed by first sorting on `expr IS NULL`.
+    Emulated,
+}
+
+/// Writes the ORDER BY clause for `order`, using `f` to write the
+/// expressions sorted by.
+pub fn sql_order<F, P, W>(
+    table: &str,
+    order: &[Order],
+    nulls: NullsSyntax,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    sql_order_impl(table, false, order, nulls, f, values, pls, w)
+}
+
+/// Like `sql_order`, but with columns qualified by `table`. Columns
+/// within other expressions are not qualified.
+pub fn sql_order_qualified<F, P, W>(
+    table: &str,
+    order: &[Order],
+    nulls: NullsSyntax,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    sql_order_impl(table, true, order, nulls, f, values, pls, w)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sql_order_impl<F, P, W>(
+    table: &str,
+    qualified: bool,
+    order: &[Order],
+    nulls: NullsSyntax,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    let write_expr = |i: usize, o: &Order, values: &mut Vec<SqlVal>, pls: &mut P, w: &mut W| {
+        let column = match related_order(table, o) {
+            Some(_) => Some(Cow::Owned(related_order_column(i))),
+            None => o.column().map(Cow::Borrowed),
         };
-        write!(w, "{}{} {}", sep, o.column, sql_dir).unwrap();
+        match column {
+            Some(column) if qualified => write!(w, "{}.{}", table, column).unwrap(),
+            Some(column) => w.write_str(&column).unwrap(),
+            None => f(o.expr().clone(), values, pls, w),
+        }
+    };
+    write!(w, " ORDER BY ").unwrap();
+    order.iter().enumerate().fold("", |sep, (i, o)| {
+        w.write_str(sep).unwrap();
+        if let (Some(first_last), NullsSyntax::Emulated) = (o.nulls(), nulls) {
+            // false sorts before true
+            write_expr(i, o, values, pls, w);
+            match first_last {
+                NullsOrder::First => w.write_str(" IS NULL DESC, "),
+                NullsOrder::Last => w.write_str(" IS NULL ASC, "),
+            }
+            .unwrap();
+        }
+        write_expr(i, o, values, pls, w);
+        write!(w, " {}", sql_direction(o.direction())).unwrap();
+        if let (Some(first_last), NullsSyntax::Native) = (o.nulls(), nulls) {
+            match first_last {
+                NullsOrder::First => w.write_str(" NULLS FIRST"),
+                NullsOrder::Last => w.write_str(" NULLS LAST"),
+            }
+            .unwrap();
+        }
         ", "
     });
 }
 
+fn sql_direction(direction: &OrderDirection) -> &'static str {
+    match direction {
+        OrderDirection::Ascending => "ASC",
+        OrderDirection::Descending => "DESC",
+    }
+}
+
 pub fn column_default(col: &AColumn) -> Result<SqlVal> {
     if let Some(val) = col.default() {
         return Ok(val.clone());
@@ -198,16 +601,52 @@ pub fn column_default(col: &AColumn) -> Result<SqlVal> {
     Ok(match col.typeid()? {
         TypeIdentifier::Ty(ty) => match ty {
             SqlType::Bool => SqlVal::Bool(false),
+            SqlType::SmallInt => SqlVal::SmallInt(0),
             SqlType::Int => SqlVal::Int(0),
             SqlType::BigInt => SqlVal::Int(0),
+            SqlType::UBigInt => SqlVal::UBigInt(0),
+            SqlType::HugeInt => SqlVal::HugeInt(0),
+            SqlType::UHugeInt => SqlVal::UHugeInt(0),
             SqlType::Real => SqlVal::Real(0.0),
             SqlType::Text => SqlVal::Text("".to_string()),
             SqlType::Blob => SqlVal::Blob(Vec::new()),
+            SqlType::Json => SqlVal::Json("null".to_string()),
             #[cfg(feature = "datetime")]
             SqlType::Timestamp => SqlVal::Timestamp(NaiveDateTime::from_timestamp(0, 0)),
+            #[cfg(feature = "datetime")]
+            SqlType::Date => SqlVal::Date(NaiveDate::from_ymd(1970, 1, 1)),
+            #[cfg(feature = "datetime")]
+            SqlType::Time => SqlVal::Time(NaiveTime::from_hms(0, 0, 0)),
+            #[cfg(feature = "datetime")]
+            SqlType::TimestampTz => {
+                SqlVal::TimestampTz(DateTime::from_utc(NaiveDateTime::from_timestamp(0, 0), Utc))
+            }
+            #[cfg(feature = "datetime")]
+            SqlType::Interval => SqlVal::Interval(Duration::zero()),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            SqlType::Timestamp => SqlVal::TimeTimestamp(time::PrimitiveDateTime::new(
+                time::OffsetDateTime::UNIX_EPOCH.date(),
+                time::Time::MIDNIGHT,
+            )),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            SqlType::Date => SqlVal::TimeDate(time::OffsetDateTime::UNIX_EPOCH.date()),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            SqlType::Time => SqlVal::TimeTime(time::Time::MIDNIGHT),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            SqlType::TimestampTz => SqlVal::TimeTimestampTz(time::OffsetDateTime::UNIX_EPOCH),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            SqlType::Interval => SqlVal::TimeInterval(time::Duration::ZERO),
+            #[cfg(feature = "decimal")]
+            SqlType::Numeric { .. } => SqlVal::Numeric(rust_decimal::Decimal::ZERO),
+            #[cfg(feature = "uuid")]
+            SqlType::Uuid => SqlVal::Uuid(uuid::Uuid::nil()),
+            SqlType::Inet => SqlVal::Inet(std::net::Ipv4Addr::UNSPECIFIED.into()),
+            SqlType::Array(elem) => SqlVal::Array(elem, Vec::new()),
             SqlType::Custom(_) => return Err(Error::NoCustomDefault),
         },
-        TypeIdentifier::Name(_) => return Err(Error::NoCustomDefault),
+        TypeIdentifier::Name(_) | TypeIdentifier::Portable { .. } => {
+            return Err(Error::NoCustomDefault)
+        }
     })
 }
 
@@ -217,7 +656,7 @@ pub fn list_columns(columns: &[Column], w: &mut impl Write) {
     write!(w, "{}", colnames.as_slice().join(",")).unwrap();
 }
 
-fn sql_joins(joins: Vec<Join>, w: &mut impl Write) {
+pub fn sql_joins(joins: Vec<Join>, w: &mut impl Write) {
     for join in joins {
         match join {
             Join::Inner {
@@ -231,8 +670,57 @@ fn sql_joins(joins: Vec<Join>, w: &mut impl Write) {
                 w.write_str(" = ").unwrap();
                 sql_column(col2, w);
             }
+            Join::Left {
+                join_table,
+                col1,
+                col2,
+            } => {
+                // LEFT JOIN <join_table> ON <col1> = <col2>
+                write!(w, "LEFT JOIN {} ON ", join_table).unwrap();
+                sql_column(col1, w);
+                w.write_str(" = ").unwrap();
+                sql_column(col2, w);
+            }
+        }
+    }
+}
+
+/// Writes the start of a select query loading `columns`, which may
+/// come from several tables, from `table` and the tables joined to it
+/// by `joins`. When there is a filter, the rows of `table` are
+/// selected by a subquery, so that its unqualified column names are
+/// not ambiguous, and `filter` is called to write the condition.
+pub fn sql_select_join(
+    columns: &[query::Column],
+    table: &str,
+    joins: Vec<Join>,
+    filter: Option<impl FnOnce(&mut String)>,
+    order: Option<&[Order]>,
+    w: &mut String,
+) {
+    w.push_str("SELECT ");
+    columns.iter().fold("", |sep, col| {
+        w.push_str(sep);
+        sql_column(col.clone(), w);
+        ","
+    });
+    match filter {
+        Some(filter) => {
+            w.push_str(" FROM (SELECT * FROM ");
+            sql_from(table, order, w);
+            w.push_str(" WHERE ");
+            filter(w);
+            write!(w, ") AS {}", table).unwrap();
+        }
+        None => {
+            w.push_str(" FROM ");
+            sql_from(table, order, w);
         }
     }
+    for join in joins {
+        w.push(' ');
+        sql_joins(vec![join], w);
+    }
 }
 
 fn sql_column(col: query::Column, w: &mut impl Write) {
@@ -248,13 +736,184 @@ pub fn sql_literal_value(val: SqlVal) -> Result<String> {
     match val {
         SqlVal::Null => Ok("NULL".to_string()),
         SqlVal::Bool(val) => Ok(val.to_string()),
+        SmallInt(val) => Ok(val.to_string()),
         Int(val) => Ok(val.to_string()),
         BigInt(val) => Ok(val.to_string()),
+        // Backends storing wide integers as blobs must convert them first
+        UBigInt(val) => Ok(val.to_string()),
+        HugeInt(val) => Ok(val.to_string()),
+        UHugeInt(val) => Ok(val.to_string()),
         Real(val) => Ok(val.to_string()),
-        Text(val) => Ok(format!("'{}'", val)),
+        Text(val) | Enum(val) => Ok(format!("'{}'", val)),
         Blob(val) => Ok(format!("x'{}'", hex::encode_upper(val))),
         #[cfg(feature = "datetime")]
         Timestamp(ndt) => Ok(ndt.format("'%Y-%m-%dT%H:%M:%S%.f'").to_string()),
+        #[cfg(feature = "datetime")]
+        Date(date) => Ok(format!("'{}'", date.format(DATE_FORMAT))),
+        #[cfg(feature = "datetime")]
+        Time(time) => Ok(format!("'{}'", time.format(TIME_FORMAT))),
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => Ok(format!("'{}'", dt.format(TIMESTAMPTZ_FORMAT))),
+        // Backends storing intervals as numbers must convert them first
+        #[cfg(feature = "datetime")]
+        Interval(interval) => Ok(format!(
+            "'{} microseconds'",
+            crate::sqlval::interval_to_micros(&interval)
+        )),
+        #[cfg(feature = "time")]
+        TimeTimestamp(dt) => Ok(format!(
+            "'{}T{}'",
+            crate::time::format_date(&dt.date())?,
+            crate::time::format_time(&dt.time())
+        )),
+        #[cfg(feature = "time")]
+        TimeDate(date) => Ok(format!("'{}'", crate::time::format_date(&date)?)),
+        #[cfg(feature = "time")]
+        TimeTime(time) => Ok(format!("'{}'", crate::time::format_time(&time))),
+        #[cfg(feature = "time")]
+        TimeTimestampTz(dt) => Ok(format!("'{}'", crate::time::format_offset_datetime(&dt)?)),
+        #[cfg(feature = "time")]
+        TimeInterval(interval) => Ok(format!(
+            "'{} microseconds'",
+            crate::time::duration_to_micros(&interval)?
+        )),
+        // Backends storing decimals as text must convert them first
+        #[cfg(feature = "decimal")]
+        Numeric(d) => Ok(d.to_string()),
+        // Backends storing uuids or arrays otherwise must convert them first
+        #[cfg(feature = "uuid")]
+        Uuid(u) => Ok(format!("'{}'", u)),
+        Inet(ip) => Ok(format!("'{}'", ip)),
+        Array(_, vals) => Ok(format!("'{}'", array_literal(&vals)?.replace('\'', "''"))),
+        // JSON commonly contains quotes
+        Json(val) => Ok(format!("'{}'", val.replace('\'', "''"))),
         Custom(val) => Err(Error::LiteralForCustomUnsupported((*val).clone())),
     }
 }
+
+/// The text representation of an array, such as `{1,2}` or
+/// `{"a","b"}`.
+fn array_literal(vals: &[SqlVal]) -> Result<String> {
+    let elems: Vec<String> = vals
+        .iter()
+        .map(|val| match val {
+            SqlVal::Text(text) => Ok(format!(
+                "\"{}\"",
+                text.replace('\\', "\\\\").replace('"', "\\\"")
+            )),
+            val => sql_literal_value(val.clone()),
+        })
+        .collect::<Result<_>>()?;
+    Ok(format!("{{{}}}", elems.join(",")))
+}
+
+/// Checks that the columns returned by a raw query, named `names`,
+/// match the columns expected by the result type.
+pub fn check_raw_columns<'n>(
+    names: impl ExactSizeIterator<Item = &'n str>,
+    columns: &[Column],
+) -> Result<()> {
+    if names.len() != columns.len() {
+        return Err(Error::RawQueryColumns(format!(
+            "query returns {} columns but {} were expected",
+            names.len(),
+            columns.len()
+        )));
+    }
+    for (name, col) in names.zip(columns) {
+        // Postgres folds unquoted names to lowercase
+        if !name.eq_ignore_ascii_case(col.name()) {
+            return Err(Error::RawQueryColumns(format!(
+                "query returns column {} where {} was expected",
+                name,
+                col.name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the `?` placeholders in `sql` to use the numbered
+/// placeholder source `pls`. Question marks within quoted strings,
+/// quoted identifiers, comments and dollar-quoted strings are left
+/// alone, as are the `?|` and `?&` operators. A literal `?`, such as
+/// the jsonb `?` operator, is written as `??`. Backslash escapes in
+/// `E'...'` strings are not recognized.
+pub fn number_placeholders<'s>(sql: &'s str, pls: &mut impl PlaceholderSource) -> Cow<'s, str> {
+    if !sql.contains('?') {
+        return Cow::Borrowed(sql);
+    }
+    let mut out = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        // The length of the text starting at `rest` to copy unchanged
+        let len = match c {
+            '?' => {
+                let next = rest[1..].chars().next();
+                let after = rest[1..].chars().nth(1);
+                match next {
+                    Some('?') => {
+                        out.push('?');
+                        rest = &rest[2..];
+                        continue;
+                    }
+                    // Unless doubled, which is || or && following a placeholder
+                    Some(op @ ('|' | '&')) if after != Some(op) => 2,
+                    _ => {
+                        out.push_str(&pls.next_placeholder());
+                        rest = &rest[1..];
+                        continue;
+                    }
+                }
+            }
+            '\'' | '"' => rest[1..].find(c).map_or(rest.len(), |i| i + 2),
+            '-' if rest.starts_with("--") => rest.find('\n').map_or(rest.len(), |i| i + 1),
+            '/' if rest.starts_with("/*") => block_comment_len(rest),
+            // $1 is a numbered parameter and a$ part of an identifier
+            '$' if !out.ends_with(|p: char| p.is_alphanumeric() || p == '_') => {
+                dollar_quoted_len(rest).unwrap_or(1)
+            }
+            _ => c.len_utf8(),
+        };
+        out.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Cow::Owned(out)
+}
+
+/// The length of the (possibly nested) block comment at the start of `sql`.
+fn block_comment_len(sql: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < sql.len() {
+        if sql[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if sql[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += sql[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    sql.len()
+}
+
+/// The length of the dollar-quoted string, such as `$$...$$` or
+/// `$tag$...$tag$`, at the start of `sql`, or None if `sql` does not
+/// start with one.
+fn dollar_quoted_len(sql: &str) -> Option<usize> {
+    let tag_len = sql[1..].find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if sql[1..].starts_with(|c: char| c.is_ascii_digit()) || !sql[1 + tag_len..].starts_with('$') {
+        return None;
+    }
+    let tag = &sql[..tag_len + 2];
+    Some(
+        sql[tag.len()..]
+            .find(tag)
+            .map_or(sql.len(), |i| 2 * tag.len() + i),
+    )
+}
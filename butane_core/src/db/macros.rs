@@ -1,6 +1,27 @@
 #[macro_export]
 macro_rules! connection_method_wrapper {
     ($ty:path) => {
+        $crate::connection_method_wrapper!(@impl $ty {});
+    };
+    // Also forwards query_iter, which requires a mutable borrow of
+    // the wrapped connection methods.
+    ($ty:path, mut) => {
+        $crate::connection_method_wrapper!(@impl $ty {
+            fn query_iter<'a, 'b, 'c: 'a>(
+                &'c mut self,
+                table: &str,
+                columns: &'b [Column],
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods_mut()?
+                    .query_iter(table, columns, expr, limit, offset, sort)
+            }
+        });
+    };
+    (@impl $ty:path { $($extra:tt)* }) => {
         impl ConnectionMethods for $ty {
             fn execute(&self, sql: &str) -> Result<()> {
                 ConnectionMethods::execute(self.wrapped_connection_methods()?, sql)
@@ -17,6 +38,56 @@ macro_rules! connection_method_wrapper {
                 self.wrapped_connection_methods()?
                     .query(table, columns, expr, limit, offset, sort)
             }
+            fn query_distinct<'a, 'b, 'c: 'a>(
+                &'c self,
+                table: &str,
+                columns: &'b [Column],
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_distinct(table, columns, expr, limit, offset, sort)
+            }
+            fn query_join<'a, 'c: 'a>(
+                &'c self,
+                table: &str,
+                columns: &[$crate::query::Column],
+                joins: Vec<$crate::query::Join>,
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_join(table, columns, joins, expr, limit, offset, sort)
+            }
+            fn query_prepared<'a, 'b, 'c: 'a>(
+                &'c self,
+                table: &str,
+                columns: &'b [Column],
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+                params: &[(&'static str, SqlVal)],
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_prepared(table, columns, expr, limit, offset, sort, params)
+            }
+            fn raw_query_rows<'a, 'b, 'c: 'a>(
+                &'c self,
+                sql: &str,
+                params: &[SqlVal],
+                columns: &'b [Column],
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .raw_query_rows(sql, params, columns)
+            }
+            fn raw_execute(&self, sql: &str, params: &[SqlVal]) -> Result<usize> {
+                self.wrapped_connection_methods()?.raw_execute(sql, params)
+            }
             fn insert_returning_pk(
                 &self,
                 table: &str,
@@ -63,6 +134,13 @@ macro_rules! connection_method_wrapper {
             fn has_table(&self, table: &str) -> Result<bool> {
                 self.wrapped_connection_methods()?.has_table(table)
             }
+            fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+                self.wrapped_connection_methods()?.has_column(table, column)
+            }
+            fn sql_backend(&self) -> Option<Box<dyn $crate::db::Backend>> {
+                self.wrapped_connection_methods().ok()?.sql_backend()
+            }
+            $($extra)*
         }
     };
 }
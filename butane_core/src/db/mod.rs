@@ -13,7 +13,7 @@
 //!    database backend. It is returned by the `connect` method.
 
 use crate::query::BoolExpr;
-use crate::{migrations::adb, Error, Result, SqlVal, SqlValRef};
+use crate::{migrations::adb, Error, Result, SqlType, SqlVal, SqlValRef};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fs;
@@ -46,6 +46,11 @@ pub trait BackendConnection: ConnectionMethods + Send + 'static {
     /// Begin a database transaction. The transaction object must be
     /// used in place of this connection until it is committed and aborted.
     fn transaction(&mut self) -> Result<Transaction>;
+    /// Begin a database transaction which holds a database-wide lock
+    /// used to serialize the application of migrations between
+    /// processes. The lock is released when the transaction is
+    /// committed or aborted.
+    fn migration_transaction(&mut self) -> Result<Transaction<'_>>;
     /// Retrieve the backend backend this connection
     fn backend(&self) -> Box<dyn Backend>;
     fn backend_name(&self) -> &'static str;
@@ -68,11 +73,18 @@ impl Connection {
     fn wrapped_connection_methods(&self) -> Result<&dyn BackendConnection> {
         Ok(self.conn.as_ref())
     }
+    #[allow(clippy::unnecessary_wraps)]
+    fn wrapped_connection_methods_mut(&mut self) -> Result<&mut dyn BackendConnection> {
+        Ok(self.conn.as_mut())
+    }
 }
 impl BackendConnection for Connection {
     fn transaction(&mut self) -> Result<Transaction> {
         self.conn.transaction()
     }
+    fn migration_transaction(&mut self) -> Result<Transaction<'_>> {
+        self.conn.migration_transaction()
+    }
     fn backend(&self) -> Box<dyn Backend> {
         self.conn.backend()
     }
@@ -83,7 +95,7 @@ impl BackendConnection for Connection {
         self.conn.is_closed()
     }
 }
-connection_method_wrapper!(Connection);
+connection_method_wrapper!(Connection, mut);
 
 /// Connection specification. Contains the name of a database backend
 /// and the backend-specific connection string. See [connect][crate::db::connect]
@@ -133,6 +145,24 @@ pub trait Backend {
     fn name(&self) -> &'static str;
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String>;
     fn connect(&self, conn_str: &str) -> Result<Connection>;
+    /// Whether columns are changed in place with `ALTER TABLE` rather
+    /// than by copying the table, for changes other than to the
+    /// primary key or auto-increment status.
+    fn alters_columns_in_place(&self) -> bool {
+        false
+    }
+    /// Whether enums are types in the database, which must be
+    /// recreated when values are removed or reordered.
+    fn has_native_enums(&self) -> bool {
+        false
+    }
+    /// Whether existing values are converted when a column changes
+    /// from type `old` to `new`. Values which are not converted keep
+    /// their old representation, which may not compare with or read
+    /// back as values of the new type.
+    fn converts_column_values(&self, _old: &SqlType, _new: &SqlType) -> bool {
+        true
+    }
 }
 
 impl Backend for Box<dyn Backend> {
@@ -145,6 +175,15 @@ impl Backend for Box<dyn Backend> {
     fn connect(&self, conn_str: &str) -> Result<Connection> {
         self.deref().connect(conn_str)
     }
+    fn alters_columns_in_place(&self) -> bool {
+        self.deref().alters_columns_in_place()
+    }
+    fn has_native_enums(&self) -> bool {
+        self.deref().has_native_enums()
+    }
+    fn converts_column_values(&self, old: &SqlType, new: &SqlType) -> bool {
+        self.deref().converts_column_values(old, new)
+    }
 }
 
 /// Find a backend by name.
@@ -207,6 +246,11 @@ impl<'c> Transaction<'c> {
         let a: &dyn BackendTransaction<'c> = self.trans.as_ref();
         Ok(a.connection_methods())
     }
+    #[allow(clippy::unnecessary_wraps)]
+    fn wrapped_connection_methods_mut(&mut self) -> Result<&mut dyn ConnectionMethods> {
+        let a: &mut dyn BackendTransaction<'c> = self.trans.as_mut();
+        Ok(a.connection_methods_mut())
+    }
 }
 
-connection_method_wrapper!(Transaction<'_>);
+connection_method_wrapper!(Transaction<'_>, mut);
@@ -2,21 +2,28 @@
 use super::connmethods::VecRows;
 use super::helper;
 use super::*;
-use crate::custom::{SqlTypeCustom, SqlValRefCustom};
-use crate::migrations::adb::{AColumn, ATable, Operation, TypeIdentifier, ADB};
+use crate::custom::{SqlTypeCustom, SqlValCustom, SqlValRefCustom};
+use crate::migrations::adb::{AColumn, AEnum, ATable, Operation, TypeIdentifier, ADB};
 use crate::{debug, query};
-use crate::{Result, SqlType, SqlVal, SqlValRef};
+use crate::{ArrayElement, Result, SqlType, SqlVal, SqlValRef};
 use bytes::BufMut;
 #[cfg(feature = "datetime")]
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use hashlink::LruCache;
 use postgres::fallible_iterator::FallibleIterator;
 use postgres::GenericClient;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
 use std::cell::RefCell;
 use std::fmt::Write;
 
 /// The name of the postgres backend.
 pub const BACKEND_NAME: &str = "pg";
 
+/// Key of the advisory lock taken while applying migrations. Chosen
+/// arbitrarily, but must never change.
+const MIGRATION_LOCK_KEY: i64 = 0x6275_7461_6e65; // "butane"
+
 /// Pg [Backend][crate::db::Backend] implementation.
 #[derive(Default)]
 pub struct PgBackend {}
@@ -49,16 +56,26 @@ impl Backend for PgBackend {
             conn: Box::new(self.connect(path)?),
         })
     }
+
+    fn alters_columns_in_place(&self) -> bool {
+        true
+    }
+
+    fn has_native_enums(&self) -> bool {
+        true
+    }
 }
 
 /// Pg database connection.
 pub struct PgConnection {
     conn: RefCell<postgres::Client>,
+    statements: StatementCache,
 }
 impl PgConnection {
     fn open(params: &str) -> Result<Self> {
         Ok(PgConnection {
             conn: RefCell::new(Self::connect(params)?),
+            statements: RefCell::new(LruCache::new(STATEMENT_CACHE_CAPACITY)),
         })
     }
     fn connect(params: &str) -> Result<postgres::Client> {
@@ -78,11 +95,25 @@ impl PgConnectionLike for PgConnection {
     fn cell(&self) -> Result<&RefCell<Self::Client>> {
         Ok(&self.conn)
     }
+    fn cell_mut(&mut self) -> Result<&mut RefCell<Self::Client>> {
+        Ok(&mut self.conn)
+    }
+    fn statement_cache(&self) -> &StatementCache {
+        &self.statements
+    }
 }
 impl BackendConnection for PgConnection {
     fn transaction(&mut self) -> Result<Transaction<'_>> {
         let trans: postgres::Transaction<'_> = self.conn.get_mut().transaction()?;
-        let trans = Box::new(PgTransaction::new(trans));
+        let trans = Box::new(PgTransaction::new(trans, &self.statements));
+        Ok(Transaction::new(trans))
+    }
+    fn migration_transaction(&mut self) -> Result<Transaction<'_>> {
+        let mut trans: postgres::Transaction<'_> = self.conn.get_mut().transaction()?;
+        // Transaction-level advisory locks are released automatically
+        // on commit or rollback.
+        trans.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK_KEY])?;
+        let trans = Box::new(PgTransaction::new(trans, &self.statements));
         Ok(Transaction::new(trans))
     }
     fn backend(&self) -> Box<dyn Backend> {
@@ -111,8 +142,19 @@ fn sqlvalref_for_pg_query<'a>(v: &'a SqlValRef<'a>) -> &'a dyn postgres::types::
 pub trait PgConnectionLike {
     type Client: postgres::GenericClient;
     fn cell(&self) -> Result<&RefCell<Self::Client>>;
+    fn cell_mut(&mut self) -> Result<&mut RefCell<Self::Client>>;
+    fn statement_cache(&self) -> &StatementCache;
 }
 
+/// Statements prepared by a connection, keyed by their sql and
+/// parameter types. The least recently used statements are evicted
+/// once the cache is full. Implementation detail. Semver exempt.
+pub type StatementCache =
+    RefCell<LruCache<(String, Vec<postgres::types::Type>), postgres::Statement>>;
+
+/// Maximum number of prepared statements cached per connection.
+const STATEMENT_CACHE_CAPACITY: usize = 128;
+
 impl<T> ConnectionMethods for T
 where
     T: PgConnectionLike,
@@ -133,54 +175,173 @@ where
         limit: Option<i32>,
         offset: Option<i32>,
         order: Option<&[query::Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        select(self, table, columns, false, expr, limit, offset, order)
+    }
+    fn query_iter<'a, 'b, 'c: 'a>(
+        &'c mut self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        let (stmt, values) =
+            prepare_select(self, table, columns, false, expr, limit, offset, order)?;
+        check_columns(&stmt, columns.len())?;
+        // The mutable borrow of self keeps the connection borrowed
+        // for as long as the rows are read from it.
+        let rows = self
+            .cell_mut()?
+            .get_mut()
+            .query_raw(&stmt, values.iter().map(sqlval_for_pg_query))?;
+        Ok(Box::new(RowStream {
+            rows,
+            current: None,
+        }))
+    }
+    fn query_distinct<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        select(self, table, columns, true, expr, limit, offset, order)
+    }
+    fn query_join<'a, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &[query::Column],
+        joins: Vec<query::Join>,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
     ) -> Result<RawQueryResult<'a>> {
         let mut sqlquery = String::new();
-        helper::sql_select(columns, table, &mut sqlquery);
         let mut values: Vec<SqlVal> = Vec::new();
-        if let Some(expr) = expr {
-            sqlquery.write_str(" WHERE ").unwrap();
-            sql_for_expr(
-                query::Expr::Condition(Box::new(expr)),
+        let mut pls = PgPlaceholderSource::new();
+        helper::sql_select_join(
+            columns,
+            table,
+            joins,
+            expr.map(|expr| {
+                |w: &mut String| {
+                    sql_for_expr(
+                        query::Expr::Condition(Box::new(expr)),
+                        &mut values,
+                        &mut pls,
+                        w,
+                    )
+                }
+            }),
+            order,
+            &mut sqlquery,
+        );
+        if let Some(order) = order {
+            helper::sql_order_qualified(
+                table,
+                order,
+                helper::NullsSyntax::Native,
+                sql_for_expr,
                 &mut values,
-                &mut PgPlaceholderSource::new(),
+                &mut pls,
                 &mut sqlquery,
-            );
+            )
         }
-
-        if let Some(order) = order {
-            helper::sql_order(order, &mut sqlquery)
-        }
-
         if let Some(limit) = limit {
             helper::sql_limit(limit, &mut sqlquery)
         }
-
         if let Some(offset) = offset {
             helper::sql_offset(offset, &mut sqlquery)
         }
-
         if cfg!(feature = "log") {
             debug!("query sql {}", sqlquery);
         }
-        eprintln!("query sql {}", sqlquery);
 
         let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
         let stmt = self
             .cell()?
             .try_borrow_mut()?
             .prepare_typed(&sqlquery, types.as_ref())?;
-        // todo avoid intermediate vec?
-        let rowvec: Vec<postgres::Row> = self
+        query_statement(self, &stmt, &values, columns.len())
+    }
+    fn query_prepared<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+        params: &[(&'static str, SqlVal)],
+    ) -> Result<RawQueryResult<'a>> {
+        helper::check_params(expr.as_ref(), params)?;
+        let (sqlquery, values) = sql_for_query(
+            table,
+            columns,
+            false,
+            expr,
+            limit,
+            offset,
+            order,
+            &mut helper::ParamPlaceholderSource::new(PgPlaceholderSource::new(), params),
+        );
+        if cfg!(feature = "log") {
+            debug!("prepared query sql {}", sqlquery);
+        }
+
+        let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+        let key = (sqlquery, types);
+        let cached = self.statement_cache().try_borrow_mut()?.get(&key).cloned();
+        let stmt = match cached {
+            Some(stmt) => stmt,
+            None => {
+                let stmt = self
+                    .cell()?
+                    .try_borrow_mut()?
+                    .prepare_typed(&key.0, key.1.as_ref())?;
+                self.statement_cache()
+                    .try_borrow_mut()?
+                    .insert(key, stmt.clone());
+                stmt
+            }
+        };
+        query_statement(self, &stmt, &values, columns.len())
+    }
+    fn raw_query_rows<'a, 'b, 'c: 'a>(
+        &'c self,
+        sql: &str,
+        params: &[SqlVal],
+        columns: &'b [Column],
+    ) -> Result<RawQueryResult<'a>> {
+        let sql = helper::number_placeholders(sql, &mut PgPlaceholderSource::new());
+        if cfg!(feature = "log") {
+            debug!("raw query sql {}", sql);
+        }
+        let types: Vec<postgres::types::Type> = params.iter().map(pgtype_for_val).collect();
+        let stmt = self
             .cell()?
             .try_borrow_mut()?
-            .query_raw(&stmt, values.iter().map(sqlval_for_pg_query))?
-            .map_err(Error::Postgres)
-            .map(|r| {
-                check_columns(&r, columns)?;
-                Ok(r)
-            })
-            .collect()?;
-        Ok(Box::new(VecRows::new(rowvec)))
+            .prepare_typed(&sql, types.as_ref())?;
+        helper::check_raw_columns(stmt.columns().iter().map(|c| c.name()), columns)?;
+        query_statement(self, &stmt, params, columns.len())
+    }
+    fn raw_execute(&self, sql: &str, params: &[SqlVal]) -> Result<usize> {
+        let sql = helper::number_placeholders(sql, &mut PgPlaceholderSource::new());
+        if cfg!(feature = "log") {
+            debug!("raw execute sql {}", sql);
+        }
+        let types: Vec<postgres::types::Type> = params.iter().map(pgtype_for_val).collect();
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sql, types.as_ref())?;
+        let params: Vec<&DynToSqlPg> = params.iter().map(|v| v as &DynToSqlPg).collect();
+        let cnt = client.execute(&stmt, params.as_slice())?;
+        Ok(cnt as usize)
     }
     fn insert_returning_pk(
         &self,
@@ -295,15 +456,32 @@ where
         let rows = self.cell()?.try_borrow_mut()?.query(&stmt, &[&table])?;
         Ok(!rows.is_empty())
     }
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        // future improvement, should be schema-aware
+        let stmt = self.cell()?.try_borrow_mut()?.prepare(
+            "SELECT column_name FROM information_schema.columns WHERE table_name=$1 AND column_name=$2;",
+        )?;
+        let rows = self
+            .cell()?
+            .try_borrow_mut()?
+            .query(&stmt, &[&table, &column])?;
+        Ok(!rows.is_empty())
+    }
+    fn sql_backend(&self) -> Option<Box<dyn Backend>> {
+        Some(Box::new(PgBackend::new()))
+    }
 }
 
 struct PgTransaction<'c> {
     trans: Option<RefCell<postgres::Transaction<'c>>>,
+    // Statements are prepared on the connection, so outlive the transaction.
+    statements: &'c StatementCache,
 }
 impl<'c> PgTransaction<'c> {
-    fn new(trans: postgres::Transaction<'c>) -> Self {
+    fn new(trans: postgres::Transaction<'c>, statements: &'c StatementCache) -> Self {
         PgTransaction {
             trans: Some(RefCell::new(trans)),
+            statements,
         }
     }
     fn get(&self) -> Result<&RefCell<postgres::Transaction<'c>>> {
@@ -321,6 +499,12 @@ impl<'c> PgConnectionLike for PgTransaction<'c> {
     fn cell(&self) -> Result<&RefCell<Self::Client>> {
         self.get()
     }
+    fn cell_mut(&mut self) -> Result<&mut RefCell<Self::Client>> {
+        self.trans.as_mut().ok_or_else(Self::already_consumed)
+    }
+    fn statement_cache(&self) -> &StatementCache {
+        self.statements
+    }
 }
 
 impl<'c> BackendTransaction<'c> for PgTransaction<'c> {
@@ -374,23 +558,85 @@ impl<'a> postgres::types::ToSql for SqlValRef<'a> {
         use SqlValRef::*;
         match self {
             Bool(b) => b.to_sql_checked(requested_ty, out),
+            // Columns of i16 fields were INTEGER before SMALLINT was used
+            SmallInt(i) if *requested_ty == postgres::types::Type::INT4 => {
+                i32::from(*i).to_sql_checked(requested_ty, out)
+            }
+            SmallInt(i) => i.to_sql_checked(requested_ty, out),
             Int(i) => i.to_sql_checked(requested_ty, out),
             BigInt(i) => i.to_sql_checked(requested_ty, out),
+            UBigInt(i) => numeric_int_to_sql(false, u128::from(*i), requested_ty, out),
+            HugeInt(i) => numeric_int_to_sql(*i < 0, i.unsigned_abs(), requested_ty, out),
+            UHugeInt(i) => numeric_int_to_sql(false, *i, requested_ty, out),
             Real(r) => r.to_sql_checked(requested_ty, out),
             Text(t) => t.to_sql_checked(requested_ty, out),
+            Enum(t) => match requested_ty.kind() {
+                // Enum values are sent as their text
+                postgres::types::Kind::Enum(_) => {
+                    out.put(t.as_bytes());
+                    Ok(postgres::types::IsNull::No)
+                }
+                _ => t.to_sql_checked(requested_ty, out),
+            },
             Blob(b) => b.to_sql_checked(requested_ty, out),
             #[cfg(feature = "datetime")]
             Timestamp(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Date(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Time(t) => t.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            TimestampTz(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Interval(interval) => {
+                check_type_match(&postgres::types::Type::INTERVAL, requested_ty)?;
+                interval_to_sql(crate::sqlval::interval_to_micros(interval), out);
+                Ok(postgres::types::IsNull::No)
+            }
+            #[cfg(feature = "time")]
+            TimeTimestamp(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "time")]
+            TimeDate(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "time")]
+            TimeTime(t) => t.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "time")]
+            TimeTimestampTz(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "time")]
+            TimeInterval(interval) => {
+                check_type_match(&postgres::types::Type::INTERVAL, requested_ty)?;
+                interval_to_sql(crate::time::duration_to_micros(interval)?, out);
+                Ok(postgres::types::IsNull::No)
+            }
+            #[cfg(feature = "decimal")]
+            Numeric(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "uuid")]
+            Uuid(u) => {
+                check_type_match(&postgres::types::Type::UUID, requested_ty)?;
+                out.put_slice(u.as_bytes());
+                Ok(postgres::types::IsNull::No)
+            }
+            Inet(ip) => ip.to_sql_checked(requested_ty, out),
+            Array(_, vals) => {
+                let vals: Vec<SqlValRef> = vals.iter().map(SqlValRef::from).collect();
+                vals.to_sql_checked(requested_ty, out)
+            }
+            Json(json) => json_to_sql(json, requested_ty, out),
             Null => Ok(postgres::types::IsNull::Yes),
-            Custom(SqlValRefCustom::PgToSql { ty, tosql }) => {
+            Custom(SqlValRefCustom::PgToSql { ty, tosql })
+            | Custom(SqlValRefCustom::Portable { ty, tosql, .. }) => {
                 check_type_match(ty, requested_ty)?;
                 tosql.to_sql_checked(requested_ty, out)
             }
-            Custom(SqlValRefCustom::PgBytes { ty, data }) => {
+            Custom(SqlValRefCustom::PgBytes { ty, data })
+            | Custom(SqlValRefCustom::PortableBytes { ty, data, .. }) => {
                 check_type_match(ty, requested_ty)?;
                 out.put(*data);
                 Ok(postgres::types::IsNull::No)
             }
+            Custom(SqlValRefCustom::Sqlite(v)) => Err(Box::new(Error::IncompatibleCustom(
+                SqlValCustom::Sqlite(v.clone()),
+                BACKEND_NAME,
+            ))),
         }
     }
     fn accepts(_ty: &postgres::types::Type) -> bool {
@@ -402,6 +648,178 @@ impl<'a> postgres::types::ToSql for SqlValRef<'a> {
     postgres::types::to_sql_checked!();
 }
 
+/// Writes serialized JSON as `requested_ty`, which may be JSONB,
+/// JSON or text.
+fn json_to_sql(
+    json: &str,
+    requested_ty: &postgres::types::Type,
+    out: &mut bytes::BytesMut,
+) -> std::result::Result<postgres::types::IsNull, Box<dyn std::error::Error + 'static + Sync + Send>>
+{
+    use postgres::types::Type;
+    match *requested_ty {
+        Type::JSONB => {
+            // JSONB binary format version
+            out.put_u8(1);
+            out.put(json.as_bytes());
+            Ok(postgres::types::IsNull::No)
+        }
+        Type::JSON => {
+            out.put(json.as_bytes());
+            Ok(postgres::types::IsNull::No)
+        }
+        _ => postgres::types::ToSql::to_sql_checked(&json, requested_ty, out),
+    }
+}
+
+/// Writes an interval of `micros` microseconds in the binary format of
+/// postgres: microseconds, days and months.
+#[cfg(any(feature = "datetime", feature = "time"))]
+fn interval_to_sql(micros: i64, out: &mut bytes::BytesMut) {
+    out.put_i64(micros);
+    out.put_i32(0);
+    out.put_i32(0);
+}
+
+/// Reads an interval in the binary format of postgres as a number of
+/// microseconds. A day is taken to be 24 hours. Intervals with months
+/// are rejected, as a month has no fixed length; butane never writes
+/// them.
+#[cfg(any(feature = "datetime", feature = "time"))]
+fn interval_from_sql(
+    raw: &[u8],
+) -> std::result::Result<i64, Box<dyn std::error::Error + 'static + Sync + Send>> {
+    use std::convert::TryInto;
+    if raw.len() != 16 {
+        return Err("invalid interval encoding".into());
+    }
+    let micros = i64::from_be_bytes(raw[0..8].try_into()?);
+    let days = i32::from_be_bytes(raw[8..12].try_into()?);
+    let months = i32::from_be_bytes(raw[12..16].try_into()?);
+    if months != 0 {
+        return Err("intervals with months cannot be converted to a duration".into());
+    }
+    i64::from(days)
+        .checked_mul(24 * 60 * 60 * 1_000_000)
+        .and_then(|days| days.checked_add(micros))
+        .ok_or_else(|| "interval out of range".into())
+}
+
+/// Writes an integer in the binary format of a postgres NUMERIC: the
+/// number of base 10000 digits, the weight of the first digit, the
+/// sign, the number of decimal digits after the point and the digits.
+/// rust_decimal's codec is not used as its 96 bit mantissa cannot hold
+/// every i128 or u128, and it is only present with the `decimal` feature.
+fn numeric_int_to_sql(
+    negative: bool,
+    magnitude: u128,
+    requested_ty: &postgres::types::Type,
+    out: &mut bytes::BytesMut,
+) -> std::result::Result<postgres::types::IsNull, Box<dyn std::error::Error + 'static + Sync + Send>>
+{
+    check_type_match(&postgres::types::Type::NUMERIC, requested_ty)?;
+    let mut digits: Vec<i16> = Vec::new();
+    let mut rest = magnitude;
+    while rest > 0 {
+        digits.push((rest % 10000) as i16);
+        rest /= 10000;
+    }
+    let weight = digits.len() as i16 - 1;
+    // Trailing zero digits are implied by the weight
+    let first_nonzero = digits.iter().position(|d| *d != 0).unwrap_or(digits.len());
+    digits.drain(..first_nonzero);
+    out.put_i16(digits.len() as i16);
+    out.put_i16(weight.max(0));
+    out.put_u16(if negative { 0x4000 } else { 0 });
+    out.put_u16(0);
+    for digit in digits.iter().rev() {
+        out.put_i16(*digit);
+    }
+    Ok(postgres::types::IsNull::No)
+}
+
+/// An integer read from a postgres NUMERIC.
+struct NumericInt {
+    negative: bool,
+    magnitude: u128,
+}
+impl NumericInt {
+    fn to_sqlvalref(&self, ty: &SqlType) -> Result<SqlValRef<'static>> {
+        use std::convert::TryFrom;
+        let unsigned = || {
+            if self.negative {
+                Err(Error::OutOfRange)
+            } else {
+                Ok(self.magnitude)
+            }
+        };
+        Ok(match ty {
+            SqlType::UBigInt => {
+                SqlValRef::UBigInt(u64::try_from(unsigned()?).map_err(|_| Error::OutOfRange)?)
+            }
+            SqlType::HugeInt if self.negative => {
+                if self.magnitude > 1 << 127 {
+                    return Err(Error::OutOfRange);
+                }
+                // Wraps for i128::MIN, whose magnitude is not an i128
+                SqlValRef::HugeInt((self.magnitude as i128).wrapping_neg())
+            }
+            SqlType::HugeInt => {
+                SqlValRef::HugeInt(i128::try_from(self.magnitude).map_err(|_| Error::OutOfRange)?)
+            }
+            SqlType::UHugeInt => SqlValRef::UHugeInt(unsigned()?),
+            _ => return Err(Error::Internal(format!("{} is not a wide integer", ty))),
+        })
+    }
+}
+impl<'a> postgres::types::FromSql<'a> for NumericInt {
+    fn from_sql(
+        _ty: &postgres::types::Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
+        use std::convert::TryInto;
+        if raw.len() < 8 {
+            return Err("invalid numeric encoding".into());
+        }
+        let ndigits = i16::from_be_bytes(raw[0..2].try_into()?);
+        let weight = i16::from_be_bytes(raw[2..4].try_into()?);
+        let negative = match u16::from_be_bytes(raw[4..6].try_into()?) {
+            0 => false,
+            0x4000 => true,
+            _ => return Err("numeric is not a number".into()),
+        };
+        let digits = &raw[8..];
+        if ndigits < 0 || digits.len() != 2 * ndigits as usize {
+            return Err("invalid numeric encoding".into());
+        }
+        let mut magnitude: u128 = 0;
+        for (i, digit) in digits.chunks(2).enumerate() {
+            let digit = i16::from_be_bytes(digit.try_into()?);
+            if i as i16 > weight {
+                if digit != 0 {
+                    return Err(Box::new(Error::OutOfRange));
+                }
+                continue;
+            }
+            magnitude = magnitude
+                .checked_mul(10000)
+                .and_then(|m| m.checked_add(digit as u128))
+                .ok_or(Error::OutOfRange)?;
+        }
+        // Trailing zero digits are not stored
+        for _ in (ndigits.max(0))..=weight {
+            magnitude = magnitude.checked_mul(10000).ok_or(Error::OutOfRange)?;
+        }
+        Ok(NumericInt {
+            negative,
+            magnitude,
+        })
+    }
+    fn accepts(ty: &postgres::types::Type) -> bool {
+        *ty == postgres::types::Type::NUMERIC
+    }
+}
+
 fn check_type_match(
     ty1: &postgres::types::Type,
     ty2: &postgres::types::Type,
@@ -424,6 +842,7 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
         use postgres::types::Type;
         match *ty {
             Type::BOOL => Ok(SqlValRef::Bool(bool::from_sql(ty, raw)?)),
+            Type::INT2 => Ok(SqlValRef::SmallInt(i16::from_sql(ty, raw)?)),
             Type::INT4 => Ok(SqlValRef::Int(i32::from_sql(ty, raw)?)),
             Type::INT8 => Ok(SqlValRef::BigInt(i64::from_sql(ty, raw)?)),
             Type::FLOAT8 => Ok(SqlValRef::Real(f64::from_sql(ty, raw)?)),
@@ -435,6 +854,52 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
             )?)),
             #[cfg(feature = "datetime")]
             Type::TIMESTAMP => Ok(SqlValRef::Timestamp(NaiveDateTime::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::DATE => Ok(SqlValRef::Date(NaiveDate::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::TIME => Ok(SqlValRef::Time(NaiveTime::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::TIMESTAMPTZ => Ok(SqlValRef::TimestampTz(DateTime::<Utc>::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::INTERVAL => Ok(SqlValRef::Interval(Duration::microseconds(
+                interval_from_sql(raw)?,
+            ))),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            Type::TIMESTAMP => Ok(SqlValRef::TimeTimestamp(time::PrimitiveDateTime::from_sql(
+                ty, raw,
+            )?)),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            Type::DATE => Ok(SqlValRef::TimeDate(time::Date::from_sql(ty, raw)?)),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            Type::TIME => Ok(SqlValRef::TimeTime(time::Time::from_sql(ty, raw)?)),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            Type::TIMESTAMPTZ => Ok(SqlValRef::TimeTimestampTz(time::OffsetDateTime::from_sql(
+                ty, raw,
+            )?)),
+            #[cfg(all(feature = "time", not(feature = "datetime")))]
+            Type::INTERVAL => Ok(SqlValRef::TimeInterval(time::Duration::microseconds(
+                interval_from_sql(raw)?,
+            ))),
+            #[cfg(feature = "decimal")]
+            Type::NUMERIC => Ok(SqlValRef::Numeric(Decimal::from_sql(ty, raw)?)),
+            #[cfg(feature = "uuid")]
+            Type::UUID => Ok(SqlValRef::Uuid(uuid::Uuid::from_slice(raw)?)),
+            Type::INET => Ok(SqlValRef::Inet(std::net::IpAddr::from_sql(ty, raw)?)),
+            _ if array_element(ty).is_some() => {
+                let vals: Vec<SqlValRef> = postgres::types::FromSql::from_sql(ty, raw)?;
+                Ok(SqlValRef::Array(
+                    array_element(ty).unwrap(),
+                    Cow::Owned(vals.into_iter().map(SqlVal::from).collect()),
+                ))
+            }
+            Type::JSONB => match raw.split_first() {
+                Some((1, json)) => Ok(SqlValRef::Json(Cow::Borrowed(std::str::from_utf8(json)?))),
+                _ => Err("unsupported JSONB encoding version".into()),
+            },
+            Type::JSON => Ok(SqlValRef::Json(Cow::Borrowed(std::str::from_utf8(raw)?))),
+            _ if matches!(ty.kind(), postgres::types::Kind::Enum(_)) => {
+                Ok(SqlValRef::Text(std::str::from_utf8(raw)?))
+            }
             _ => Ok(SqlValRef::Custom(SqlValRefCustom::PgBytes {
                 ty: ty.clone(),
                 data: raw,
@@ -458,12 +923,12 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
     }
 }
 
-fn check_columns(row: &postgres::Row, cols: &[Column]) -> Result<()> {
-    if cols.len() != row.len() {
+fn check_columns(stmt: &postgres::Statement, ncols: usize) -> Result<()> {
+    if ncols != stmt.columns().len() {
         Err(Error::Internal(format!(
             "postgres returns columns {} doesn't match requested columns {}",
-            row.len(),
-            cols.len()
+            stmt.columns().len(),
+            ncols
         )))
     } else {
         Ok(())
@@ -471,30 +936,227 @@ fn check_columns(row: &postgres::Row, cols: &[Column]) -> Result<()> {
 }
 
 impl BackendRow for postgres::Row {
-    fn get(&self, idx: usize, _ty: SqlType) -> Result<SqlValRef> {
-        Ok(self.try_get(idx)?)
+    fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef> {
+        match ty {
+            // NUMERIC is otherwise read as a decimal, if at all
+            SqlType::UBigInt | SqlType::HugeInt | SqlType::UHugeInt => {
+                match self.try_get::<_, Option<NumericInt>>(idx)? {
+                    Some(val) => val.to_sqlvalref(&ty),
+                    None => Ok(SqlValRef::Null),
+                }
+            }
+            _ => Ok(self.try_get(idx)?),
+        }
     }
     fn len(&self) -> usize {
         postgres::Row::len(self)
     }
 }
 
-fn sql_for_expr<W>(
-    expr: query::Expr,
-    values: &mut Vec<SqlVal>,
-    pls: &mut PgPlaceholderSource,
-    w: &mut W,
-) where
+fn sql_for_expr<P, W>(expr: query::Expr, values: &mut Vec<SqlVal>, pls: &mut P, w: &mut W)
+where
+    P: helper::PlaceholderSource,
     W: Write,
 {
-    helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
+    match expr {
+        query::Expr::TextRank { tbl, col, query } => {
+            w.write_str("CAST(ts_rank(").unwrap();
+            if let Some(tbl) = tbl {
+                write!(w, "{}.", tbl).unwrap();
+            }
+            write!(
+                w,
+                "{}, websearch_to_tsquery('{}', ",
+                tsvector_column(col),
+                FULLTEXT_CONFIG
+            )
+            .unwrap();
+            sql_for_expr(*query, values, pls, w);
+            w.write_str(")) AS DOUBLE PRECISION)").unwrap();
+        }
+        query::Expr::Condition(c) => match *c {
+            BoolExpr::MatchesText { tbl, col, query } => {
+                if let Some(tbl) = tbl {
+                    write!(w, "{}.", tbl).unwrap();
+                }
+                write!(
+                    w,
+                    "{} @@ websearch_to_tsquery('{}', ",
+                    tsvector_column(col),
+                    FULLTEXT_CONFIG
+                )
+                .unwrap();
+                sql_for_expr(query, values, pls, w);
+                w.write_char(')').unwrap();
+            }
+            BoolExpr::ILike(col, ex) => {
+                write!(w, "{} ILIKE ", col).unwrap();
+                sql_for_expr(ex, values, pls, w)
+            }
+            // Values within JSON are compared as JSONB rather than
+            // text, so that numbers compare numerically and values of
+            // different types are never equal, as on other backends.
+            BoolExpr::Cmp(query::Expr::JsonPath { col, path }, op, query::Expr::Val(val))
+                if val != SqlVal::Null =>
+            {
+                let json = crate::json::sqlval_to_json(&val).map_or(SqlVal::Null, SqlVal::Json);
+                write!(
+                    w,
+                    "{} #> {}{}",
+                    col,
+                    helper::json_path_array(&path),
+                    helper::cmp_op_sql(op)
+                )
+                .unwrap();
+                sql_for_expr(query::Expr::Val(json), values, pls, w)
+            }
+            c => helper::sql_for_expr(
+                query::Expr::Condition(Box::new(c)),
+                sql_for_expr,
+                values,
+                pls,
+                w,
+            ),
+        },
+        _ => helper::sql_for_expr(expr, sql_for_expr, values, pls, w),
+    }
 }
 
-fn sql_val_from_postgres<I>(row: &postgres::Row, idx: I, col: &Column) -> Result<SqlVal>
-where
-    I: postgres::row::RowIndex + std::fmt::Display,
-{
-    let sqlref: SqlValRef = row.try_get(idx)?;
+#[allow(clippy::too_many_arguments)]
+fn select<'a>(
+    conn: &'a impl PgConnectionLike,
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[query::Order]>,
+) -> Result<RawQueryResult<'a>> {
+    let (stmt, values) =
+        prepare_select(conn, table, columns, distinct, expr, limit, offset, order)?;
+    query_statement(conn, &stmt, &values, columns.len())
+}
+
+/// Prepares a select query, returning the statement and its parameter values.
+#[allow(clippy::too_many_arguments)]
+fn prepare_select(
+    conn: &impl PgConnectionLike,
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[query::Order]>,
+) -> Result<(postgres::Statement, Vec<SqlVal>)> {
+    let (sqlquery, values) = sql_for_query(
+        table,
+        columns,
+        distinct,
+        expr,
+        limit,
+        offset,
+        order,
+        &mut PgPlaceholderSource::new(),
+    );
+    if cfg!(feature = "log") {
+        debug!("query sql {}", sqlquery);
+    }
+
+    let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+    let stmt = conn
+        .cell()?
+        .try_borrow_mut()?
+        .prepare_typed(&sqlquery, types.as_ref())?;
+    Ok((stmt, values))
+}
+
+/// The sql and parameter values for a select query.
+#[allow(clippy::too_many_arguments)]
+fn sql_for_query(
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[query::Order]>,
+    pls: &mut impl helper::PlaceholderSource,
+) -> (String, Vec<SqlVal>) {
+    let mut sqlquery = String::new();
+    if distinct {
+        helper::sql_select_distinct(columns, table, order, &mut sqlquery);
+    } else {
+        helper::sql_select(columns, table, order, &mut sqlquery);
+    }
+    let mut values: Vec<SqlVal> = Vec::new();
+    if let Some(expr) = expr {
+        sqlquery.write_str(" WHERE ").unwrap();
+        sql_for_expr(
+            query::Expr::Condition(Box::new(expr)),
+            &mut values,
+            pls,
+            &mut sqlquery,
+        );
+    }
+
+    if let Some(order) = order {
+        helper::sql_order(
+            table,
+            order,
+            helper::NullsSyntax::Native,
+            sql_for_expr,
+            &mut values,
+            pls,
+            &mut sqlquery,
+        )
+    }
+
+    if let Some(limit) = limit {
+        helper::sql_limit(limit, &mut sqlquery)
+    }
+
+    if let Some(offset) = offset {
+        helper::sql_offset(offset, &mut sqlquery)
+    }
+    (sqlquery, values)
+}
+
+/// Executes a prepared select statement.
+fn query_statement<'a>(
+    conn: &impl PgConnectionLike,
+    stmt: &postgres::Statement,
+    values: &[SqlVal],
+    ncolumns: usize,
+) -> Result<RawQueryResult<'a>> {
+    check_columns(stmt, ncolumns)?;
+    let rowvec: Vec<postgres::Row> = conn
+        .cell()?
+        .try_borrow_mut()?
+        .query_raw(stmt, values.iter().map(sqlval_for_pg_query))?
+        .map_err(Error::Postgres)
+        .collect()?;
+    Ok(Box::new(VecRows::new(rowvec)))
+}
+
+/// Rows read lazily from the server, borrowing the connection.
+struct RowStream<'a> {
+    rows: postgres::RowIter<'a>,
+    current: Option<postgres::Row>,
+}
+impl<'a> BackendRows for RowStream<'a> {
+    fn next<'b>(&'b mut self) -> Result<Option<&'b (dyn BackendRow + 'b)>> {
+        self.current = self.rows.next()?;
+        Ok(self.current.as_ref().map(|row| row as &dyn BackendRow))
+    }
+    fn current<'b>(&'b self) -> Option<&'b (dyn BackendRow + 'b)> {
+        self.current.as_ref().map(|row| row as &dyn BackendRow)
+    }
+}
+
+fn sql_val_from_postgres(row: &postgres::Row, idx: usize, col: &Column) -> Result<SqlVal> {
+    let sqlref: SqlValRef = BackendRow::get(row, idx, col.ty().clone())?;
     let sqlval: SqlVal = sqlref.into();
     if sqlval.is_compatible(col.ty(), true) {
         Ok(sqlval)
@@ -517,8 +1179,107 @@ fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
         Operation::RemoveTable(name) => Ok(drop_table(name)),
         Operation::AddColumn(tbl, col) => add_column(tbl, col),
         Operation::RemoveColumn(tbl, name) => Ok(remove_column(tbl, name)),
-        Operation::ChangeColumn(tbl, old, new) => change_column(current, tbl, old, Some(new)),
+        Operation::ChangeColumn(tbl, old, new) => alter_column(current, tbl, old, new),
+        Operation::AddFullText(tbl, col) => {
+            current.transform_with(op.clone());
+            Ok(add_fulltext(tbl, col))
+        }
+        Operation::RemoveFullText(tbl, col) => {
+            current.transform_with(op.clone());
+            Ok(remove_fulltext(tbl, col))
+        }
+        Operation::AddEnum(e) => {
+            current.transform_with(op.clone());
+            Ok(create_enum(e))
+        }
+        Operation::RemoveEnum(name) => {
+            current.transform_with(op.clone());
+            Ok(drop_enum(name))
+        }
+        Operation::ChangeEnum(old, new) => change_enum(current, old, new),
+    }
+}
+
+fn create_enum(e: &AEnum) -> String {
+    let variants = e
+        .variants
+        .iter()
+        .map(|v| enum_literal(v))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("CREATE TYPE {} AS ENUM ({});", e.name, variants)
+}
+
+fn drop_enum(name: &str) -> String {
+    format!("DROP TYPE {};", name)
+}
+
+fn enum_literal(variant: &str) -> String {
+    format!("'{}'", variant.replace('\'', "''"))
+}
+
+/// Changes the values of the enum type `old` to those of `new`. Values
+/// are added in place where possible. Otherwise the type is recreated
+/// and the columns using it are converted, which fails if any row
+/// uses a value which has been removed.
+fn change_enum(current: &mut ADB, old: &AEnum, new: &AEnum) -> Result<String> {
+    let mut stmts: Vec<String> = Vec::new();
+    if old.is_extended_by(new) {
+        for (i, variant) in new.variants.iter().enumerate() {
+            if old.variants.contains(variant) {
+                continue;
+            }
+            let position = match (i.checked_sub(1), old.variants.first()) {
+                (Some(prev), _) => format!(" AFTER {}", enum_literal(&new.variants[prev])),
+                (None, Some(first)) => format!(" BEFORE {}", enum_literal(first)),
+                (None, None) => String::new(),
+            };
+            stmts.push(format!(
+                "ALTER TYPE {} ADD VALUE {}{};",
+                new.name,
+                enum_literal(variant),
+                position
+            ));
+        }
+    } else {
+        let tmp_name = format!("{}__butane_old", old.name);
+        stmts.push(format!("ALTER TYPE {} RENAME TO {};", old.name, tmp_name));
+        stmts.push(create_enum(new));
+        let typeid = TypeIdentifier::Name(new.name.clone());
+        for table in current.tables() {
+            for col in table
+                .columns
+                .iter()
+                .filter(|c| c.typeid().ok().as_ref() == Some(&typeid))
+            {
+                // The default cannot be converted along with the column
+                if col.default().is_some() {
+                    stmts.push(format!(
+                        "ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;",
+                        table.name,
+                        col.name()
+                    ));
+                }
+                stmts.push(format!(
+                    "ALTER TABLE {tbl} ALTER COLUMN {col} TYPE {ty} USING {col}::text::{ty};",
+                    tbl = table.name,
+                    col = col.name(),
+                    ty = new.name
+                ));
+                if let Some(default) = col.default() {
+                    stmts.push(format!(
+                        "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {};",
+                        table.name,
+                        col.name(),
+                        helper::sql_literal_value(default.clone())?
+                    ));
+                }
+            }
+        }
+        stmts.push(drop_enum(&tmp_name));
     }
+    current.replace_enum(new.clone());
+    Ok(stmts.join("\n"))
 }
 
 fn create_table(table: &ATable, allow_exists: bool) -> Result<String> {
@@ -556,10 +1317,11 @@ fn define_column(col: &AColumn) -> Result<String> {
 
 fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
     match col.typeid()? {
-        TypeIdentifier::Name(name) => Ok(Cow::Owned(name)),
+        TypeIdentifier::Name(name) | TypeIdentifier::Portable { name, .. } => Ok(Cow::Owned(name)),
         TypeIdentifier::Ty(ty) => {
             if col.is_auto() {
                 match ty {
+                    SqlType::SmallInt => Ok(Cow::Borrowed("SMALLSERIAL")),
                     SqlType::Int => Ok(Cow::Borrowed("SERIAL")),
                     SqlType::BigInt => Ok(Cow::Borrowed("BIGSERIAL")),
                     _ => Err(Error::InvalidAuto(col.name().to_string())),
@@ -567,15 +1329,46 @@ fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
             } else {
                 Ok(match ty {
                     SqlType::Bool => Cow::Borrowed("BOOLEAN"),
+                    SqlType::SmallInt => Cow::Borrowed("SMALLINT"),
                     SqlType::Int => Cow::Borrowed("INTEGER"),
                     SqlType::BigInt => Cow::Borrowed("BIGINT"),
+                    SqlType::UBigInt => Cow::Borrowed("NUMERIC(20)"),
+                    SqlType::HugeInt | SqlType::UHugeInt => Cow::Borrowed("NUMERIC(39)"),
                     SqlType::Real => Cow::Borrowed("DOUBLE PRECISION"),
                     SqlType::Text => Cow::Borrowed("TEXT"),
-                    #[cfg(feature = "datetime")]
+                    #[cfg(any(feature = "datetime", feature = "time"))]
                     SqlType::Timestamp => Cow::Borrowed("TIMESTAMP"),
+                    #[cfg(any(feature = "datetime", feature = "time"))]
+                    SqlType::Date => Cow::Borrowed("DATE"),
+                    #[cfg(any(feature = "datetime", feature = "time"))]
+                    SqlType::Time => Cow::Borrowed("TIME"),
+                    #[cfg(any(feature = "datetime", feature = "time"))]
+                    SqlType::TimestampTz => Cow::Borrowed("TIMESTAMPTZ"),
+                    #[cfg(any(feature = "datetime", feature = "time"))]
+                    SqlType::Interval => Cow::Borrowed("INTERVAL"),
+                    #[cfg(feature = "decimal")]
+                    SqlType::Numeric { precision, scale } => {
+                        Cow::Owned(format!("NUMERIC({}, {})", precision, scale))
+                    }
+                    #[cfg(feature = "uuid")]
+                    SqlType::Uuid => Cow::Borrowed("UUID"),
+                    SqlType::Inet => Cow::Borrowed("INET"),
+                    SqlType::Array(elem) => Cow::Borrowed(match elem {
+                        ArrayElement::Bool => "BOOLEAN[]",
+                        ArrayElement::Int => "INTEGER[]",
+                        ArrayElement::BigInt => "BIGINT[]",
+                        ArrayElement::Real => "DOUBLE PRECISION[]",
+                        ArrayElement::Text => "TEXT[]",
+                    }),
                     SqlType::Blob => Cow::Borrowed("BYTEA"),
+                    SqlType::Json => Cow::Borrowed("JSONB"),
                     SqlType::Custom(c) => match c {
-                        SqlTypeCustom::Pg(ref ty) => Cow::Owned(ty.name().to_string()),
+                        SqlTypeCustom::Pg(ref ty) | SqlTypeCustom::Portable { pg: ref ty, .. } => {
+                            Cow::Owned(ty.name().to_string())
+                        }
+                        SqlTypeCustom::Sqlite(_) => {
+                            return Err(Error::IncompatibleCustomT(c, BACKEND_NAME))
+                        }
                     },
                 })
             }
@@ -601,6 +1394,49 @@ fn remove_column(tbl_name: &str, name: &str) -> String {
     format!("ALTER TABLE {} DROP COLUMN {};", tbl_name, name)
 }
 
+/// The text search configuration used for full-text search.
+const FULLTEXT_CONFIG: &str = "english";
+
+/// The name of the generated tsvector column used for full-text search
+/// on `col`.
+fn tsvector_column(col: &str) -> String {
+    format!("{}_tsv", col)
+}
+
+/// Adds a generated tsvector column for full-text search on `col`,
+/// with a GIN index.
+fn add_fulltext(tbl_name: &str, col: &str) -> String {
+    let tsv = tsvector_column(col);
+    format!(
+        "ALTER TABLE {tbl} ADD COLUMN {tsv} tsvector GENERATED ALWAYS AS (to_tsvector('{cfg}', coalesce({col}, ''))) STORED;\n\
+         CREATE INDEX {tbl}_{tsv}_idx ON {tbl} USING GIN ({tsv});",
+        tbl = tbl_name,
+        tsv = tsv,
+        cfg = FULLTEXT_CONFIG,
+        col = col
+    )
+}
+
+fn remove_fulltext(tbl_name: &str, col: &str) -> String {
+    // Also drops the index
+    format!(
+        "ALTER TABLE {} DROP COLUMN IF EXISTS {};",
+        tbl_name,
+        tsvector_column(col)
+    )
+}
+
+/// Statements to add the full-text search columns of `table`, which
+/// are lost when it is copied.
+fn add_fulltexts(table: &ATable) -> Vec<String> {
+    table
+        .columns
+        .iter()
+        .filter(|c| c.fulltext())
+        .map(|c| add_fulltext(&table.name, c.name()))
+        .collect()
+}
+
 fn copy_table(old: &ATable, new: &ATable) -> String {
     let column_names = new
         .columns
@@ -618,6 +1454,118 @@ fn tmp_table_name(name: &str) -> String {
     format!("{}__butane_tmp", name)
 }
 
+/// Change a column in place with `ALTER TABLE` where possible, so
+/// that the table's data, indexes, grants and triggers are left
+/// intact. Falls back to `change_column` for changes to the primary
+/// key or auto-increment status, which cannot be made in place.
+fn alter_column(current: &mut ADB, tbl_name: &str, old: &AColumn, new: &AColumn) -> Result<String> {
+    if old.is_pk() != new.is_pk() || old.is_auto() || new.is_auto() {
+        return change_column(current, tbl_name, old, Some(new));
+    }
+    let table = match current.get_table(tbl_name) {
+        Some(table) => table,
+        None => {
+            crate::warn!(
+                "Cannot alter column {} from table {} that does not exist",
+                &old.name(),
+                tbl_name
+            );
+            return Ok(String::new());
+        }
+    };
+    let mut new_table = table.clone();
+    let name = new.name();
+    let mut stmts: Vec<String> = Vec::new();
+    let new_type = col_sqltype(new)?;
+    if col_sqltype(old)? != new_type {
+        // A generated column prevents changing the type of the
+        // columns it is generated from
+        let fulltext = table.column(name).map_or(false, |c| c.fulltext());
+        if fulltext {
+            stmts.push(remove_fulltext(tbl_name, name));
+        }
+        stmts.push(format!(
+            "ALTER TABLE {} ALTER COLUMN {} TYPE {} USING {};",
+            tbl_name,
+            name,
+            new_type,
+            convert_column(name, old, new, &new_type)
+        ));
+        if fulltext {
+            stmts.push(add_fulltext(tbl_name, name));
+        }
+    }
+    if old.nullable() != new.nullable() {
+        let action = if new.nullable() { "DROP" } else { "SET" };
+        stmts.push(format!(
+            "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL;",
+            tbl_name, name, action
+        ));
+    }
+    if old.default() != new.default() {
+        stmts.push(match new.default() {
+            Some(val) => format!(
+                "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {};",
+                tbl_name,
+                name,
+                helper::sql_literal_value(val.clone())?
+            ),
+            None => format!(
+                "ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;",
+                tbl_name, name
+            ),
+        });
+    }
+    if old.unique() != new.unique() {
+        stmts.push(if new.unique() {
+            // Postgres names the constraint as it would an inline one
+            format!("ALTER TABLE {} ADD UNIQUE ({});", tbl_name, name)
+        } else {
+            drop_unique_constraint(tbl_name, name)
+        });
+    }
+    new_table.replace_column(new.clone());
+    current.replace_table(new_table);
+    Ok(stmts.join("\n"))
+}
+
+/// Drop the unique constraint on the single column `col`. The
+/// constraint's name is looked up when the migration is applied, as
+/// it may not be the one postgres would have chosen.
+fn drop_unique_constraint(tbl_name: &str, col: &str) -> String {
+    // Unquoted identifiers are folded to lower case
+    format!(
+        "DO $$\n\
+         DECLARE constraint_name TEXT;\n\
+         BEGIN\n\
+         SELECT c.conname INTO STRICT constraint_name FROM pg_constraint c \
+         JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = c.conkey[1] \
+         WHERE c.conrelid = '{tbl}'::regclass AND c.contype = 'u' \
+         AND cardinality(c.conkey) = 1 AND a.attname = lower('{col}');\n\
+         EXECUTE format('ALTER TABLE {tbl} DROP CONSTRAINT %I', constraint_name);\n\
+         END $$;",
+        tbl = tbl_name,
+        col = col
+    )
+}
+
+/// The expression converting the values of column `name` from the
+/// type of `old` to that of `new`, which is `new_type`.
+fn convert_column(name: &str, old: &AColumn, new: &AColumn, new_type: &str) -> String {
+    match (old.typeid(), new.typeid()) {
+        // Uuids were previously stored as BYTEA
+        #[cfg(feature = "uuid")]
+        (Ok(TypeIdentifier::Ty(SqlType::Blob)), Ok(TypeIdentifier::Ty(SqlType::Uuid))) => {
+            format!("encode({}, 'hex')::UUID", name)
+        }
+        #[cfg(feature = "uuid")]
+        (Ok(TypeIdentifier::Ty(SqlType::Uuid)), Ok(TypeIdentifier::Ty(SqlType::Blob))) => {
+            format!("decode(replace({}::TEXT, '-', ''), 'hex')", name)
+        }
+        _ => format!("{}::{}", name, new_type),
+    }
+}
+
 fn change_column(
     current: &mut ADB,
     tbl_name: &str,
@@ -646,8 +1594,12 @@ fn change_column(
         &drop_table(&old_table.name),
         &format!("ALTER TABLE {} RENAME TO {};", &new_table.name, tbl_name),
     ];
-    let result = stmts.join("\n");
+    let mut result = stmts.join("\n");
     new_table.name = old_table.name.clone();
+    for stmt in add_fulltexts(&new_table) {
+        result.push('\n');
+        result.push_str(&stmt);
+    }
     current.replace_table(new_table);
     Ok(result)
 }
@@ -680,23 +1632,66 @@ pub fn sql_insert_or_replace_with_placeholders(
 
 fn pgtype_for_val(val: &SqlVal) -> postgres::types::Type {
     use postgres::types::Type;
+    if let SqlVal::Enum(_) = val {
+        // Left for the server to infer, as the enum type is not known
+        return Type::UNKNOWN;
+    }
     match val.sqltype() {
         None => Type::UNKNOWN,
         Some(SqlType::Bool) => postgres::types::Type::BOOL,
+        Some(SqlType::SmallInt) => postgres::types::Type::INT2,
         Some(SqlType::Int) => postgres::types::Type::INT4,
         Some(SqlType::BigInt) => postgres::types::Type::INT8,
+        Some(SqlType::UBigInt | SqlType::HugeInt | SqlType::UHugeInt) => {
+            postgres::types::Type::NUMERIC
+        }
         Some(SqlType::Real) => postgres::types::Type::FLOAT8,
         Some(SqlType::Text) => postgres::types::Type::TEXT,
         Some(SqlType::Blob) => postgres::types::Type::BYTEA,
-        #[cfg(feature = "datetime")]
+        Some(SqlType::Json) => postgres::types::Type::JSONB,
+        #[cfg(any(feature = "datetime", feature = "time"))]
         Some(SqlType::Timestamp) => postgres::types::Type::TIMESTAMP,
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        Some(SqlType::Date) => postgres::types::Type::DATE,
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        Some(SqlType::Time) => postgres::types::Type::TIME,
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        Some(SqlType::TimestampTz) => postgres::types::Type::TIMESTAMPTZ,
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        Some(SqlType::Interval) => postgres::types::Type::INTERVAL,
+        #[cfg(feature = "decimal")]
+        Some(SqlType::Numeric { .. }) => postgres::types::Type::NUMERIC,
+        #[cfg(feature = "uuid")]
+        Some(SqlType::Uuid) => postgres::types::Type::UUID,
+        Some(SqlType::Inet) => postgres::types::Type::INET,
+        Some(SqlType::Array(elem)) => match elem {
+            ArrayElement::Bool => Type::BOOL_ARRAY,
+            ArrayElement::Int => Type::INT4_ARRAY,
+            ArrayElement::BigInt => Type::INT8_ARRAY,
+            ArrayElement::Real => Type::FLOAT8_ARRAY,
+            ArrayElement::Text => Type::TEXT_ARRAY,
+        },
         Some(SqlType::Custom(inner)) => match inner {
-            #[cfg(feature = "pg")]
-            SqlTypeCustom::Pg(ty, ..) => ty,
+            SqlTypeCustom::Pg(ty) | SqlTypeCustom::Portable { pg: ty, .. } => ty,
+            SqlTypeCustom::Sqlite(_) => Type::UNKNOWN,
         },
     }
 }
 
+/// The type of the elements of `ty` if it is an array type butane
+/// supports.
+fn array_element(ty: &postgres::types::Type) -> Option<ArrayElement> {
+    use postgres::types::Type;
+    Some(match *ty {
+        Type::BOOL_ARRAY => ArrayElement::Bool,
+        Type::INT4_ARRAY => ArrayElement::Int,
+        Type::INT8_ARRAY => ArrayElement::BigInt,
+        Type::FLOAT8_ARRAY => ArrayElement::Real,
+        Type::TEXT_ARRAY => ArrayElement::Text,
+        _ => return None,
+    })
+}
+
 struct PgPlaceholderSource {
     n: i8,
 }
@@ -34,6 +34,9 @@ impl ConnectionMethodWrapper for r2d2::PooledConnection<ConnectionManager> {
     fn wrapped_connection_methods(&self) -> Result<&Connection> {
         Ok(self.deref())
     }
+    fn wrapped_connection_methods_mut(&mut self) -> Result<&mut Connection> {
+        Ok(self.deref_mut())
+    }
 }
 
-connection_method_wrapper!(r2d2::PooledConnection<ConnectionManager>);
+connection_method_wrapper!(r2d2::PooledConnection<ConnectionManager>, mut);
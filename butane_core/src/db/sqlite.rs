@@ -1,22 +1,32 @@
 //! SQLite database backend
 use super::helper;
 use super::*;
+use crate::custom::{SqlTypeCustom, SqlValCustom, SqlValRefCustom, SqliteValue};
 use crate::db::connmethods::BackendRows;
 use crate::debug;
 use crate::migrations::adb::{AColumn, ATable, Operation, TypeIdentifier, ADB};
 use crate::query;
-use crate::query::Order;
+use crate::query::{DatePart, Func, Order};
 use crate::{Result, SqlType, SqlVal, SqlValRef};
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
+#[cfg(feature = "datetime")]
+use chrono::{DateTime, Duration, Utc};
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use pin_project::pin_project;
 use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
 use std::fmt::Write;
+use std::ops::DerefMut;
 use std::pin::Pin;
 
-#[cfg(feature = "datetime")]
 const SQLITE_DT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+#[cfg(feature = "time")]
+const SQLITE_TIME_DT_FORMAT: &[time::format_description::FormatItem<'static>] =
+    time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+#[cfg(all(feature = "time", not(feature = "datetime")))]
+const SQLITE_TIME_FORMAT: &[time::format_description::FormatItem<'static>] =
+    time::macros::format_description!("[hour]:[minute]:[second][optional [.[subsecond]]]");
 
 /// The name of the sqlite backend.
 pub const BACKEND_NAME: &str = "sqlite";
@@ -57,6 +67,12 @@ impl Backend for SQLiteBackend {
             conn: Box::new(self.connect(path)?),
         })
     }
+
+    fn converts_column_values(&self, old: &SqlType, new: &SqlType) -> bool {
+        // Copying the table leaves integers in their old storage rather
+        // than the fixed-width blobs of the wider types.
+        old == new || !matches!(new, SqlType::UBigInt | SqlType::HugeInt | SqlType::UHugeInt)
+    }
 }
 
 /// SQLite database connection.
@@ -84,6 +100,15 @@ impl BackendConnection for SQLiteConnection {
         let trans = Box::new(SqliteTransaction::new(trans));
         Ok(Transaction::new(trans))
     }
+    fn migration_transaction(&mut self) -> Result<Transaction<'_>> {
+        // An exclusive transaction locks the entire database file
+        // against other connections, which is exactly what we need.
+        let trans: rusqlite::Transaction<'_> = self
+            .conn
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Exclusive)?;
+        let trans = Box::new(SqliteTransaction::new(trans));
+        Ok(Transaction::new(trans))
+    }
     fn backend(&self) -> Box<dyn Backend> {
         Box::new(SQLiteBackend {})
     }
@@ -112,44 +137,119 @@ impl ConnectionMethods for rusqlite::Connection {
         limit: Option<i32>,
         offset: Option<i32>,
         order: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        select(self, table, columns, false, expr, limit, offset, order)
+    }
+    fn query_distinct<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        select(self, table, columns, true, expr, limit, offset, order)
+    }
+    fn query_join<'a, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &[query::Column],
+        joins: Vec<query::Join>,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[Order]>,
     ) -> Result<RawQueryResult<'a>> {
         let mut sqlquery = String::new();
-        helper::sql_select(columns, table, &mut sqlquery);
         let mut values: Vec<SqlVal> = Vec::new();
-        if let Some(expr) = expr {
-            sqlquery.write_str(" WHERE ").unwrap();
-            sql_for_expr(
-                query::Expr::Condition(Box::new(expr)),
+        let mut pls = SQLitePlaceholderSource::new();
+        helper::sql_select_join(
+            columns,
+            table,
+            joins,
+            expr.map(|expr| {
+                |w: &mut String| {
+                    sql_for_expr(
+                        query::Expr::Condition(Box::new(expr)),
+                        &mut values,
+                        &mut pls,
+                        w,
+                    )
+                }
+            }),
+            order,
+            &mut sqlquery,
+        );
+        if let Some(order) = order {
+            helper::sql_order_qualified(
+                table,
+                order,
+                helper::NullsSyntax::Emulated,
+                sql_for_expr,
                 &mut values,
-                &mut SQLitePlaceholderSource::new(),
+                &mut pls,
                 &mut sqlquery,
-            );
-        }
-
-        if let Some(order) = order {
-            helper::sql_order(order, &mut sqlquery)
+            )
         }
-
         if let Some(limit) = limit {
             helper::sql_limit(limit, &mut sqlquery)
         }
-
         if let Some(offset) = offset {
             if limit.is_none() {
-                // Sqlite only supports offset in conjunction with
-                // limit, so add a max limit if we don't have one
-                // already.
                 helper::sql_limit(i32::MAX, &mut sqlquery)
             }
             helper::sql_offset(offset, &mut sqlquery)
         }
-
         debug!("query sql {}", sqlquery);
 
         let stmt = self.prepare(&sqlquery)?;
         let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
         Ok(Box::new(adapter))
     }
+    fn query_prepared<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[Order]>,
+        params: &[(&'static str, SqlVal)],
+    ) -> Result<RawQueryResult<'a>> {
+        helper::check_params(expr.as_ref(), params)?;
+        let (sqlquery, values) = sql_for_query(
+            table,
+            columns,
+            false,
+            expr,
+            limit,
+            offset,
+            order,
+            &mut helper::ParamPlaceholderSource::new(SQLitePlaceholderSource::new(), params),
+        );
+        debug!("prepared query sql {}", sqlquery);
+
+        let stmt = self.prepare_cached(&sqlquery)?;
+        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
+        Ok(Box::new(adapter))
+    }
+    fn raw_query_rows<'a, 'b, 'c: 'a>(
+        &'c self,
+        sql: &str,
+        params: &[SqlVal],
+        columns: &'b [Column],
+    ) -> Result<RawQueryResult<'a>> {
+        debug!("raw query sql {}", sql);
+        let stmt = self.prepare(sql)?;
+        helper::check_raw_columns(stmt.column_names().into_iter(), columns)?;
+        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(params))?;
+        Ok(Box::new(adapter))
+    }
+    fn raw_execute(&self, sql: &str, params: &[SqlVal]) -> Result<usize> {
+        debug!("raw execute sql {}", sql);
+        Ok(self.execute(sql, rusqlite::params_from_iter(params))?)
+    }
     fn insert_returning_pk(
         &self,
         table: &str,
@@ -247,6 +347,14 @@ impl ConnectionMethods for rusqlite::Connection {
         let mut rows = stmt.query([table])?;
         Ok(rows.next()?.is_some())
     }
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = self.prepare("SELECT name FROM pragma_table_info(?) WHERE name=?;")?;
+        let mut rows = stmt.query([table, column])?;
+        Ok(rows.next()?.is_some())
+    }
+    fn sql_backend(&self) -> Option<Box<dyn Backend>> {
+        Some(Box::new(SQLiteBackend::new()))
+    }
 }
 
 struct SqliteTransaction<'c> {
@@ -294,54 +402,162 @@ impl<'c> BackendTransaction<'c> for SqliteTransaction<'c> {
 
 impl rusqlite::ToSql for SqlVal {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
-        Ok(sqlvalref_to_sqlite(&self.as_ref()))
+        sqlvalref_to_sqlite(&self.as_ref())
     }
 }
 
 impl<'a> rusqlite::ToSql for SqlValRef<'a> {
     fn to_sql<'b>(&'b self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'a>> {
-        Ok(sqlvalref_to_sqlite(self))
+        sqlvalref_to_sqlite(self)
     }
 }
 
-fn sqlvalref_to_sqlite<'a, 'b>(valref: &'b SqlValRef<'a>) -> rusqlite::types::ToSqlOutput<'a> {
+fn sqlvalref_to_sqlite<'a, 'b>(
+    valref: &'b SqlValRef<'a>,
+) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'a>> {
     use rusqlite::types::{ToSqlOutput::Borrowed, ToSqlOutput::Owned, Value, ValueRef};
     use SqlValRef::*;
-    match valref {
+    Ok(match valref {
         Bool(b) => Owned(Value::Integer(*b as i64)),
+        SmallInt(i) => Owned(Value::Integer(*i as i64)),
         Int(i) => Owned(Value::Integer(*i as i64)),
         BigInt(i) => Owned(Value::Integer(*i)),
+        UBigInt(i) => Owned(Value::Blob(i.to_be_bytes().to_vec())),
+        HugeInt(i) => Owned(Value::Blob(hugeint_to_bytes(*i).to_vec())),
+        UHugeInt(i) => Owned(Value::Blob(i.to_be_bytes().to_vec())),
         Real(r) => Owned(Value::Real(*r)),
-        Text(t) => Borrowed(ValueRef::Text(t.as_bytes())),
+        Text(t) | Enum(t) => Borrowed(ValueRef::Text(t.as_bytes())),
         Blob(b) => Borrowed(ValueRef::Blob(b)),
         #[cfg(feature = "datetime")]
         Timestamp(dt) => {
             let f = dt.format(SQLITE_DT_FORMAT);
             Owned(Value::Text(f.to_string()))
         }
+        #[cfg(feature = "datetime")]
+        Date(d) => Owned(Value::Text(d.format(helper::DATE_FORMAT).to_string())),
+        #[cfg(feature = "datetime")]
+        Time(t) => Owned(Value::Text(t.format(helper::TIME_FORMAT).to_string())),
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => Owned(Value::Text(
+            dt.format(helper::TIMESTAMPTZ_FORMAT).to_string(),
+        )),
+        #[cfg(feature = "datetime")]
+        Interval(interval) => Owned(Value::Integer(crate::sqlval::interval_to_micros(interval))),
+        #[cfg(feature = "time")]
+        TimeTimestamp(dt) => Owned(Value::Text(
+            dt.format(SQLITE_TIME_DT_FORMAT)
+                .map_err(conversion_failure)?,
+        )),
+        #[cfg(feature = "time")]
+        TimeDate(d) => Owned(Value::Text(
+            crate::time::format_date(d).map_err(conversion_failure)?,
+        )),
+        #[cfg(feature = "time")]
+        TimeTime(t) => Owned(Value::Text(crate::time::format_time(t))),
+        #[cfg(feature = "time")]
+        TimeTimestampTz(dt) => Owned(Value::Text(
+            crate::time::format_offset_datetime(dt).map_err(conversion_failure)?,
+        )),
+        #[cfg(feature = "time")]
+        TimeInterval(interval) => Owned(Value::Integer(
+            crate::time::duration_to_micros(interval).map_err(conversion_failure)?,
+        )),
+        #[cfg(feature = "decimal")]
+        Numeric(d) => Owned(Value::Text(crate::decimal::to_ordered_text(d))),
+        #[cfg(feature = "uuid")]
+        Uuid(u) => Owned(Value::Blob(u.as_bytes().to_vec())),
+        Inet(ip) => Owned(Value::Text(ip.to_string())),
+        Array(_, vals) => Owned(Value::Text(crate::json::array_to_json(vals))),
+        Json(Cow::Borrowed(json)) => Borrowed(ValueRef::Text(json.as_bytes())),
+        Json(Cow::Owned(json)) => Owned(Value::Text(json.clone())),
         Null => Owned(Value::Null),
-        Custom(_) => panic!("Custom types not supported in sqlite"),
+        Custom(SqlValRefCustom::Sqlite(v)) => Owned(sqlite_value(v)),
+        #[cfg(feature = "pg")]
+        Custom(
+            SqlValRefCustom::Portable { sqlite, .. }
+            | SqlValRefCustom::PortableBytes { sqlite, .. },
+        ) => Owned(sqlite_value(sqlite)),
+        Custom(c) => {
+            let err = match SqlValCustom::try_from(c.clone()) {
+                Ok(v) => Error::IncompatibleCustom(v, BACKEND_NAME),
+                Err(e) => e,
+            };
+            return Err(conversion_failure(err));
+        }
+    })
+}
+
+fn conversion_failure(e: impl std::error::Error + Send + Sync + 'static) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
+
+// Integers too wide for INTEGER are stored as big-endian blobs, which
+// sqlite compares bytewise and so in numerical order. The sign bit of
+// signed integers is flipped so that negative numbers sort first.
+
+fn hugeint_to_bytes(i: i128) -> [u8; 16] {
+    ((i as u128) ^ (1 << 127)).to_be_bytes()
+}
+
+fn hugeint_from_bytes(bytes: [u8; 16]) -> i128 {
+    (u128::from_be_bytes(bytes) ^ (1 << 127)) as i128
+}
+
+/// The bytes of a wide integer stored as a blob of `N` bytes.
+fn wide_int_bytes<const N: usize>(val: rusqlite::types::ValueRef, ty: &SqlType) -> Result<[u8; N]> {
+    let bytes = val.as_blob()?;
+    bytes
+        .try_into()
+        .map_err(|_| Error::CannotConvertSqlVal(ty.clone(), SqlVal::Blob(bytes.to_vec())))
+}
+
+fn sqlite_value(val: &SqliteValue) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+    match val {
+        SqliteValue::Integer(i) => Value::Integer(*i),
+        SqliteValue::Real(r) => Value::Real(*r),
+        SqliteValue::Text(t) => Value::Text(t.clone()),
+        SqliteValue::Blob(b) => Value::Blob(b.clone()),
+    }
+}
+
+/// A prepared statement, either owned or from the connection's cache.
+trait AsStatement<'a> {
+    fn as_statement(&mut self) -> &mut rusqlite::Statement<'a>;
+}
+impl<'a> AsStatement<'a> for rusqlite::Statement<'a> {
+    fn as_statement(&mut self) -> &mut rusqlite::Statement<'a> {
+        self
+    }
+}
+impl<'a> AsStatement<'a> for rusqlite::CachedStatement<'a> {
+    fn as_statement(&mut self) -> &mut rusqlite::Statement<'a> {
+        self.deref_mut()
     }
 }
 
 #[pin_project]
-struct QueryAdapterInner<'a> {
-    stmt: rusqlite::Statement<'a>,
+struct QueryAdapterInner<'a, S> {
     // will always be Some when the constructor has finished. We use an option only to get the
-    // stmt in place before we can reference it.
+    // stmt in place before we can reference it. Declared before stmt so
+    // that the rows are dropped before the statement they reference.
     rows: Option<rusqlite::Rows<'a>>,
+    stmt: S,
 }
 
-impl<'a> QueryAdapterInner<'a> {
-    fn new(stmt: rusqlite::Statement<'a>, params: impl rusqlite::Params) -> Result<Pin<Box<Self>>> {
-        let mut q = Box::pin(QueryAdapterInner { stmt, rows: None });
+impl<'a, S> QueryAdapterInner<'a, S>
+where
+    S: AsStatement<'a>,
+{
+    fn new(stmt: S, params: impl rusqlite::Params) -> Result<Pin<Box<Self>>> {
+        let mut q = Box::pin(QueryAdapterInner { rows: None, stmt });
         unsafe {
             //Soundness: we pin a QueryAdapterInner value containing
             //  both the stmt and the rows referencing the statement
             //  together. It is not possible to drop/move the stmt without
             //  bringing the referencing rows along with it.
             let q_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut q));
-            let stmt_ref: *mut rusqlite::Statement<'a> = &mut q_ref.stmt;
+            let stmt_ref: *mut rusqlite::Statement<'a> = q_ref.stmt.as_statement();
             q_ref.rows = Some((*stmt_ref).query(params)?)
         }
         Ok(q)
@@ -359,18 +575,24 @@ impl<'a> QueryAdapterInner<'a> {
     }
 }
 
-struct QueryAdapter<'a> {
-    inner: Pin<Box<QueryAdapterInner<'a>>>,
+struct QueryAdapter<'a, S> {
+    inner: Pin<Box<QueryAdapterInner<'a, S>>>,
 }
-impl<'a> QueryAdapter<'a> {
-    fn new(stmt: rusqlite::Statement<'a>, params: impl rusqlite::Params) -> Result<Self> {
+impl<'a, S> QueryAdapter<'a, S>
+where
+    S: AsStatement<'a>,
+{
+    fn new(stmt: S, params: impl rusqlite::Params) -> Result<Self> {
         Ok(QueryAdapter {
             inner: QueryAdapterInner::new(stmt, params)?,
         })
     }
 }
 
-impl<'a> BackendRows for QueryAdapter<'a> {
+impl<'a, S> BackendRows for QueryAdapter<'a, S>
+where
+    S: AsStatement<'a>,
+{
     fn next<'b>(&'b mut self) -> Result<Option<&'b (dyn BackendRow + 'b)>> {
         Ok(self
             .inner
@@ -395,15 +617,168 @@ impl BackendRow for rusqlite::Row<'_> {
     }
 }
 
-fn sql_for_expr<W>(
-    expr: query::Expr,
-    values: &mut Vec<SqlVal>,
-    pls: &mut SQLitePlaceholderSource,
-    w: &mut W,
-) where
+fn sql_for_expr<P, W>(expr: query::Expr, values: &mut Vec<SqlVal>, pls: &mut P, w: &mut W)
+where
+    P: helper::PlaceholderSource,
     W: Write,
 {
-    helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
+    // SQLite has no date types, timestamps are text in
+    // SQLITE_DT_FORMAT, so date functions are expressed with strftime.
+    match expr {
+        query::Expr::Func(Func::Extract(part), args) => {
+            let fmt = match part {
+                DatePart::Year => "%Y",
+                DatePart::Month => "%m",
+                DatePart::Day => "%d",
+                DatePart::Hour => "%H",
+                DatePart::Minute => "%M",
+                DatePart::Second => "%S",
+            };
+            write!(w, "CAST(strftime('{}', ", fmt).unwrap();
+            args.into_iter()
+                .for_each(|arg| sql_for_expr(arg, values, pls, w));
+            w.write_str(") AS INTEGER)").unwrap();
+        }
+        query::Expr::Func(Func::DateTrunc(part), args) => {
+            let fmt = match part {
+                DatePart::Year => "%Y-01-01 00:00:00",
+                DatePart::Month => "%Y-%m-01 00:00:00",
+                DatePart::Day => "%Y-%m-%d 00:00:00",
+                DatePart::Hour => "%Y-%m-%d %H:00:00",
+                DatePart::Minute => "%Y-%m-%d %H:%M:00",
+                DatePart::Second => SQLITE_DT_FORMAT,
+            };
+            write!(w, "strftime('{}', ", fmt).unwrap();
+            args.into_iter()
+                .for_each(|arg| sql_for_expr(arg, values, pls, w));
+            w.write_char(')').unwrap();
+        }
+        query::Expr::JsonPath { col, path } => {
+            write!(w, "json_extract({}, {})", col, json_path(&path)).unwrap();
+        }
+        // Full-text search uses the FTS5 table maintained by
+        // migrations. bm25 is lower for more relevant rows.
+        query::Expr::TextRank { tbl, col, query } => {
+            let tbl = tbl.as_deref().unwrap_or_default();
+            let fts = fulltext_table(tbl, col);
+            write!(
+                w,
+                "coalesce((SELECT -bm25({fts}) FROM {fts} WHERE {fts} MATCH ",
+                fts = fts
+            )
+            .unwrap();
+            sql_for_expr(*query, values, pls, w);
+            write!(w, " AND {}.rowid = {}.rowid), 0)", fts, tbl).unwrap();
+        }
+        query::Expr::Condition(c) => match *c {
+            BoolExpr::MatchesText { tbl, col, query } => {
+                let tbl = tbl.as_deref().unwrap_or_default();
+                let fts = fulltext_table(tbl, col);
+                write!(
+                    w,
+                    "{}.rowid IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ",
+                    tbl,
+                    fts = fts
+                )
+                .unwrap();
+                sql_for_expr(query, values, pls, w);
+                w.write_char(')').unwrap();
+            }
+            c => helper::sql_for_expr(
+                query::Expr::Condition(Box::new(c)),
+                sql_for_expr,
+                values,
+                pls,
+                w,
+            ),
+        },
+        _ => helper::sql_for_expr(expr, sql_for_expr, values, pls, w),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn select<'a>(
+    conn: &'a rusqlite::Connection,
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[Order]>,
+) -> Result<RawQueryResult<'a>> {
+    let (sqlquery, values) = sql_for_query(
+        table,
+        columns,
+        distinct,
+        expr,
+        limit,
+        offset,
+        order,
+        &mut SQLitePlaceholderSource::new(),
+    );
+    debug!("query sql {}", sqlquery);
+
+    let stmt = conn.prepare(&sqlquery)?;
+    let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
+    Ok(Box::new(adapter))
+}
+
+/// The sql and parameter values for a select query.
+#[allow(clippy::too_many_arguments)]
+fn sql_for_query(
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[Order]>,
+    pls: &mut impl helper::PlaceholderSource,
+) -> (String, Vec<SqlVal>) {
+    let mut sqlquery = String::new();
+    if distinct {
+        helper::sql_select_distinct(columns, table, order, &mut sqlquery);
+    } else {
+        helper::sql_select(columns, table, order, &mut sqlquery);
+    }
+    let mut values: Vec<SqlVal> = Vec::new();
+    if let Some(expr) = expr {
+        sqlquery.write_str(" WHERE ").unwrap();
+        sql_for_expr(
+            query::Expr::Condition(Box::new(expr)),
+            &mut values,
+            pls,
+            &mut sqlquery,
+        );
+    }
+
+    if let Some(order) = order {
+        helper::sql_order(
+            table,
+            order,
+            helper::NullsSyntax::Emulated,
+            sql_for_expr,
+            &mut values,
+            pls,
+            &mut sqlquery,
+        )
+    }
+
+    if let Some(limit) = limit {
+        helper::sql_limit(limit, &mut sqlquery)
+    }
+
+    if let Some(offset) = offset {
+        if limit.is_none() {
+            // Sqlite only supports offset in conjunction with
+            // limit, so add a max limit if we don't have one
+            // already.
+            helper::sql_limit(i32::MAX, &mut sqlquery)
+        }
+        helper::sql_offset(offset, &mut sqlquery)
+    }
+    (sqlquery, values)
 }
 
 fn sql_val_from_rusqlite(val: rusqlite::types::ValueRef, col: &Column) -> Result<SqlVal> {
@@ -414,13 +789,31 @@ fn sql_valref_from_rusqlite<'a>(
     val: rusqlite::types::ValueRef<'a>,
     ty: &SqlType,
 ) -> Result<SqlValRef<'a>> {
-    if let rusqlite::types::ValueRef::Null = val {
+    use rusqlite::types::ValueRef;
+    if let ValueRef::Null = val {
         return Ok(SqlValRef::Null);
     }
     Ok(match ty {
         SqlType::Bool => SqlValRef::Bool(val.as_i64()? != 0),
+        SqlType::SmallInt => {
+            SqlValRef::SmallInt(i16::try_from(val.as_i64()?).map_err(|_| Error::OutOfRange)?)
+        }
         SqlType::Int => SqlValRef::Int(val.as_i64()? as i32),
         SqlType::BigInt => SqlValRef::BigInt(val.as_i64()?),
+        // Values may have been written as INTEGER before the column
+        // was widened
+        SqlType::UBigInt => SqlValRef::UBigInt(match val {
+            ValueRef::Integer(i) => u64::try_from(i).map_err(|_| Error::OutOfRange)?,
+            _ => u64::from_be_bytes(wide_int_bytes(val, ty)?),
+        }),
+        SqlType::HugeInt => SqlValRef::HugeInt(match val {
+            ValueRef::Integer(i) => i128::from(i),
+            _ => hugeint_from_bytes(wide_int_bytes(val, ty)?),
+        }),
+        SqlType::UHugeInt => SqlValRef::UHugeInt(match val {
+            ValueRef::Integer(i) => u128::try_from(i).map_err(|_| Error::OutOfRange)?,
+            _ => u128::from_be_bytes(wide_int_bytes(val, ty)?),
+        }),
         SqlType::Real => SqlValRef::Real(val.as_f64()?),
         SqlType::Text => SqlValRef::Text(val.as_str()?),
         #[cfg(feature = "datetime")]
@@ -428,36 +821,110 @@ fn sql_valref_from_rusqlite<'a>(
             val.as_str()?,
             SQLITE_DT_FORMAT,
         )?),
+        #[cfg(feature = "datetime")]
+        SqlType::Date => SqlValRef::Date(NaiveDate::parse_from_str(
+            val.as_str()?,
+            helper::DATE_FORMAT,
+        )?),
+        #[cfg(feature = "datetime")]
+        SqlType::Time => SqlValRef::Time(NaiveTime::parse_from_str(
+            val.as_str()?,
+            helper::TIME_FORMAT,
+        )?),
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => {
+            SqlValRef::TimestampTz(DateTime::parse_from_rfc3339(val.as_str()?)?.with_timezone(&Utc))
+        }
+        #[cfg(feature = "datetime")]
+        SqlType::Interval => SqlValRef::Interval(Duration::microseconds(val.as_i64()?)),
+        #[cfg(all(feature = "time", not(feature = "datetime")))]
+        SqlType::Timestamp => SqlValRef::TimeTimestamp(time::PrimitiveDateTime::parse(
+            val.as_str()?,
+            SQLITE_TIME_DT_FORMAT,
+        )?),
+        #[cfg(all(feature = "time", not(feature = "datetime")))]
+        SqlType::Date => {
+            SqlValRef::TimeDate(time::Date::parse(val.as_str()?, crate::time::DATE_FORMAT)?)
+        }
+        #[cfg(all(feature = "time", not(feature = "datetime")))]
+        SqlType::Time => SqlValRef::TimeTime(time::Time::parse(val.as_str()?, SQLITE_TIME_FORMAT)?),
+        #[cfg(all(feature = "time", not(feature = "datetime")))]
+        SqlType::TimestampTz => SqlValRef::TimeTimestampTz(time::OffsetDateTime::parse(
+            val.as_str()?,
+            &time::format_description::well_known::Rfc3339,
+        )?),
+        #[cfg(all(feature = "time", not(feature = "datetime")))]
+        SqlType::Interval => SqlValRef::TimeInterval(time::Duration::microseconds(val.as_i64()?)),
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric { .. } => {
+            SqlValRef::Numeric(crate::decimal::from_ordered_text(val.as_str()?)?)
+        }
+        #[cfg(feature = "uuid")]
+        SqlType::Uuid => {
+            let bytes = val.as_blob()?;
+            SqlValRef::Uuid(uuid::Uuid::from_slice(bytes).map_err(|_| {
+                Error::CannotConvertSqlVal(ty.clone(), SqlVal::Blob(bytes.to_vec()))
+            })?)
+        }
+        SqlType::Inet => {
+            let text = val.as_str()?;
+            SqlValRef::Inet(text.parse().map_err(|_| {
+                Error::CannotConvertSqlVal(ty.clone(), SqlVal::Text(text.to_string()))
+            })?)
+        }
+        SqlType::Array(elem) => SqlValRef::Array(
+            *elem,
+            Cow::Owned(crate::json::array_from_json(*elem, val.as_str()?)?),
+        ),
         SqlType::Blob => SqlValRef::Blob(val.as_blob()?),
-        SqlType::Custom(v) => {
+        SqlType::Json => SqlValRef::Json(Cow::Borrowed(val.as_str()?)),
+        #[cfg(feature = "pg")]
+        SqlType::Custom(v @ SqlTypeCustom::Pg(_)) => {
             return Err(Error::IncompatibleCustomT(v.deref().clone(), BACKEND_NAME))
         }
+        SqlType::Custom(_) => SqlValRef::Custom(SqlValRefCustom::Sqlite(match val {
+            ValueRef::Integer(i) => SqliteValue::Integer(i),
+            ValueRef::Real(r) => SqliteValue::Real(r),
+            ValueRef::Text(_) => SqliteValue::Text(val.as_str()?.to_string()),
+            ValueRef::Blob(b) => SqliteValue::Blob(b.to_vec()),
+            ValueRef::Null => unreachable!("null values are handled above"),
+        })),
     })
 }
 
 fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
     match op {
-        Operation::AddTable(table) => Ok(create_table(table, false)),
-        Operation::AddTableIfNotExists(table) => Ok(create_table(table, true)),
+        Operation::AddTable(table) => create_table(table, false),
+        Operation::AddTableIfNotExists(table) => create_table(table, true),
         Operation::RemoveTable(name) => Ok(drop_table(name)),
         Operation::AddColumn(tbl, col) => add_column(tbl, col),
-        Operation::RemoveColumn(tbl, name) => Ok(remove_column(current, tbl, name)),
-        Operation::ChangeColumn(tbl, old, new) => Ok(change_column(current, tbl, old, Some(new))),
+        Operation::RemoveColumn(tbl, name) => remove_column(current, tbl, name),
+        Operation::ChangeColumn(tbl, old, new) => change_column(current, tbl, old, Some(new)),
+        Operation::AddFullText(tbl, col) => Ok(add_fulltext(tbl, col)),
+        Operation::RemoveFullText(tbl, col) => Ok(remove_fulltext(tbl, col)),
+        // SQLite has no enum types. Values are stored as text in
+        // columns declared with the enum's name.
+        Operation::AddEnum(_) | Operation::RemoveEnum(_) | Operation::ChangeEnum(_, _) => {
+            Ok(String::new())
+        }
     }
 }
 
-fn create_table(table: &ATable, allow_exists: bool) -> String {
+fn create_table(table: &ATable, allow_exists: bool) -> Result<String> {
     let coldefs = table
         .columns
         .iter()
         .map(define_column)
-        .collect::<Vec<String>>()
+        .collect::<Result<Vec<String>>>()?
         .join(",\n");
     let modifier = if allow_exists { "IF NOT EXISTS " } else { "" };
-    format!("CREATE TABLE {}{} (\n{}\n);", modifier, table.name, coldefs)
+    Ok(format!(
+        "CREATE TABLE {}{} (\n{}\n);",
+        modifier, table.name, coldefs
+    ))
 }
 
-fn define_column(col: &AColumn) -> String {
+fn define_column(col: &AColumn) -> Result<String> {
     let mut constraints: Vec<String> = Vec::new();
     if !col.nullable() {
         constraints.push("NOT NULL".to_string());
@@ -473,36 +940,60 @@ fn define_column(col: &AColumn) -> String {
     if col.unique() {
         constraints.push("UNIQUE".to_string());
     }
-    format!(
+    Ok(format!(
         "{} {} {}",
         &col.name(),
-        col_sqltype(col),
+        col_sqltype(col)?,
         constraints.join(" ")
-    )
+    ))
 }
 
-fn col_sqltype(col: &AColumn) -> Cow<str> {
-    match col.typeid() {
-        Ok(TypeIdentifier::Ty(ty)) => Cow::Borrowed(sqltype(&ty)),
+fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
+    Ok(match col.typeid() {
+        Ok(TypeIdentifier::Ty(ty)) => Cow::Borrowed(sqltype(&ty)?),
         Ok(TypeIdentifier::Name(name)) => Cow::Owned(name),
+        Ok(TypeIdentifier::Portable { sqlite, .. }) => Cow::Borrowed(sqlite.name()),
         // sqlite doesn't actually require that the column type be
         // specified
         Err(_) => Cow::Borrowed(""),
-    }
+    })
 }
 
-fn sqltype(ty: &SqlType) -> &'static str {
-    match ty {
+fn sqltype(ty: &SqlType) -> Result<&'static str> {
+    Ok(match ty {
         SqlType::Bool => "INTEGER",
+        SqlType::SmallInt => "INTEGER",
         SqlType::Int => "INTEGER",
         SqlType::BigInt => "INTEGER",
+        // Big-endian, sorting in numerical order
+        SqlType::UBigInt | SqlType::HugeInt | SqlType::UHugeInt => "BLOB",
         SqlType::Real => "REAL",
         SqlType::Text => "TEXT",
-        #[cfg(feature = "datetime")]
+        #[cfg(any(feature = "datetime", feature = "time"))]
         SqlType::Timestamp => "TEXT",
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        SqlType::Date | SqlType::Time | SqlType::TimestampTz => "TEXT",
+        // Microseconds
+        #[cfg(any(feature = "datetime", feature = "time"))]
+        SqlType::Interval => "INTEGER",
+        // Text which sorts in numerical order
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric { .. } => "TEXT",
+        #[cfg(feature = "uuid")]
+        SqlType::Uuid => "BLOB",
+        SqlType::Inet => "TEXT",
+        // JSON
+        SqlType::Array(_) => "TEXT",
         SqlType::Blob => "BLOB",
-        SqlType::Custom(_) => panic!("Custom types not supported by sqlite backend"),
-    }
+        SqlType::Json => "TEXT",
+        SqlType::Custom(SqlTypeCustom::Sqlite(affinity)) => affinity.name(),
+        #[cfg(feature = "pg")]
+        SqlType::Custom(SqlTypeCustom::Portable { sqlite, .. }) => sqlite.name(),
+        #[cfg(feature = "pg")]
+        SqlType::Custom(v @ SqlTypeCustom::Pg(_)) => {
+            return Err(Error::IncompatibleCustomT(v.clone(), BACKEND_NAME))
+        }
+    })
 }
 
 fn drop_table(name: &str) -> String {
@@ -510,16 +1001,32 @@ fn drop_table(name: &str) -> String {
 }
 
 fn add_column(tbl_name: &str, col: &AColumn) -> Result<String> {
-    let default: SqlVal = helper::column_default(col)?;
+    let default: SqlVal = match helper::column_default(col)? {
+        #[cfg(feature = "datetime")]
+        SqlVal::Interval(interval) => SqlVal::BigInt(crate::sqlval::interval_to_micros(&interval)),
+        #[cfg(feature = "time")]
+        SqlVal::TimeInterval(interval) => {
+            SqlVal::BigInt(crate::time::duration_to_micros(&interval)?)
+        }
+        #[cfg(feature = "decimal")]
+        SqlVal::Numeric(d) => SqlVal::Text(crate::decimal::to_ordered_text(&d)),
+        #[cfg(feature = "uuid")]
+        SqlVal::Uuid(u) => SqlVal::Blob(u.as_bytes().to_vec()),
+        SqlVal::Array(_, vals) => SqlVal::Text(crate::json::array_to_json(&vals)),
+        SqlVal::UBigInt(i) => SqlVal::Blob(i.to_be_bytes().to_vec()),
+        SqlVal::HugeInt(i) => SqlVal::Blob(hugeint_to_bytes(i).to_vec()),
+        SqlVal::UHugeInt(i) => SqlVal::Blob(i.to_be_bytes().to_vec()),
+        default => default,
+    };
     Ok(format!(
         "ALTER TABLE {} ADD COLUMN {} DEFAULT {};",
         tbl_name,
-        define_column(col),
+        define_column(col)?,
         helper::sql_literal_value(default)?
     ))
 }
 
-fn remove_column(current: &mut ADB, tbl_name: &str, name: &str) -> String {
+fn remove_column(current: &mut ADB, tbl_name: &str, name: &str) -> Result<String> {
     let old = current
         .get_table(tbl_name)
         .and_then(|table| table.column(name))
@@ -532,11 +1039,82 @@ fn remove_column(current: &mut ADB, tbl_name: &str, name: &str) -> String {
                 name,
                 tbl_name
             );
-            "".to_string()
+            Ok("".to_string())
         }
     }
 }
 
+/// `path` into a JSON document as a string literal in the syntax
+/// used by the JSON functions, e.g. `'$."a"[0]'`.
+fn json_path(path: &str) -> String {
+    let mut json_path = String::from("$");
+    for seg in crate::json::path_segments(path) {
+        if crate::json::is_index(seg) {
+            write!(json_path, "[{}]", seg).unwrap();
+        } else {
+            write!(json_path, ".\"{}\"", seg).unwrap();
+        }
+    }
+    format!("'{}'", json_path.replace('\'', "''"))
+}
+
+/// The FTS5 table holding the full-text index of `col`.
+fn fulltext_table(tbl_name: &str, col: &str) -> String {
+    format!("{}_{}_fts", tbl_name, col)
+}
+
+/// Creates an FTS5 table indexing `col` by rowid, populates it, and
+/// adds triggers to keep it in sync with the table. The index keeps
+/// its own copy of the text, rather than using the table as external
+/// content, as INSERT OR REPLACE does not fire delete triggers.
+fn add_fulltext(tbl_name: &str, col: &str) -> String {
+    let fts = fulltext_table(tbl_name, col);
+    let insert = format!(
+        "DELETE FROM {fts} WHERE rowid = new.rowid; INSERT INTO {fts}(rowid, {col}) VALUES (new.rowid, new.{col});",
+        fts = fts,
+        col = col
+    );
+    [
+        format!(
+            "CREATE VIRTUAL TABLE {} USING fts5({}, tokenize = 'porter unicode61');",
+            fts, col
+        ),
+        format!(
+            "INSERT INTO {}(rowid, {}) SELECT rowid, {} FROM {};",
+            fts, col, col, tbl_name
+        ),
+        format!(
+            "CREATE TRIGGER {fts}_insert AFTER INSERT ON {tbl} BEGIN {insert} END;",
+            fts = fts,
+            tbl = tbl_name,
+            insert = insert
+        ),
+        format!(
+            "CREATE TRIGGER {fts}_update AFTER UPDATE ON {tbl} BEGIN DELETE FROM {fts} WHERE rowid = old.rowid; {insert} END;",
+            fts = fts,
+            tbl = tbl_name,
+            insert = insert
+        ),
+        format!(
+            "CREATE TRIGGER {fts}_delete AFTER DELETE ON {tbl} BEGIN DELETE FROM {fts} WHERE rowid = old.rowid; END;",
+            fts = fts,
+            tbl = tbl_name
+        ),
+    ]
+    .join("\n")
+}
+
+fn remove_fulltext(tbl_name: &str, col: &str) -> String {
+    let fts = fulltext_table(tbl_name, col);
+    format!(
+        "DROP TRIGGER IF EXISTS {fts}_insert;\n\
+         DROP TRIGGER IF EXISTS {fts}_update;\n\
+         DROP TRIGGER IF EXISTS {fts}_delete;\n\
+         DROP TABLE IF EXISTS {fts};",
+        fts = fts
+    )
+}
+
 fn copy_table(old: &ATable, new: &ATable) -> String {
     let column_names = new
         .columns
@@ -559,7 +1137,7 @@ fn change_column(
     tbl_name: &str,
     old: &AColumn,
     new: Option<&AColumn>,
-) -> String {
+) -> Result<String> {
     let table = current.get_table(tbl_name);
     if table.is_none() {
         crate::warn!(
@@ -567,9 +1145,19 @@ fn change_column(
             &old.name(),
             tbl_name
         );
-        return "".to_string();
+        return Ok("".to_string());
     }
     let old_table = table.unwrap();
+    if let Some(col) = new {
+        // Such as a blob becoming a Uuid, which is still stored as a
+        // blob. Copying the table would not change the data.
+        if define_column(col)? == define_column(old)? {
+            let mut new_table = old_table.clone();
+            new_table.replace_column(col.clone());
+            current.replace_table(new_table);
+            return Ok("".to_string());
+        }
+    }
     let mut new_table = old_table.clone();
     new_table.name = tmp_table_name(&new_table.name);
     match new {
@@ -577,15 +1165,23 @@ fn change_column(
         None => new_table.remove_column(old.name()),
     }
     let stmts: [&str; 4] = [
-        &create_table(&new_table, false),
+        &create_table(&new_table, false)?,
         &copy_table(old_table, &new_table),
         &drop_table(&old_table.name),
         &format!("ALTER TABLE {} RENAME TO {};", &new_table.name, tbl_name),
     ];
-    let result = stmts.join("\n");
+    let mut result = stmts.join("\n");
     new_table.name = old_table.name.clone();
+    // The triggers are dropped with the old table, and rowids may
+    // have changed, so rebuild any full-text indexes.
+    for col in new_table.columns.iter().filter(|c| c.fulltext()) {
+        result.push('\n');
+        result.push_str(&remove_fulltext(tbl_name, col.name()));
+        result.push('\n');
+        result.push_str(&add_fulltext(tbl_name, col.name()));
+    }
     current.replace_table(new_table);
-    result
+    Ok(result)
 }
 
 pub fn sql_insert_or_update(table: &str, columns: &[Column], w: &mut impl Write) {
@@ -0,0 +1,120 @@
+//! Support for exact decimal numbers, using `rust_decimal`.
+
+#[cfg(feature = "sqlite")]
+use crate::Error::OutOfRange;
+use crate::{
+    Error::CannotConvertSqlVal, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql,
+};
+use rust_decimal::Decimal;
+#[cfg(feature = "sqlite")]
+use std::convert::TryFrom;
+
+/// The precision of a [Decimal] field without a `#[numeric]`
+/// attribute. Together with [DEFAULT_SCALE] this holds any `Decimal`
+/// exactly.
+pub const DEFAULT_PRECISION: u8 = 57;
+/// The scale of a [Decimal] field without a `#[numeric]` attribute.
+pub const DEFAULT_SCALE: u8 = 28;
+
+impl ToSql for Decimal {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Numeric(*self)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Numeric(*self)
+    }
+}
+impl FromSql for Decimal {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Numeric(d) => Ok(d),
+            _ => Err(CannotConvertSqlVal(Self::SQLTYPE, valref.into())),
+        }
+    }
+}
+impl FieldType for Decimal {
+    const SQLTYPE: SqlType = SqlType::Numeric {
+        precision: DEFAULT_PRECISION,
+        scale: DEFAULT_SCALE,
+    };
+    type RefType = Self;
+}
+
+// Decimals are stored as text by backends without a native decimal
+// type. To make comparisons of that text agree with comparisons of
+// the numbers, a nonzero number is written as its sign, the position
+// of its decimal point and its significant digits. The digits of
+// negative numbers are complemented, so that larger magnitudes sort
+// first, and terminated by a character sorting after every digit.
+//
+// For example, 12.5 is "230125", 0 is "1" and -12.5 is "069874~".
+
+/// Offset added to the position of the decimal point, which ranges
+/// from -27 (for 0.0...01) to 29, so that it is always positive.
+#[cfg(feature = "sqlite")]
+const POINT_OFFSET: i64 = 28;
+
+/// Converts `d` to text whose ordering matches that of the numbers.
+#[cfg(feature = "sqlite")]
+pub(crate) fn to_ordered_text(d: &Decimal) -> String {
+    if d.is_zero() {
+        return "1".to_string();
+    }
+    let d = d.normalize();
+    let all_digits = d.mantissa().unsigned_abs().to_string();
+    let point = all_digits.len() as i64 - i64::from(d.scale()) + POINT_OFFSET;
+    let digits = all_digits.trim_end_matches('0');
+    if d.is_sign_positive() {
+        format!("2{:02}{}", point, digits)
+    } else {
+        format!("0{:02}{}~", 99 - point, complement(digits))
+    }
+}
+
+/// Parses text produced by [to_ordered_text].
+#[cfg(feature = "sqlite")]
+pub(crate) fn from_ordered_text(text: &str) -> Result<Decimal> {
+    let invalid = || CannotConvertSqlVal(Decimal::SQLTYPE, SqlVal::Text(text.to_string()));
+    let (negative, rest) = match text.as_bytes().first() {
+        Some(b'1') if text.len() == 1 => return Ok(Decimal::ZERO),
+        Some(b'2') => (false, &text[1..]),
+        Some(b'0') => (true, text[1..].strip_suffix('~').ok_or_else(invalid)?),
+        _ => return Err(invalid()),
+    };
+    if rest.len() < 3 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let (point, digits) = rest.split_at(2);
+    let mut point: i64 = point.parse().map_err(|_| invalid())?;
+    let digits = if negative {
+        point = 99 - point;
+        complement(digits)
+    } else {
+        digits.to_string()
+    };
+    let point = point - POINT_OFFSET;
+    let mut mantissa: i128 = digits.parse().map_err(|_| OutOfRange)?;
+    let scale = digits.len() as i64 - point;
+    let scale = if scale < 0 {
+        mantissa = 10i128
+            .checked_pow(u32::try_from(-scale).map_err(|_| OutOfRange)?)
+            .and_then(|p| mantissa.checked_mul(p))
+            .ok_or(OutOfRange)?;
+        0
+    } else {
+        u32::try_from(scale).map_err(|_| OutOfRange)?
+    };
+    if negative {
+        mantissa = -mantissa;
+    }
+    Decimal::try_from_i128_with_scale(mantissa, scale).map_err(|_| OutOfRange)
+}
+
+/// Replaces each decimal digit `d` with `9 - d`.
+#[cfg(feature = "sqlite")]
+fn complement(digits: &str) -> String {
+    digits
+        .bytes()
+        .map(|b| char::from(b'9' - (b - b'0')))
+        .collect()
+}
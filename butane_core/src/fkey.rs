@@ -153,7 +153,7 @@ where
 {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
         Ok(ForeignKey {
-            valpk: SqlVal::from(valref).into(),
+            valpk: valref.try_into_owned()?.into(),
             val: OnceCell::new(),
         })
     }
@@ -0,0 +1,186 @@
+//! Support for storing serde-serializable values as JSON.
+
+#[cfg(feature = "sqlite")]
+use crate::ArrayElement;
+use crate::{
+    Error::CannotConvertSqlVal, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::borrow::Cow;
+#[cfg(feature = "sqlite")]
+use std::convert::TryFrom;
+use std::ops::{Deref, DerefMut};
+
+/// Wrapper storing any value which can be serialized and
+/// deserialized with serde as JSON. JSON is stored as `JSONB` on
+/// Postgres and as text on SQLite.
+///
+/// A type may instead be stored as JSON directly by annotating it
+/// with `#[butane_type(Json)]`.
+///
+/// # Examples
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Settings {
+///     theme: String,
+///     font_size: i32,
+/// }
+///
+/// #[model]
+/// struct User {
+///     id: i64,
+///     settings: Json<Settings>,
+/// }
+///
+/// let large = query!(User, settings.path("font_size") > 14).load(&conn)?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Json<T>(pub T);
+impl<T> Json<T> {
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T> From<T> for Json<T> {
+    fn from(val: T) -> Self {
+        Json(val)
+    }
+}
+impl<T> Deref for Json<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize> ToSql for Json<T> {
+    fn to_sql(&self) -> SqlVal {
+        to_sql(&self.0)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        to_sql_ref(&self.0)
+    }
+}
+impl<T: DeserializeOwned> FromSql for Json<T> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        from_sql_ref(valref).map(Json)
+    }
+}
+impl<T: Serialize + DeserializeOwned> FieldType for Json<T> {
+    const SQLTYPE: SqlType = SqlType::Json;
+    type RefType = Self;
+}
+impl<T> JsonType for Json<T> {}
+
+/// Marker trait for types stored as JSON, whose fields may be
+/// filtered by the values within them. Implemented for [Json] and by
+/// `#[butane_type(Json)]`.
+pub trait JsonType {}
+
+/// Serializes `val` as a JSON [SqlVal]. Used to implement [ToSql] for
+/// types stored as JSON.
+///
+/// # Panics
+/// Panics if `val` cannot be serialized as JSON, such as a map with
+/// keys which are not strings. Use [try_to_sql] to handle this.
+pub fn to_sql<T: Serialize + ?Sized>(val: &T) -> SqlVal {
+    try_to_sql(val).expect("value cannot be serialized as JSON")
+}
+
+/// Like [to_sql], but returns a [SqlValRef].
+pub fn to_sql_ref<T: Serialize + ?Sized>(val: &T) -> SqlValRef<'static> {
+    SqlValRef::Json(Cow::Owned(
+        serialize(val).expect("value cannot be serialized as JSON"),
+    ))
+}
+
+/// Like [to_sql], but returns an error if `val` cannot be serialized
+/// as JSON.
+pub fn try_to_sql<T: Serialize + ?Sized>(val: &T) -> Result<SqlVal> {
+    serialize(val).map(SqlVal::Json)
+}
+
+/// Deserializes a value from JSON. Used to implement [FromSql] for
+/// types stored as JSON. Text is also accepted, as JSON may have been
+/// stored in a text column.
+pub fn from_sql_ref<T: DeserializeOwned>(valref: SqlValRef) -> Result<T> {
+    match valref {
+        SqlValRef::Json(json) => Ok(serde_json::from_str(&json)?),
+        SqlValRef::Text(text) => Ok(serde_json::from_str(text)?),
+        _ => Err(CannotConvertSqlVal(SqlType::Json, valref.into())),
+    }
+}
+
+fn serialize<T: Serialize + ?Sized>(val: &T) -> Result<String> {
+    Ok(serde_json::to_string(val)?)
+}
+
+/// Converts a scalar value to the JSON it would be compared with in a
+/// JSON document, as needed by backends which compare JSON values
+/// rather than SQL values.
+#[cfg(any(feature = "pg", feature = "sqlite"))]
+pub(crate) fn sqlval_to_json(val: &SqlVal) -> Option<String> {
+    let json = match val {
+        SqlVal::Bool(b) => serde_json::Value::from(*b),
+        SqlVal::Int(i) => serde_json::Value::from(*i),
+        SqlVal::BigInt(i) => serde_json::Value::from(*i),
+        SqlVal::Real(r) => serde_json::Value::from(*r),
+        SqlVal::Text(t) | SqlVal::Enum(t) => serde_json::Value::from(t.as_str()),
+        SqlVal::Json(json) => return Some(json.clone()),
+        _ => return None,
+    };
+    Some(json.to_string())
+}
+
+/// Serializes the elements of an array as a JSON array, for backends
+/// without native arrays.
+#[cfg(feature = "sqlite")]
+pub(crate) fn array_to_json(vals: &[SqlVal]) -> String {
+    let elems: Vec<String> = vals
+        .iter()
+        .map(|val| sqlval_to_json(val).unwrap_or_else(|| "null".to_string()))
+        .collect();
+    format!("[{}]", elems.join(","))
+}
+
+/// Deserializes the elements of an array of type `elem` from a JSON
+/// array produced by [array_to_json].
+#[cfg(feature = "sqlite")]
+pub(crate) fn array_from_json(elem: ArrayElement, json: &str) -> Result<Vec<SqlVal>> {
+    let invalid = || CannotConvertSqlVal(SqlType::Array(elem), SqlVal::Text(json.to_string()));
+    let vals: Vec<serde_json::Value> = serde_json::from_str(json)?;
+    vals.into_iter()
+        .map(|val| {
+            let val = match elem {
+                ArrayElement::Bool => val.as_bool().map(SqlVal::Bool),
+                ArrayElement::Int => val
+                    .as_i64()
+                    .and_then(|i| i32::try_from(i).ok())
+                    .map(SqlVal::Int),
+                ArrayElement::BigInt => val.as_i64().map(SqlVal::BigInt),
+                ArrayElement::Real => val.as_f64().map(SqlVal::Real),
+                ArrayElement::Text => val.as_str().map(|s| SqlVal::Text(s.to_string())),
+            };
+            val.ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// The segments of a path into a JSON document, such as `a.b.0`.
+/// Segments consisting only of digits index arrays.
+pub(crate) fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('.').filter(|s| !s.is_empty())
+}
+
+/// Whether the path segment `seg` indexes an array.
+#[cfg(feature = "sqlite")]
+pub(crate) fn is_index(seg: &str) -> bool {
+    seg.bytes().all(|b| b.is_ascii_digit())
+}
@@ -10,11 +10,16 @@ pub mod codegen;
 pub mod custom;
 pub mod db;
 pub mod fkey;
+pub mod json;
 pub mod many;
 pub mod migrations;
 pub mod query;
 pub mod sqlval;
 
+#[cfg(feature = "decimal")]
+pub mod decimal;
+#[cfg(feature = "time")]
+pub mod time;
 #[cfg(feature = "uuid")]
 pub mod uuid;
 
@@ -136,6 +141,12 @@ pub enum Error {
     UnknownBackend(String),
     #[error("Range error")]
     OutOfRange,
+    #[error("Invalid query parameters: {0}")]
+    InvalidQueryParams(String),
+    #[error("Raw query columns do not match the result type: {0}")]
+    RawQueryColumns(String),
+    #[error("Invalid pagination cursor: {0}")]
+    InvalidCursor(String),
     #[error("Internal logic error {0}")]
     Internal(String),
     #[error("Cannot resolve type {0}. Are you missing a #[butane_type] attribute?")]
@@ -166,6 +177,9 @@ pub enum Error {
     #[cfg(feature = "datetime")]
     #[error("Chrono error {0}")]
     Chrono(#[from] chrono::ParseError),
+    #[cfg(feature = "time")]
+    #[error("Time error {0}")]
+    Time(#[from] ::time::error::Parse),
     #[error("RefCell error {0}")]
     CellBorrow(#[from] std::cell::BorrowMutError),
     #[cfg(feature = "tls")]
@@ -197,16 +211,58 @@ impl From<rusqlite::types::FromSqlError> for Error {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SqlType {
     Bool,
+    /// 2 bytes
+    SmallInt,
     /// 4 bytes
     Int,
     /// 8 bytes
     BigInt,
+    /// Unsigned 8 bytes, stored as `NUMERIC(20)` where the backend
+    /// supports it and as an 8 byte blob otherwise
+    UBigInt,
+    /// 16 bytes, stored as `NUMERIC(39)` where the backend supports it
+    /// and as a 16 byte blob otherwise
+    HugeInt,
+    /// Unsigned 16 bytes, stored like [SqlType::HugeInt]
+    UHugeInt,
     /// 8 byte float
     Real,
     Text,
-    #[cfg(feature = "datetime")]
+    #[cfg(any(feature = "datetime", feature = "time"))]
     Timestamp,
+    /// A date without a time
+    #[cfg(any(feature = "datetime", feature = "time"))]
+    Date,
+    /// A time of day without a date
+    #[cfg(any(feature = "datetime", feature = "time"))]
+    Time,
+    /// A timestamp with a time zone, stored in UTC
+    #[cfg(any(feature = "datetime", feature = "time"))]
+    TimestampTz,
+    /// A length of time, with microsecond precision
+    #[cfg(any(feature = "datetime", feature = "time"))]
+    Interval,
+    /// An exact decimal number with `precision` significant digits,
+    /// `scale` of which follow the decimal point
+    #[cfg(feature = "decimal")]
+    Numeric {
+        precision: u8,
+        scale: u8,
+    },
+    /// A UUID, stored natively where the backend supports it and as a
+    /// 16 byte blob otherwise
+    #[cfg(feature = "uuid")]
+    Uuid,
+    /// An IPv4 or IPv6 address, stored as text where the backend has
+    /// no network address type
+    Inet,
+    /// An array of values, stored natively where the backend supports
+    /// it and as JSON text otherwise
+    Array(ArrayElement),
     Blob,
+    /// JSON, stored as JSONB where the backend supports it and as
+    /// text otherwise
+    Json,
     Custom(SqlTypeCustom),
 }
 impl std::fmt::Display for SqlType {
@@ -214,19 +270,59 @@ impl std::fmt::Display for SqlType {
         use SqlType::*;
         match &self {
             Bool => "bool",
+            SmallInt => "small int",
             Int => "int",
             BigInt => "big int",
+            UBigInt => "unsigned big int",
+            HugeInt => "huge int",
+            UHugeInt => "unsigned huge int",
             Real => "float",
             Text => "string",
-            #[cfg(feature = "datetime")]
+            #[cfg(any(feature = "datetime", feature = "time"))]
             Timestamp => "timestamp",
+            #[cfg(any(feature = "datetime", feature = "time"))]
+            Date => "date",
+            #[cfg(any(feature = "datetime", feature = "time"))]
+            Time => "time",
+            #[cfg(any(feature = "datetime", feature = "time"))]
+            TimestampTz => "timestamp with time zone",
+            #[cfg(any(feature = "datetime", feature = "time"))]
+            Interval => "interval",
+            #[cfg(feature = "decimal")]
+            Numeric { .. } => "numeric",
+            #[cfg(feature = "uuid")]
+            Uuid => "uuid",
+            Inet => "inet",
+            Array(_) => "array",
             Blob => "blob",
+            Json => "json",
             Custom(_) => "custom",
         }
         .fmt(f)
     }
 }
 
+/// The types which may be the elements of a [SqlType::Array].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ArrayElement {
+    Bool,
+    Int,
+    BigInt,
+    Real,
+    Text,
+}
+impl From<ArrayElement> for SqlType {
+    fn from(elem: ArrayElement) -> Self {
+        match elem {
+            ArrayElement::Bool => SqlType::Bool,
+            ArrayElement::Int => SqlType::Int,
+            ArrayElement::BigInt => SqlType::BigInt,
+            ArrayElement::Real => SqlType::Real,
+            ArrayElement::Text => SqlType::Text,
+        }
+    }
+}
+
 #[cfg(feature = "log")]
 pub use log::debug;
 #[cfg(feature = "log")]
@@ -2,6 +2,7 @@
 //! CLI tool, there is no need to use this module. Even if applying
 //! migrations without this tool, you are unlikely to need this module.
 
+use crate::custom::SqliteAffinity;
 use crate::{Error, Result, SqlType, SqlVal};
 use serde::{de::Deserializer, de::Visitor, ser::Serializer, Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -15,6 +16,12 @@ use std::collections::{HashMap, HashSet};
 pub enum TypeIdentifier {
     Ty(SqlType),
     Name(String),
+    /// A custom type named `name` by backends which support it
+    /// natively, and stored with the given affinity by Sqlite.
+    Portable {
+        name: String,
+        sqlite: SqliteAffinity,
+    },
 }
 impl From<SqlType> for TypeIdentifier {
     fn from(ty: SqlType) -> Self {
@@ -136,12 +143,15 @@ impl TypeResolver {
 pub struct ADB {
     tables: HashMap<String, ATable>,
     extra_types: HashMap<TypeKey, DeferredSqlType>,
+    #[serde(default)]
+    enums: HashMap<String, AEnum>,
 }
 impl ADB {
     pub fn new() -> Self {
         ADB {
             tables: HashMap::new(),
             extra_types: HashMap::new(),
+            enums: HashMap::new(),
         }
     }
     pub fn tables(&self) -> impl Iterator<Item = &ATable> {
@@ -162,6 +172,84 @@ impl ADB {
     pub fn add_type(&mut self, key: TypeKey, sqltype: DeferredSqlType) {
         self.extra_types.insert(key, sqltype);
     }
+    pub fn enums(&self) -> impl Iterator<Item = &AEnum> {
+        self.enums.values()
+    }
+    pub fn get_enum<'a>(&'a self, name: &str) -> Option<&'a AEnum> {
+        self.enums.get(name)
+    }
+    pub fn replace_enum(&mut self, e: AEnum) {
+        self.enums.insert(e.name.clone(), e);
+    }
+    pub fn remove_enum(&mut self, name: &str) {
+        self.enums.remove(name);
+    }
+    /// The names of enums which no column uses.
+    pub fn unused_enums(&self) -> Vec<String> {
+        self.enums
+            .keys()
+            .filter(|name| {
+                let typeid = TypeIdentifier::Name(name.to_string());
+                !self
+                    .tables()
+                    .flat_map(|table| table.columns.iter())
+                    .any(|col| col.typeid().ok().as_ref() == Some(&typeid))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Merge the changes made by `other` since their common ancestor
+    /// `base` into this database. Tables, columns and enums added,
+    /// changed or removed on only one side keep that change. A change
+    /// made differently on both sides is a conflict and an error, as
+    /// is changing a table on both sides if either side changes or
+    /// removes one of its columns.
+    pub fn merge(&mut self, base: &ADB, other: &ADB) -> Result<()> {
+        let names: HashSet<String> = base
+            .tables
+            .keys()
+            .chain(self.tables.keys())
+            .chain(other.tables.keys())
+            .cloned()
+            .collect();
+        for name in names {
+            let ours = self.tables.get(&name);
+            let merged = match (base.tables.get(&name), ours, other.tables.get(&name)) {
+                (base, Some(ours), Some(theirs)) => Some(merge_table(base, ours, theirs)?),
+                (base, ours, theirs) => merge3(base, ours, theirs, &format!("table {}", name))?,
+            };
+            match merged {
+                Some(table) => self.replace_table(table),
+                None => self.remove_table(&name),
+            }
+        }
+        for (key, ty) in other.types() {
+            self.extra_types
+                .entry(key.clone())
+                .or_insert_with(|| ty.clone());
+        }
+        let names: HashSet<String> = base
+            .enums
+            .keys()
+            .chain(self.enums.keys())
+            .chain(other.enums.keys())
+            .cloned()
+            .collect();
+        for name in names {
+            let merged = merge3(
+                base.enums.get(&name),
+                self.enums.get(&name),
+                other.enums.get(&name),
+                &format!("enum {}", name),
+            )?;
+            match merged {
+                Some(e) => self.replace_enum(e),
+                None => self.remove_enum(&name),
+            }
+        }
+        Ok(())
+    }
 
     /// Fixup as many DeferredSqlType::Deferred instances as possible
     /// into DeferredSqlType::Known
@@ -235,12 +323,110 @@ impl ADB {
                     t.replace_column(new);
                 }
             }
+            AddFullText(table, name) => self.set_fulltext(&table, &name, true),
+            RemoveFullText(table, name) => self.set_fulltext(&table, &name, false),
+            AddEnum(e) => self.replace_enum(e),
+            RemoveEnum(name) => self.remove_enum(&name),
+            ChangeEnum(_, new) => self.replace_enum(new),
+        }
+    }
+
+    fn set_fulltext(&mut self, table: &str, name: &str, fulltext: bool) {
+        if let Some(col) = self
+            .tables
+            .get_mut(table)
+            .and_then(|t| t.columns.iter_mut().find(|c| c.name == name))
+        {
+            col.fulltext = fulltext;
         }
     }
 }
 
+/// Abstract representation of an enumerated type, created as a
+/// native type by backends which support them (currently Postgres).
+/// Three-way merge of a table present on both sides, column by column.
+fn merge_table(base: Option<&ATable>, ours: &ATable, theirs: &ATable) -> Result<ATable> {
+    if let Some(base) = base {
+        // Backends may change or remove a column by rebuilding the
+        // table from the definition on that side alone, which would
+        // lose the other side's changes to the table.
+        let rebuilds = |side: &ATable| {
+            base.columns
+                .iter()
+                .any(|col| side.column(&col.name) != Some(col))
+        };
+        if ours != base && theirs != base && (rebuilds(ours) || rebuilds(theirs)) {
+            return Err(Error::MigrationError(format!(
+                "Both migrations change table {}, and changing or removing a column may rebuild it",
+                ours.name
+            )));
+        }
+    }
+    let mut merged = ours.clone();
+    let mut names: Vec<&String> = ours.columns.iter().map(|c| &c.name).collect();
+    for col in base.iter().flat_map(|t| &t.columns).chain(&theirs.columns) {
+        if !names.contains(&&col.name) {
+            names.push(&col.name);
+        }
+    }
+    for name in names {
+        let what = format!("column {}.{}", ours.name, name);
+        let col = merge3(
+            base.and_then(|t| t.column(name)),
+            ours.column(name),
+            theirs.column(name),
+            &what,
+        )?;
+        match col {
+            Some(col) => merged.replace_column(col),
+            None => merged.remove_column(name),
+        }
+    }
+    Ok(merged)
+}
+
+/// Three-way merge of a single item, given its state in the common
+/// ancestor and on each side. `None` means the item does not exist.
+fn merge3<T: Clone + PartialEq>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+    what: &str,
+) -> Result<Option<T>> {
+    if theirs == base || theirs == ours {
+        Ok(ours.cloned())
+    } else if ours == base {
+        Ok(theirs.cloned())
+    } else {
+        Err(Error::MigrationError(format!(
+            "Conflicting changes to {}",
+            what
+        )))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AEnum {
+    pub name: String,
+    /// The values of the type, in order.
+    pub variants: Vec<String>,
+}
+impl AEnum {
+    pub fn new(name: String, variants: Vec<String>) -> AEnum {
+        AEnum { name, variants }
+    }
+    /// Whether `new` can be made from this type by only adding
+    /// values, leaving the existing values in the same order.
+    pub fn is_extended_by(&self, new: &AEnum) -> bool {
+        let mut remaining = new.variants.iter();
+        self.variants
+            .iter()
+            .all(|v| remaining.any(|other| other == v))
+    }
+}
+
 /// Abstract representation of a database table schema.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ATable {
     pub name: String,
     pub columns: Vec<AColumn>,
@@ -315,6 +501,9 @@ pub struct AColumn {
     #[serde(default)]
     unique: bool,
     default: Option<SqlVal>,
+    /// Whether the column has a full-text search index.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    fulltext: bool,
 }
 impl AColumn {
     pub fn new(
@@ -334,6 +523,7 @@ impl AColumn {
             auto,
             unique,
             default,
+            fulltext: false,
         }
     }
     /// Simple column that is non-null, non-auto, non-pk, non-unique with no default
@@ -355,6 +545,15 @@ impl AColumn {
     pub fn default(&self) -> &Option<SqlVal> {
         &self.default
     }
+    /// Whether the column has a full-text search index, which is
+    /// added and removed with [Operation::AddFullText] and
+    /// [Operation::RemoveFullText].
+    pub fn fulltext(&self) -> bool {
+        self.fulltext
+    }
+    pub fn set_fulltext(&mut self, fulltext: bool) {
+        self.fulltext = fulltext;
+    }
     pub fn typeid(&self) -> Result<TypeIdentifier> {
         match &self.sqltype {
             DeferredSqlType::KnownId(t) => Ok(t.clone()),
@@ -389,21 +588,45 @@ pub enum Operation {
     AddColumn(String, AColumn),
     RemoveColumn(String, String),
     ChangeColumn(String, AColumn, AColumn),
+    /// Add a full-text search index on a column, given by table and
+    /// column name.
+    AddFullText(String, String),
+    /// Remove the full-text search index on a column.
+    RemoveFullText(String, String),
+    AddEnum(AEnum),
+    RemoveEnum(String),
+    /// Change the values of an enum, given its old and new definitions.
+    ChangeEnum(AEnum, AEnum),
 }
 
 /// Determine the operations necessary to move the database schema from `old` to `new`.
 pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
     let mut ops: Vec<Operation> = Vec::new();
+    // Enums must exist before the columns using them
+    for e in new.enums() {
+        match old.get_enum(&e.name) {
+            None => ops.push(Operation::AddEnum(e.clone())),
+            Some(old_enum) if old_enum != e => {
+                ops.push(Operation::ChangeEnum(old_enum.clone(), e.clone()))
+            }
+            Some(_) => (),
+        }
+    }
     let new_names: HashSet<&String> = new.tables.keys().collect();
     let old_names: HashSet<&String> = old.tables.keys().collect();
     let new_tables = new_names.difference(&old_names);
     for added in new_tables {
         let added: &str = added.as_ref();
-        ops.push(Operation::AddTable(
-            new.tables.get(added).expect("no table").clone(),
-        ));
+        let table = new.tables.get(added).expect("no table");
+        // Full-text indexes are added separately, once the table exists
+        let mut plain = table.clone();
+        plain.columns.iter_mut().for_each(|c| c.fulltext = false);
+        ops.push(Operation::AddTable(plain));
+        ops.extend(fulltext_ops(table, Operation::AddFullText));
     }
     for removed in old_names.difference(&new_names) {
+        let table = old.tables.get(*removed).expect("no table");
+        ops.extend(fulltext_ops(table, Operation::RemoveFullText));
         ops.push(Operation::RemoveTable((*removed).to_string()));
     }
     for table in new_names.intersection(&old_names) {
@@ -413,9 +636,27 @@ pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
             new.tables.get(table).expect("no table"),
         ));
     }
+    for e in old.enums() {
+        if new.get_enum(&e.name).is_none() {
+            ops.push(Operation::RemoveEnum(e.name.clone()));
+        }
+    }
     ops
 }
 
+/// An operation made by `op` for each column of `table` with a
+/// full-text index.
+fn fulltext_ops(
+    table: &ATable,
+    op: fn(String, String) -> Operation,
+) -> impl Iterator<Item = Operation> + '_ {
+    table
+        .columns
+        .iter()
+        .filter(|c| c.fulltext)
+        .map(move |c| op(table.name.clone(), c.name.clone()))
+}
+
 fn col_by_name<'a>(columns: &'a [AColumn], name: &str) -> Option<&'a AColumn> {
     columns.iter().find(|c| c.name == name)
 }
@@ -427,12 +668,21 @@ fn diff_table(old: &ATable, new: &ATable) -> Vec<Operation> {
     let added_names = new_names.difference(&old_names);
     for added in added_names {
         let added: &str = added.as_ref();
-        ops.push(Operation::AddColumn(
-            new.name.clone(),
-            col_by_name(&new.columns, added).unwrap().clone(),
-        ));
+        let col = col_by_name(&new.columns, added).unwrap();
+        let mut plain = col.clone();
+        plain.fulltext = false;
+        ops.push(Operation::AddColumn(new.name.clone(), plain));
+        if col.fulltext {
+            ops.push(Operation::AddFullText(new.name.clone(), col.name.clone()));
+        }
     }
     for removed in old_names.difference(&new_names) {
+        if col_by_name(&old.columns, removed).unwrap().fulltext {
+            ops.push(Operation::RemoveFullText(
+                old.name.clone(),
+                (*removed).to_string(),
+            ));
+        }
         ops.push(Operation::RemoveColumn(
             old.name.clone(),
             (*removed).to_string(),
@@ -445,11 +695,32 @@ fn diff_table(old: &ATable, new: &ATable) -> Vec<Operation> {
         if col == old_col {
             continue;
         }
-        ops.push(Operation::ChangeColumn(
-            new.name.clone(),
-            old_col.clone(),
-            col.clone(),
-        ));
+        if old_col.fulltext && !col.fulltext {
+            ops.push(Operation::RemoveFullText(
+                new.name.clone(),
+                colname.to_string(),
+            ));
+        }
+        // Any full-text index is removed before, or added after,
+        // changing the column itself.
+        let fulltext = old_col.fulltext && col.fulltext;
+        let mut old_plain = old_col.clone();
+        old_plain.fulltext = fulltext;
+        let mut new_plain = col.clone();
+        new_plain.fulltext = fulltext;
+        if old_plain != new_plain {
+            ops.push(Operation::ChangeColumn(
+                new.name.clone(),
+                old_plain,
+                new_plain,
+            ));
+        }
+        if col.fulltext && !old_col.fulltext {
+            ops.push(Operation::AddFullText(
+                new.name.clone(),
+                colname.to_string(),
+            ));
+        }
     }
     ops
 }
@@ -1,4 +1,4 @@
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
+use super::adb::{AEnum, ATable, DeferredSqlType, TypeKey, ADB};
 use super::fs::{Filesystem, OsFilesystem};
 use super::{Migration, MigrationMut, Migrations, MigrationsMut};
 use crate::{ConnectionMethods, DataObject, Result};
@@ -6,6 +6,7 @@ use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::fs::{File, OpenOptions};
 
 use std::io::{Read, Write};
@@ -20,12 +21,17 @@ struct MigrationInfo {
     /// The migration this one is based on, or None if this is the
     /// first migration in the chain
     from_name: Option<String>,
+    /// Further migrations this one is based on, if it is a merge
+    /// migration
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merged_from: Vec<String>,
     backends: Vec<String>,
 }
 impl MigrationInfo {
     fn new() -> Self {
         MigrationInfo {
             from_name: None,
+            merged_from: Vec::new(),
             backends: Vec::new(),
         }
     }
@@ -134,6 +140,20 @@ impl MigrationMut for FsMigration {
         Ok(())
     }
 
+    fn write_enum(&mut self, e: &AEnum) -> Result<()> {
+        self.write_contents(
+            &format!("{}.enum", e.name),
+            serde_json::to_string(e)?.as_bytes(),
+        )
+    }
+
+    fn delete_enum(&mut self, name: &str) -> Result<()> {
+        self.ensure_dir()?;
+        let path = self.root.join(format!("{}.enum", name));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
     fn add_sql(&mut self, backend_name: &str, up_sql: &str, down_sql: &str) -> Result<()> {
         self.write_sql(&format!("{}_up", backend_name), up_sql)?;
         self.write_sql(&format!("{}_down", backend_name), down_sql)?;
@@ -173,6 +193,12 @@ impl MigrationMut for FsMigration {
         info.from_name = prev;
         self.write_info(&info)
     }
+
+    fn set_merged_from(&mut self, others: Vec<String>) -> Result<()> {
+        let mut info = self.info()?;
+        info.merged_from = others;
+        self.write_info(&info)
+    }
 }
 
 impl Migration for FsMigration {
@@ -189,6 +215,9 @@ impl Migration for FsMigration {
                     if name.ends_with(".table") {
                         let table: ATable = serde_json::from_reader(self.fs.read(&entry)?)?;
                         db.replace_table(table)
+                    } else if name.ends_with(".enum") {
+                        let e: AEnum = serde_json::from_reader(self.fs.read(&entry)?)?;
+                        db.replace_enum(e)
                     } else if name == TYPES_FILENAME {
                         let types: SqlTypeMap = serde_json::from_reader(
                             self.fs.read(&self.root.join(TYPES_FILENAME))?,
@@ -209,6 +238,10 @@ impl Migration for FsMigration {
         Ok(self.info()?.from_name.map(Cow::from))
     }
 
+    fn merged_from(&self) -> Result<Vec<String>> {
+        Ok(self.info()?.merged_from)
+    }
+
     fn name(&self) -> Cow<str> {
         // There should be no way our root has no name portion
         self.root.file_name().unwrap().to_string_lossy()
@@ -263,6 +296,34 @@ impl FsMigrations {
             }
         }
     }
+    /// Get the migrations which no other migration is based on, in
+    /// order of name. Normally there is just one, the latest
+    /// migration. Migrations created independently from the same
+    /// parent, such as on two branches of version control, result in
+    /// several heads, which must be joined with
+    /// [create_merge_migration][MigrationsMut::create_merge_migration]
+    /// before further migrations are created.
+    pub fn heads(&self) -> Result<Vec<FsMigration>> {
+        let mut migrations: Vec<FsMigration> = Vec::new();
+        if self.root.exists() {
+            for path in self.fs.list_dir(&self.root)? {
+                if path.is_dir() && path.file_name() != Some(OsStr::new("current")) {
+                    migrations.push(FsMigration {
+                        fs: self.fs.clone(),
+                        root: path,
+                    });
+                }
+            }
+        }
+        let mut based_on: Vec<String> = Vec::new();
+        for m in &migrations {
+            based_on.extend(m.migration_from()?.map(|s| s.to_string()));
+            based_on.extend(m.merged_from()?);
+        }
+        migrations.retain(|m| !based_on.iter().any(|name| *name == m.name()));
+        migrations.sort_by(|a, b| a.name().cmp(&b.name()));
+        Ok(migrations)
+    }
     fn save_state(&mut self, state: &MigrationsState) -> Result<()> {
         let path = self.root.join("state.json");
         let mut f = self.fs.write(&path)?;
@@ -312,8 +373,13 @@ impl MigrationsMut for FsMigrations {
     fn add_migration(&mut self, m: Self::M) -> Result<()> {
         // Update state
         let from_name = m.migration_from()?.map(|s| s.to_string());
+        let merged_from = m.merged_from()?;
         let mut state = self.get_state()?;
-        if state.latest.is_none() || state.latest == from_name {
+        let extends_latest = match &state.latest {
+            None => true,
+            Some(latest) => from_name.as_ref() == Some(latest) || merged_from.contains(latest),
+        };
+        if extends_latest {
             state.latest = Some(m.name().to_string());
             self.save_state(&state)?;
         }
@@ -0,0 +1,229 @@
+//! Checks for migration operations which may lose data or lock
+//! tables when applied to a database which already contains data.
+
+use super::adb::{AColumn, Operation, TypeIdentifier};
+use crate::db::Backend;
+use crate::SqlType;
+use std::fmt;
+
+/// How risky an operation is to apply to a database containing data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Neither loses data nor blocks access to existing tables for
+    /// any significant time.
+    Safe,
+    /// Rewrites or otherwise locks a table for the duration of the
+    /// migration, or may fail depending on the existing rows.
+    Locking,
+    /// May irrecoverably lose existing data.
+    DataLosing,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Safe => "safe",
+            Severity::Locking => "locking",
+            Severity::DataLosing => "data-losing",
+        }
+        .fmt(f)
+    }
+}
+
+/// A problem found with a migration operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub severity: Severity,
+    pub message: String,
+}
+impl Lint {
+    fn new(severity: Severity, message: String) -> Self {
+        Lint { severity, message }
+    }
+}
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Check `ops`, as they would be applied by `backend`, for
+/// operations which are not safe. Safe operations produce no lints.
+pub fn lint(backend: &impl Backend, ops: &[Operation]) -> Vec<Lint> {
+    ops.iter()
+        .flat_map(|op| lint_operation(backend, op))
+        .collect()
+}
+
+/// The most severe of `lints`, or `Severity::Safe` if there are none.
+pub fn max_severity(lints: &[Lint]) -> Severity {
+    lints
+        .iter()
+        .map(|l| l.severity)
+        .max()
+        .unwrap_or(Severity::Safe)
+}
+
+fn lint_operation(backend: &impl Backend, op: &Operation) -> Vec<Lint> {
+    use Operation::*;
+    match op {
+        AddTable(_) | AddTableIfNotExists(_) => Vec::new(),
+        RemoveTable(table) => vec![Lint::new(
+            Severity::DataLosing,
+            format!("drops table {} and all its rows", table),
+        )],
+        AddColumn(table, col) => {
+            if col.nullable() || col.default().is_some() || col.is_auto() {
+                Vec::new()
+            } else {
+                // Existing rows are silently given a placeholder value
+                // which is indistinguishable from real data.
+                vec![Lint::new(
+                    Severity::DataLosing,
+                    format!(
+                        "adds non-nullable column {}.{} without a default; existing rows are given a placeholder value",
+                        table,
+                        col.name()
+                    ),
+                )]
+            }
+        }
+        RemoveColumn(table, col) => vec![Lint::new(
+            Severity::DataLosing,
+            format!("drops column {}.{}", table, col),
+        )],
+        ChangeColumn(table, old, new) => lint_change_column(backend, table, old, new),
+        AddFullText(table, col) => vec![Lint::new(
+            Severity::Locking,
+            format!("builds a full-text index on column {}.{}", table, col),
+        )],
+        RemoveFullText(_, _) | AddEnum(_) | RemoveEnum(_) => Vec::new(),
+        ChangeEnum(old, new) => {
+            if backend.has_native_enums() && !old.is_extended_by(new) {
+                vec![Lint::new(
+                    Severity::Locking,
+                    format!(
+                        "recreates enum {} and rewrites the tables using it; fails if any row uses a removed value",
+                        new.name
+                    ),
+                )]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn lint_change_column(
+    backend: &impl Backend,
+    table: &str,
+    old: &AColumn,
+    new: &AColumn,
+) -> Vec<Lint> {
+    let name = format!("{}.{}", table, new.name());
+    let mut lints = Vec::new();
+    let type_changed = old.typeid().ok() != new.typeid().ok();
+    // Some backends alter most columns in place, although changing
+    // the type still rewrites the table. Other changes, and all
+    // changes on other backends, copy the table.
+    let in_place = backend.alters_columns_in_place()
+        && old.is_pk() == new.is_pk()
+        && !old.is_auto()
+        && !new.is_auto();
+    if !in_place || type_changed {
+        lints.push(Lint::new(
+            Severity::Locking,
+            format!("changes column {} by rewriting table {}", name, table),
+        ));
+    }
+    match (old.typeid(), new.typeid()) {
+        (Ok(old_ty), Ok(new_ty)) if !is_lossless_conversion(&old_ty, &new_ty) => {
+            lints.push(Lint::new(
+                Severity::DataLosing,
+                format!(
+                    "changes the type of column {}; existing values may not be representable",
+                    name
+                ),
+            ))
+        }
+        (Ok(TypeIdentifier::Ty(old_ty)), Ok(TypeIdentifier::Ty(new_ty)))
+            if !backend.converts_column_values(&old_ty, &new_ty) =>
+        {
+            lints.push(Lint::new(
+                Severity::DataLosing,
+                format!(
+                    "changes the type of column {} without converting existing values",
+                    name
+                ),
+            ))
+        }
+        _ => (),
+    }
+    if old.nullable() && !new.nullable() {
+        lints.push(Lint::new(
+            Severity::Locking,
+            format!(
+                "makes column {} non-nullable; fails if any row is null",
+                name
+            ),
+        ));
+    }
+    if !old.unique() && new.unique() {
+        lints.push(Lint::new(
+            Severity::Locking,
+            format!(
+                "makes column {} unique; fails if any values are duplicated",
+                name
+            ),
+        ));
+    }
+    lints
+}
+
+/// Whether every value of type `old` can be converted to `new` without loss.
+fn is_lossless_conversion(old: &TypeIdentifier, new: &TypeIdentifier) -> bool {
+    use SqlType::*;
+    if old == new {
+        return true;
+    }
+    match (old, new) {
+        #[cfg(feature = "decimal")]
+        (
+            TypeIdentifier::Ty(Numeric {
+                precision: old_precision,
+                scale: old_scale,
+            }),
+            TypeIdentifier::Ty(Numeric { precision, scale }),
+        ) => {
+            // Neither the digits after the point nor those before it may shrink
+            let integer_digits = |p: &u8, s: &u8| i16::from(*p) - i16::from(*s);
+            scale >= old_scale
+                && integer_digits(precision, scale) >= integer_digits(old_precision, old_scale)
+        }
+        #[cfg(feature = "uuid")]
+        (TypeIdentifier::Ty(Uuid), TypeIdentifier::Ty(Blob | Text)) => true,
+        // Declaring Sqlite storage for a custom type leaves other backends unchanged
+        (TypeIdentifier::Name(old), TypeIdentifier::Portable { name, .. }) => old == name,
+        (TypeIdentifier::Ty(old), TypeIdentifier::Ty(new)) => matches!(
+            (old, new),
+            (Bool, SmallInt)
+                | (Bool, Int)
+                | (Bool, BigInt)
+                | (SmallInt, Int)
+                | (SmallInt, BigInt)
+                | (Int, BigInt)
+                | (SmallInt, Real)
+                | (Int, Real)
+                | (SmallInt | Int | BigInt | UBigInt, HugeInt)
+                | (UBigInt, UHugeInt)
+                | (Bool, Text)
+                | (SmallInt, Text)
+                | (Int, Text)
+                | (BigInt, Text)
+                | (UBigInt | HugeInt | UHugeInt, Text)
+                | (Real, Text)
+                | (Json, Text)
+                | (Inet, Text)
+        ),
+        _ => false,
+    }
+}
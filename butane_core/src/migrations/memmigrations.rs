@@ -1,4 +1,4 @@
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
+use super::adb::{AEnum, ATable, DeferredSqlType, TypeKey, ADB};
 use super::{ButaneMigration, Migration, MigrationMut, Migrations, MigrationsMut};
 use crate::query::BoolExpr;
 use crate::{ConnectionMethods, DataObject, Result};
@@ -12,6 +12,8 @@ pub struct MemMigration {
     name: String,
     db: ADB,
     from: Option<String>,
+    #[serde(default)]
+    merged_from: Vec<String>,
     up: HashMap<String, String>,
     down: HashMap<String, String>,
 }
@@ -22,6 +24,7 @@ impl MemMigration {
             name,
             db: ADB::new(),
             from: None,
+            merged_from: Vec::new(),
             up: HashMap::new(),
             down: HashMap::new(),
         }
@@ -39,6 +42,10 @@ impl Migration for MemMigration {
         Ok(self.from.as_ref().map(Cow::from))
     }
 
+    fn merged_from(&self) -> Result<Vec<String>> {
+        Ok(self.merged_from.clone())
+    }
+
     fn name(&self) -> Cow<str> {
         Cow::from(&self.name)
     }
@@ -73,6 +80,14 @@ impl MigrationMut for MemMigration {
         self.db.remove_table(table);
         Ok(())
     }
+    fn write_enum(&mut self, e: &AEnum) -> Result<()> {
+        self.db.replace_enum(e.clone());
+        Ok(())
+    }
+    fn delete_enum(&mut self, name: &str) -> Result<()> {
+        self.db.remove_enum(name);
+        Ok(())
+    }
     fn add_sql(&mut self, backend_name: &str, up_sql: &str, down_sql: &str) -> Result<()> {
         self.up.insert(backend_name.to_string(), up_sql.to_string());
         self.down
@@ -88,6 +103,10 @@ impl MigrationMut for MemMigration {
         self.from = prev;
         Ok(())
     }
+    fn set_merged_from(&mut self, others: Vec<String>) -> Result<()> {
+        self.merged_from = others;
+        Ok(())
+    }
 }
 
 /// A collection of migrations stored in memory.
@@ -139,10 +158,10 @@ impl MigrationsMut for MemMigrations {
     fn add_migration(&mut self, m: Self::M) -> Result<()> {
         let new_latest = match &self.latest {
             None => true,
-            Some(latest_name) => match m.migration_from()? {
-                None => false,
-                Some(name) => name.as_ref() == latest_name.as_str(),
-            },
+            Some(latest_name) => {
+                m.migration_from()?.as_deref() == Some(latest_name.as_str())
+                    || m.merged_from()?.contains(latest_name)
+            }
         };
         if new_latest {
             self.latest = Some(m.name().to_string());
@@ -1,8 +1,9 @@
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
+use super::adb::{AEnum, ATable, DeferredSqlType, TypeKey, ADB};
 use super::ButaneMigration;
 use crate::db::ConnectionMethods;
 use crate::query::{BoolExpr, Expr};
-use crate::{db, sqlval::ToSql, DataObject, DataResult, Error, Result};
+use crate::{db, sqlval::ToSql, DataObject, Error, Result};
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::cmp::PartialEq;
 
@@ -22,6 +23,13 @@ pub trait Migration: PartialEq {
     where
         Self: Sized;
 
+    /// Get the names of any further migrations this one is based
+    /// on. Only a merge migration, which joins migrations created
+    /// independently from the same parent, has any.
+    fn merged_from(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
     /// The name of this migration.
     fn name(&self) -> Cow<str>;
 
@@ -34,30 +42,36 @@ pub trait Migration: PartialEq {
     /// The names of the backends this migration has sql for.
     fn sql_backends(&self) -> Result<Vec<String>>;
 
+    /// Checksum of the backend-specific commands to apply this
+    /// migration. This is recorded when the migration is applied and
+    /// used to detect migrations which have been edited since.
+    fn checksum(&self, backend_name: &str) -> Result<Option<String>> {
+        Ok(self.up_sql(backend_name)?.map(|sql| sql_checksum(&sql)))
+    }
+
     /// Apply the migration to a database connection. The connection
     /// must be for the same type of database as this and the database
     /// must be in the state of the migration prior to this one
     fn apply(&self, conn: &mut impl db::BackendConnection) -> Result<()> {
-        let backend_name = conn.backend_name();
+        let backend = conn.backend();
         let tx = conn.transaction()?;
-        let sql = self
-            .up_sql(backend_name)?
-            .ok_or_else(|| Error::UnknownBackend(backend_name.to_string()))?;
-        tx.execute(&sql)?;
-        self.mark_applied(&tx)?;
+        apply_with(self, &tx, &backend)?;
         tx.commit()
     }
 
     /// Mark the migration as being applied without doing any
     /// work. Use carefully -- the caller must ensure that the
     /// database schema already matches that expected by this
-    /// migration.
+    /// migration. The checksum of the sql which would have been run
+    /// is recorded, as when the migration is applied.
     fn mark_applied(&self, conn: &impl db::ConnectionMethods) -> Result<()> {
-        conn.insert_only(
-            ButaneMigration::TABLE,
-            ButaneMigration::COLUMNS,
-            &[self.name().as_ref().to_sql_ref()],
-        )
+        let backend = conn.sql_backend().ok_or_else(|| {
+            Error::MigrationError(
+                "cannot mark a migration applied on an unknown backend".to_string(),
+            )
+        })?;
+        let checksum = self.checksum(backend.name())?;
+        super::record_applied(conn, &backend, &self.name(), checksum)
     }
 
     /// Un-apply (downgrade) the migration to a database
@@ -80,6 +94,27 @@ pub trait Migration: PartialEq {
     }
 }
 
+/// Run the sql for `m` on `conn` and record it as applied, without
+/// beginning a transaction of its own.
+pub(super) fn apply_with(
+    m: &(impl Migration + ?Sized),
+    conn: &impl ConnectionMethods,
+    backend: &impl db::Backend,
+) -> Result<()> {
+    let sql = m
+        .up_sql(backend.name())?
+        .ok_or_else(|| Error::UnknownBackend(backend.name().to_string()))?;
+    conn.execute(&sql)?;
+    super::record_applied(conn, backend, &m.name(), Some(sql_checksum(&sql)))
+}
+
+/// Checksum of migration sql, as recorded in the migration history.
+fn sql_checksum(sql: &str) -> String {
+    // Normalize line endings so that a checkout with different
+    // line-ending conventions does not look like a modification.
+    hex::encode(Sha256::digest(sql.replace("\r\n", "\n").as_bytes()))
+}
+
 /// A migration which can be modified
 pub trait MigrationMut: Migration {
     /// Adds an abstract table to the migration. The table state should
@@ -94,6 +129,25 @@ pub trait MigrationMut: Migration {
     /// butane cli command `butane delete table <TABLE>`.
     fn delete_table(&mut self, name: &str) -> Result<()>;
 
+    /// Adds an abstract enum to the migration, replacing any existing
+    /// enum of the same name. Like tables, it represents the expected
+    /// state after the migration has been applied. The default
+    /// implementation does not support enums.
+    fn write_enum(&mut self, e: &AEnum) -> Result<()> {
+        Err(Error::MigrationError(format!(
+            "This migration type does not support enums, such as {}",
+            e.name
+        )))
+    }
+
+    /// Deletes the enum with the given name, such as one no longer
+    /// used by any table. The default implementation, matching the
+    /// default for [write_enum][MigrationMut::write_enum], has no
+    /// enums to delete.
+    fn delete_enum(&mut self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
     /// Set the backend-specific commands to apply/undo this migration.
     fn add_sql(&mut self, backend_name: &str, up_sql: &str, down_sql: &str) -> Result<()>;
 
@@ -102,4 +156,16 @@ pub trait MigrationMut: Migration {
 
     /// Set the name of the migration before this one.
     fn set_migration_from(&mut self, prev: Option<String>) -> Result<()>;
+
+    /// Set the names of further migrations merged by this one. The
+    /// default implementation does not support merge migrations.
+    fn set_merged_from(&mut self, others: Vec<String>) -> Result<()> {
+        if others.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MigrationError(
+                "This migration type does not support merging".to_string(),
+            ))
+        }
+    }
 }
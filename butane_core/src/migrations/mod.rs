@@ -6,9 +6,12 @@ use crate::sqlval::{FromSql, SqlValRef, ToSql};
 use crate::{db, query, DataObject, DataResult, Error, Result, SqlType};
 
 use fallible_iterator::FallibleIterator;
+use std::collections::HashSet;
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub mod adb;
+pub mod lint;
 use adb::{AColumn, ATable, DeferredSqlType, Operation, TypeIdentifier, ADB};
 
 mod migration;
@@ -32,77 +35,116 @@ pub trait Migrations {
     /// no migrations have been created.
     fn latest(&self) -> Option<Self::M>;
 
-    /// Returns migrations since the given migration.
+    /// Returns migrations since the given migration, that is those
+    /// which `since` is not itself based on.
     fn migrations_since(&self, since: &Self::M) -> Result<Vec<Self::M>> {
-        let mut last = self.latest();
-        let mut accum: Vec<Self::M> = Vec::new();
-        while let Some(m) = last {
-            if m != *since {
-                last = match m.migration_from()? {
-                    None => None,
-                    Some(name) => self.get_migration(&name),
-                };
-                accum.push(m);
-                continue;
-            }
-
-            return Ok(accum.into_iter().rev().collect());
-        }
-        Err(Error::MigrationError("Migration not in chain".to_string()))
+        let all = self.all_migrations()?;
+        let pos = all
+            .iter()
+            .position(|m| m == since)
+            .ok_or_else(|| Error::MigrationError("Migration not in chain".to_string()))?;
+        let before: HashSet<String> = ancestry(self, all[pos].name().to_string())?
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect();
+        Ok(all
+            .into_iter()
+            .filter(|m| !before.contains(m.name().as_ref()))
+            .collect())
     }
 
-    /// Returns all migrations
+    /// Returns all migrations. Each migration is ordered after all
+    /// the migrations it is based on.
     fn all_migrations(&self) -> Result<Vec<Self::M>> {
-        let mut last = self.latest();
-        let mut accum: Vec<Self::M> = Vec::new();
-        while let Some(m) = last {
-            last = match m.migration_from()? {
-                None => None,
-                Some(name) => self.get_migration(&name),
-            };
-            accum.push(m);
+        match self.latest() {
+            None => Ok(Vec::new()),
+            Some(m) => ancestry(self, m.name().to_string()),
         }
-        Ok(accum.into_iter().rev().collect())
     }
 
     /// Get migrations which have not yet been applied to the database
     fn unapplied_migrations(&self, conn: &impl ConnectionMethods) -> Result<Vec<Self::M>> {
-        match self.last_applied_migration(conn)? {
-            None => self.all_migrations(),
-            Some(m) => self.migrations_since(&m),
+        let all = self.all_migrations()?;
+        let recorded = applied_names(conn)?;
+        // A migration is applied if it has been recorded as such or
+        // if any migration based on it has been. Walk from the newest
+        // migration so every migration is visited after those based
+        // on it.
+        let mut applied: HashSet<String> = HashSet::new();
+        for m in all.iter().rev() {
+            let name = m.name();
+            if applied.contains(name.as_ref()) || recorded.contains(name.as_ref()) {
+                applied.insert(name.to_string());
+                applied.extend(parents(m)?);
+            }
         }
+        Ok(all
+            .into_iter()
+            .filter(|m| !applied.contains(m.name().as_ref()))
+            .collect())
+    }
+
+    /// Apply all unapplied migrations to the database, returning the
+    /// migrations which were applied.
+    ///
+    /// Unlike applying each migration returned by
+    /// `unapplied_migrations`, this is safe to call from several
+    /// processes at once (for example several replicas of a service
+    /// starting simultaneously). A database-wide lock is held while
+    /// the applied state is checked and the migrations run, so each
+    /// migration is applied exactly once. All migrations are applied
+    /// in a single transaction.
+    fn apply_all(&self, conn: &mut impl db::BackendConnection) -> Result<Vec<Self::M>> {
+        let backend = conn.backend();
+        let tx = conn.migration_transaction()?;
+        // Another process may have applied migrations while we waited
+        // for the lock, so the applied state must be read under it.
+        let to_apply = self.unapplied_migrations(&tx)?;
+        for m in &to_apply {
+            migration::apply_with(m, &tx, &backend)?;
+        }
+        tx.commit()?;
+        Ok(to_apply)
     }
 
     /// Get the last migration that has been applied to the database or None
     /// if no migrations have been applied
     fn last_applied_migration(&self, conn: &impl ConnectionMethods) -> Result<Option<Self::M>> {
-        if !conn.has_table(ButaneMigration::TABLE)? {
-            return Ok(None);
-        }
-        let migrations: Vec<ButaneMigration> = conn
-            .query(
-                ButaneMigration::TABLE,
-                ButaneMigration::COLUMNS,
-                None,
-                None,
-                None,
-                None,
-            )?
-            .mapped(ButaneMigration::from_row)
-            .collect()?;
-
-        let mut m_opt = self.latest();
-        while let Some(m) = m_opt {
-            if migrations.contains(&ButaneMigration {
-                name: m.name().to_string(),
-            }) {
-                return Ok(Some(m));
+        let names = applied_names(conn)?;
+        Ok(self
+            .all_migrations()?
+            .into_iter()
+            .rev()
+            .find(|m| names.contains(m.name().as_ref())))
+    }
+
+    /// Verify that none of the migrations applied to the database
+    /// have been modified since they were applied, by comparing the
+    /// checksum recorded at application time to the current one.
+    /// Migrations applied before checksums were recorded are not
+    /// checked.
+    fn verify_checksums(&self, conn: &impl db::BackendConnection) -> Result<()> {
+        let backend_name = conn.backend_name();
+        let mut modified: Vec<String> = Vec::new();
+        for applied in applied_migrations(conn)? {
+            let recorded = match applied.checksum {
+                Some(checksum) => checksum,
+                None => continue,
+            };
+            if let Some(m) = self.get_migration(&applied.name) {
+                if m.checksum(backend_name)?.as_ref() != Some(&recorded) {
+                    modified.push(applied.name);
+                }
             }
-            m_opt = m
-                .migration_from()?
-                .and_then(|name| self.get_migration(&name))
         }
-        Ok(None)
+        if modified.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MigrationError(format!(
+                "Migrations modified after being applied: {}",
+                modified.join(", ")
+            )))
+        }
     }
 }
 
@@ -145,7 +187,13 @@ where
         name: &str,
         from: Option<&Self::M>,
     ) -> Result<bool> {
-        let to_db = self.current().db()?;
+        let mut to_db = self.current().db()?;
+        // An enum deleted from the code leaves its definition behind
+        // in the current migration, where no column uses it.
+        for unused in to_db.unused_enums() {
+            self.current().delete_enum(&unused)?;
+            to_db.remove_enum(&unused);
+        }
         self.create_migration_to(backend, name, from, to_db)
     }
 
@@ -179,12 +227,137 @@ where
         for table in to_db.tables() {
             m.write_table(table)?;
         }
+        for e in to_db.enums() {
+            m.write_enum(e)?;
+        }
         m.add_sql(backend.name(), &up_sql, &down_sql)?;
         m.set_migration_from(from.map(|m| m.name().to_string()))?;
 
         self.add_migration(m)?;
         Ok(true)
     }
+
+    /// Create a migration named `name` joining the divergent
+    /// migrations `heads`, such as migrations created independently
+    /// on two branches of version control. The resulting database
+    /// state combines the changes each head made since the migrations
+    /// it shares with the others; conflicting changes are an
+    /// error. Each head's changes are made by the migrations leading
+    /// up to it, so the merge migration itself has no work to do; its
+    /// purpose is to give later migrations a single parent.
+    fn create_merge_migration(
+        &mut self,
+        backend: &impl db::Backend,
+        name: &str,
+        heads: &[Self::M],
+    ) -> Result<()> {
+        if heads.len() < 2 {
+            return Err(Error::MigrationError(
+                "A merge requires at least two migrations".to_string(),
+            ));
+        }
+        let mut db = heads[0].db()?;
+        let mut merged: HashSet<String> = ancestry(self, heads[0].name().to_string())?
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect();
+        for head in &heads[1..] {
+            // Each head is merged against the most recent migration
+            // it shares with the heads already merged.
+            let head_ancestry = ancestry(self, head.name().to_string())?;
+            let base = match head_ancestry
+                .iter()
+                .rev()
+                .find(|m| merged.contains(m.name().as_ref()))
+            {
+                Some(m) => m.db()?,
+                None => ADB::new(),
+            };
+            db.merge(&base, &head.db()?)?;
+            merged.extend(head_ancestry.iter().map(|m| m.name().to_string()));
+        }
+        let mut m = self.new_migration(name);
+        for table in db.tables() {
+            m.write_table(table)?;
+        }
+        for e in db.enums() {
+            m.write_enum(e)?;
+        }
+        // No backend has any work to do
+        let mut backends: HashSet<String> = HashSet::new();
+        backends.insert(backend.name().to_string());
+        for head in heads {
+            backends.extend(head.sql_backends()?);
+        }
+        for backend_name in backends {
+            m.add_sql(&backend_name, "", "")?;
+        }
+        m.set_migration_from(Some(heads[0].name().to_string()))?;
+        m.set_merged_from(heads[1..].iter().map(|h| h.name().to_string()).collect())?;
+        self.add_migration(m)
+    }
+}
+
+/// The names of the migrations `m` is based on.
+fn parents(m: &impl Migration) -> Result<Vec<String>> {
+    let mut names: Vec<String> = m
+        .migration_from()?
+        .map(|s| s.to_string())
+        .into_iter()
+        .collect();
+    names.extend(m.merged_from()?);
+    Ok(names)
+}
+
+/// Get the migration named `head` and every migration it is based
+/// on, directly or indirectly. Each migration is ordered after all
+/// the migrations it is based on.
+fn ancestry<Ms: Migrations + ?Sized>(ms: &Ms, head: String) -> Result<Vec<Ms::M>> {
+    let mut ordered: Vec<Ms::M> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    // Depth-first, emitting a migration once its parents have been
+    // emitted. The flag records whether the parents were already
+    // pushed.
+    let mut stack: Vec<(String, bool)> = vec![(head, false)];
+    while let Some((name, expanded)) = stack.pop() {
+        let m = match ms.get_migration(&name) {
+            Some(m) => m,
+            None => continue,
+        };
+        if expanded {
+            ordered.push(m);
+            continue;
+        }
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        stack.push((name, true));
+        for parent in parents(&m)?.into_iter().rev() {
+            if !seen.contains(&parent) {
+                stack.push((parent, false));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// The names of the migrations recorded as applied to the database.
+fn applied_names(conn: &impl ConnectionMethods) -> Result<HashSet<String>> {
+    if !conn.has_table(ButaneMigration::TABLE)? {
+        return Ok(HashSet::new());
+    }
+    // Only the name column is queried, as older databases may not
+    // have the remaining migration history columns.
+    conn.query(
+        ButaneMigration::TABLE,
+        &ButaneMigration::COLUMNS[..1],
+        None,
+        None,
+        None,
+        None,
+    )?
+    .mapped(|row| FromSql::from_sql_ref(row.get(0, SqlType::Text)?))
+    .collect()
 }
 
 fn migrations_table() -> ATable {
@@ -199,9 +372,148 @@ fn migrations_table() -> ATable {
         None,
     );
     table.add_column(col);
+    table.add_column(AColumn::new(
+        "applied_at",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt)),
+        true,  // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,
+    ));
+    table.add_column(AColumn::new(
+        "checksum",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text)),
+        true,  // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,
+    ));
+    table.add_column(AColumn::new(
+        "seq",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt)),
+        true,  // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,
+    ));
     table
 }
 
+/// Ensure the `butane_migrations` table exists and has all the
+/// columns used to record migration history. Databases migrated by
+/// older versions of butane record only the migration name.
+fn ensure_migrations_table(
+    conn: &impl ConnectionMethods,
+    backend: &impl db::Backend,
+) -> Result<()> {
+    let table = migrations_table();
+    if !conn.has_table(&table.name)? {
+        let sql = backend.create_migration_sql(&ADB::new(), vec![Operation::AddTable(table)])?;
+        return conn.execute(&sql);
+    }
+    for col in ButaneMigration::COLUMNS.iter().skip(1) {
+        if !conn.has_column(&table.name, col.name())? {
+            let ty = match col.ty() {
+                SqlType::BigInt => "BIGINT",
+                _ => "TEXT",
+            };
+            conn.execute(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                table.name,
+                col.name(),
+                ty
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Record in the migration history that the migration named `name` has been applied.
+fn record_applied(
+    conn: &impl ConnectionMethods,
+    backend: &impl db::Backend,
+    name: &str,
+    checksum: Option<String>,
+) -> Result<()> {
+    ensure_migrations_table(conn, backend)?;
+    let applied_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| Error::OutOfRange)?
+        .as_secs() as i64;
+    // applied_at has a resolution of one second, so the sequence
+    // number orders migrations applied within the same second.
+    let seq = conn
+        .query(
+            ButaneMigration::TABLE,
+            &ButaneMigration::COLUMNS[3..],
+            None,
+            None,
+            None,
+            None,
+        )?
+        .mapped(|row| FromSql::from_sql_ref(row.get(0, SqlType::BigInt)?))
+        .fold(0i64, |max, seq: Option<i64>| Ok(max.max(seq.unwrap_or(0))))?
+        + 1;
+    ButaneMigration {
+        name: name.to_string(),
+        applied_at: Some(applied_at),
+        checksum,
+        seq: Some(seq),
+    }
+    .insert(conn)
+}
+
+/// Record of a migration which has been applied to a database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedMigration {
+    /// The name of the migration.
+    pub name: String,
+    /// When the migration was applied. None if it was applied before
+    /// butane recorded this.
+    pub applied_at: Option<SystemTime>,
+    /// Checksum of the sql used to apply the migration. None if
+    /// unknown.
+    pub checksum: Option<String>,
+}
+
+/// Get the history of migrations applied to the database, in the
+/// order in which they were applied.
+pub fn applied_migrations(conn: &impl ConnectionMethods) -> Result<Vec<AppliedMigration>> {
+    if !conn.has_table(ButaneMigration::TABLE)? {
+        return Ok(Vec::new());
+    }
+    let columns = if conn.has_column(ButaneMigration::TABLE, "seq")? {
+        ButaneMigration::COLUMNS
+    } else if conn.has_column(ButaneMigration::TABLE, "applied_at")? {
+        &ButaneMigration::COLUMNS[..3]
+    } else {
+        &ButaneMigration::COLUMNS[..1]
+    };
+    let mut history: Vec<(Option<i64>, AppliedMigration)> = conn
+        .query(ButaneMigration::TABLE, columns, None, None, None, None)?
+        .mapped(ButaneMigration::from_row)
+        .map(|m| {
+            Ok((
+                m.seq,
+                AppliedMigration {
+                    name: m.name,
+                    applied_at: m
+                        .applied_at
+                        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs as u64)),
+                    checksum: m.checksum,
+                },
+            ))
+        })
+        .collect()?;
+    // Migrations without a recorded sequence or time predate those
+    // with one
+    history.sort_by_key(|(seq, m)| (*seq, m.applied_at));
+    Ok(history.into_iter().map(|(_, m)| m).collect())
+}
+
 /// Create a `Migrations` from a filesystem location. The `#[model]`
 /// attribute will write migration information to a
 /// `butane/migrations` directory under the project directory.
@@ -212,10 +524,14 @@ pub fn from_root<P: AsRef<Path>>(path: P) -> FsMigrations {
 /// Copies the data in `from` to `to`.
 pub fn copy_migration(from: &impl Migration, to: &mut impl MigrationMut) -> Result<()> {
     to.set_migration_from(from.migration_from()?.map(|s| s.to_string()))?;
+    to.set_merged_from(from.merged_from()?)?;
     let db = from.db()?;
     for table in db.tables() {
         to.write_table(table)?;
     }
+    for e in db.enums() {
+        to.write_enum(e)?;
+    }
     for (k, v) in db.types() {
         to.add_type(k.clone(), v.clone())?;
     }
@@ -232,18 +548,58 @@ pub fn copy_migration(from: &impl Migration, to: &mut impl MigrationMut) -> Resu
 #[derive(PartialEq)]
 struct ButaneMigration {
     name: String,
+    applied_at: Option<i64>,
+    checksum: Option<String>,
+    seq: Option<i64>,
+}
+impl ButaneMigration {
+    fn insert(&self, conn: &impl ConnectionMethods) -> Result<()> {
+        conn.insert_only(
+            Self::TABLE,
+            <Self as DataResult>::COLUMNS,
+            &[
+                self.name.to_sql_ref(),
+                self.applied_at.to_sql_ref(),
+                self.checksum.to_sql_ref(),
+                self.seq.to_sql_ref(),
+            ],
+        )
+    }
 }
 impl DataResult for ButaneMigration {
     type DBO = Self;
-    const COLUMNS: &'static [Column] = &[Column::new("name", SqlType::Text)];
+    const COLUMNS: &'static [Column] = &[
+        Column::new("name", SqlType::Text),
+        Column::new("applied_at", SqlType::BigInt),
+        Column::new("checksum", SqlType::Text),
+        Column::new("seq", SqlType::BigInt),
+    ];
+    /// Rows lacking the trailing history columns are accepted, as
+    /// produced by the migrations table of older databases.
     fn from_row(row: &dyn db::BackendRow) -> Result<Self> {
-        if row.len() != 1usize {
+        if ![1usize, 3, Self::COLUMNS.len()].contains(&row.len()) {
             return Err(Error::BoundsError(
                 "Row has the wrong number of columns for this DataResult".to_string(),
             ));
         }
+        let (applied_at, checksum) = if row.len() == 1 {
+            (None, None)
+        } else {
+            (
+                FromSql::from_sql_ref(row.get(1, SqlType::BigInt)?)?,
+                FromSql::from_sql_ref(row.get(2, SqlType::Text)?)?,
+            )
+        };
+        let seq = if row.len() == Self::COLUMNS.len() {
+            FromSql::from_sql_ref(row.get(3, SqlType::BigInt)?)?
+        } else {
+            None
+        };
         Ok(ButaneMigration {
             name: FromSql::from_sql_ref(row.get(0, SqlType::Text).unwrap())?,
+            applied_at,
+            checksum,
+            seq,
         })
     }
     fn query() -> query::Query<Self> {
@@ -260,8 +616,12 @@ impl DataObject for ButaneMigration {
         &self.name
     }
     fn save(&mut self, conn: &impl ConnectionMethods) -> Result<()> {
-        let mut values: Vec<SqlValRef<'_>> = Vec::with_capacity(2usize);
-        values.push(self.name.to_sql_ref());
+        let values: [SqlValRef<'_>; 4] = [
+            self.name.to_sql_ref(),
+            self.applied_at.to_sql_ref(),
+            self.checksum.to_sql_ref(),
+            self.seq.to_sql_ref(),
+        ];
         conn.insert_or_replace(
             Self::TABLE,
             <Self as DataResult>::COLUMNS,
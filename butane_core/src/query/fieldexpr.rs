@@ -1,7 +1,8 @@
 //! Not expected to be used directly.
 
 use crate::fkey::ForeignKey;
-use crate::query::{BoolExpr, Column, Expr, Join};
+use crate::json::JsonType;
+use crate::query::{ArithOp, BoolExpr, CmpOp, Column, DatePart, Expr, Func};
 use crate::sqlval::{FieldType, SqlVal, ToSql};
 use crate::DataObject;
 use std::borrow::{Borrow, Cow};
@@ -20,6 +21,14 @@ macro_rules! binary_op {
     };
 }
 
+macro_rules! param_op {
+    ($func_name:ident, $cond:ident) => {
+        pub fn $func_name(&self, name: &'static str) -> BoolExpr {
+            BoolExpr::$cond(self.name, Expr::Param(name))
+        }
+    };
+}
+
 /// Marker trait to determine whether values can be compared.
 ///
 /// Unlike `PartialEq`, handles `Option`, which we need for nullable
@@ -60,6 +69,12 @@ where
         self.name
     }
 
+    /// The value of the field, for use in comparisons with other
+    /// fields or in arithmetic.
+    pub fn value(&self) -> ValueExpr<T> {
+        ValueExpr::new(Expr::Column(self.name))
+    }
+
     binary_op!(eq, std::cmp::PartialEq<U>, Eq);
     binary_op!(ne, std::cmp::PartialEq<U>, Ne);
     binary_op!(lt, DataOrd<U>, Lt);
@@ -67,13 +82,187 @@ where
     binary_op!(le, DataOrd<U>, Le);
     binary_op!(ge, DataOrd<U>, Ge);
 
+    param_op!(eq_param, Eq);
+    param_op!(ne_param, Ne);
+    param_op!(lt_param, Lt);
+    param_op!(gt_param, Gt);
+    param_op!(le_param, Le);
+    param_op!(ge_param, Ge);
+    param_op!(like_param, Like);
+    param_op!(ilike_param, ILike);
+
     pub fn like<U>(&self, val: U) -> BoolExpr
     where
         U: ToSql,
     {
         BoolExpr::Like(self.name, Expr::Val(val.to_sql()))
     }
+
+    /// Case-insensitive version of `like`.
+    pub fn ilike<U>(&self, val: U) -> BoolExpr
+    where
+        U: ToSql,
+    {
+        BoolExpr::ILike(self.name, Expr::Val(val.to_sql()))
+    }
+
+    /// True if the field, which must be marked `#[fulltext]`, matches
+    /// the full-text search `query`. The query is given in the web
+    /// search syntax understood by both backends: words, "quoted
+    /// phrases" and `OR`.
+    pub fn matches_text<U>(&self, query: U) -> BoolExpr
+    where
+        U: ToSql,
+    {
+        BoolExpr::MatchesText {
+            tbl: None,
+            col: self.name,
+            query: Expr::Val(query.to_sql()),
+        }
+    }
+
+    /// Version of `matches_text` taking the query from the named parameter `name`.
+    pub fn matches_text_param(&self, name: &'static str) -> BoolExpr {
+        BoolExpr::MatchesText {
+            tbl: None,
+            col: self.name,
+            query: Expr::Param(name),
+        }
+    }
+
+    /// The relevance of the field, which must be marked `#[fulltext]`,
+    /// to the full-text search `query`, for ordering results of
+    /// [matches_text][FieldExpr::matches_text]. Higher values are more
+    /// relevant. Values are only comparable within a query.
+    pub fn text_rank<U>(&self, query: U) -> ValueExpr<f64>
+    where
+        U: ToSql,
+    {
+        ValueExpr::new(Expr::TextRank {
+            tbl: None,
+            col: self.name,
+            query: Box::new(Expr::Val(query.to_sql())),
+        })
+    }
+
+    /// True if the value is equal to any of `vals`.
+    pub fn is_in<U, I>(&self, vals: I) -> BoolExpr
+    where
+        T: std::cmp::PartialEq<U>,
+        U: ToSql,
+        I: IntoIterator<Item = U>,
+    {
+        BoolExpr::In(self.name, vals.into_iter().map(|v| v.to_sql()).collect())
+    }
+
+    /// True if the value is at least `low` and at most `high`.
+    pub fn between<U>(&self, low: &U, high: &U) -> BoolExpr
+    where
+        T: DataOrd<U>,
+        U: ToSql,
+    {
+        BoolExpr::And(Box::new(self.ge(low)), Box::new(self.le(high)))
+    }
+}
+impl<T> From<FieldExpr<T>> for Expr
+where
+    T: Into<SqlVal>,
+{
+    fn from(field: FieldExpr<T>) -> Expr {
+        Expr::Column(field.name)
+    }
+}
+impl<T> FieldExpr<Option<T>>
+where
+    Option<T>: Into<SqlVal>,
+{
+    pub fn is_null(&self) -> BoolExpr {
+        BoolExpr::Eq(self.name, Expr::Val(SqlVal::Null))
+    }
+    pub fn is_not_null(&self) -> BoolExpr {
+        BoolExpr::Ne(self.name, Expr::Val(SqlVal::Null))
+    }
+}
+impl<T> FieldExpr<T>
+where
+    T: JsonType + Into<SqlVal>,
+{
+    /// The value at `path` within the JSON document, for comparison
+    /// with a value. `path` is a sequence of object keys and array
+    /// indices separated by dots, like `"a.b.0"`.
+    pub fn path(&self, path: &str) -> JsonPathExpr {
+        JsonPathExpr::new(self.name, path)
+    }
+}
+impl<T> FieldExpr<Option<T>>
+where
+    T: JsonType,
+    Option<T>: Into<SqlVal>,
+{
+    /// The value at `path` within the JSON document, as for a field
+    /// which is not nullable.
+    pub fn path(&self, path: &str) -> JsonPathExpr {
+        JsonPathExpr::new(self.name, path)
+    }
+}
+
+macro_rules! json_cmp_op {
+    ($func_name:ident, $op:ident) => {
+        pub fn $func_name<U>(&self, val: &U) -> BoolExpr
+        where
+            U: ToSql,
+        {
+            BoolExpr::Cmp(self.expr(), CmpOp::$op, Expr::Val(val.to_sql()))
+        }
+    };
+}
+
+/// A value within a JSON field, created with `path`. Used to
+/// implement the `filter!` macro. Values of any type may be compared
+/// with it, and are only equal to JSON values of the same type.
+pub struct JsonPathExpr {
+    col: &'static str,
+    path: String,
+}
+impl JsonPathExpr {
+    fn new(col: &'static str, path: &str) -> Self {
+        JsonPathExpr {
+            col,
+            path: path.to_string(),
+        }
+    }
+
+    fn expr(&self) -> Expr {
+        Expr::JsonPath {
+            col: self.col,
+            path: self.path.clone(),
+        }
+    }
+
+    json_cmp_op!(eq, Eq);
+    json_cmp_op!(ne, Ne);
+    json_cmp_op!(lt, Lt);
+    json_cmp_op!(gt, Gt);
+    json_cmp_op!(le, Le);
+    json_cmp_op!(ge, Ge);
+}
+impl<F: DataObject> FieldExpr<Option<ForeignKey<F>>> {
+    /// Like [referenced_by][FieldExpr::referenced_by] for a foreign
+    /// key which is not optional.
+    pub fn referenced_by<O: DataObject>(&self, q: BoolExpr) -> BoolExpr {
+        referenced_by::<F, O>(self.name, q)
+    }
+}
+
+fn referenced_by<F: DataObject, O: DataObject>(col2: &'static str, q: BoolExpr) -> BoolExpr {
+    BoolExpr::Exists {
+        col: Column::new(F::TABLE, F::PKCOL),
+        tbl2: Cow::Borrowed(O::TABLE),
+        col2,
+        expr: Box::new(q),
+    }
 }
+
 impl<F: DataObject> FieldExpr<ForeignKey<F>> {
     pub fn subfilter(&self, q: BoolExpr) -> BoolExpr {
         BoolExpr::Subquery {
@@ -92,8 +281,143 @@ impl<F: DataObject> FieldExpr<ForeignKey<F>> {
     pub fn fields(&self) -> F::Fields {
         F::Fields::default()
     }
+    /// True if any object of type `O` whose foreign key field is this
+    /// one refers to the object the expression is evaluated on and
+    /// matches `q`. That is, this follows the foreign key backwards,
+    /// e.g. `Post::fields().blog().referenced_by::<Post>(q)` filters
+    /// blogs by their posts. `O` may be `F`, for a model referring
+    /// to itself.
+    pub fn referenced_by<O: DataObject>(&self, q: BoolExpr) -> BoolExpr {
+        referenced_by::<F, O>(self.name, q)
+    }
+    /// The value of `field` of the object referred to.
+    pub fn related<U>(&self, field: FieldExpr<U>) -> ValueExpr<U>
+    where
+        U: Into<SqlVal>,
+    {
+        ValueExpr::new(Expr::Related {
+            tbl: None,
+            col: self.name,
+            tbl2: F::TABLE,
+            tbl2_pk: F::PKCOL,
+            col2: field.name,
+        })
+    }
+}
+
+macro_rules! cmp_op {
+    ($func_name:ident, $bound:path, $op:ident) => {
+        pub fn $func_name<U>(self, other: ValueExpr<U>) -> BoolExpr
+        where
+            T: $bound,
+        {
+            BoolExpr::Cmp(self.expr, CmpOp::$op, other.expr)
+        }
+    };
 }
 
+macro_rules! arith_op {
+    ($trait:ident, $func_name:ident, $op:ident) => {
+        impl<T> std::ops::$trait for ValueExpr<T>
+        where
+            T: std::ops::$trait<Output = T>,
+        {
+            type Output = ValueExpr<T>;
+            fn $func_name(self, other: ValueExpr<T>) -> ValueExpr<T> {
+                ValueExpr::new(Expr::Arith(
+                    Box::new(self.expr),
+                    ArithOp::$op,
+                    Box::new(other.expr),
+                ))
+            }
+        }
+    };
+}
+
+/// A typed expression, which may involve fields, values, and
+/// arithmetic. Used to implement the `filter!` macro when comparing
+/// more than a single field with a value.
+pub struct ValueExpr<T> {
+    expr: Expr,
+    phantom: PhantomData<T>,
+}
+impl<T> ValueExpr<T> {
+    pub fn new(expr: Expr) -> Self {
+        ValueExpr {
+            expr,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn into_expr(self) -> Expr {
+        self.expr
+    }
+
+    cmp_op!(eq, std::cmp::PartialEq<U>, Eq);
+    cmp_op!(ne, std::cmp::PartialEq<U>, Ne);
+    cmp_op!(lt, DataOrd<U>, Lt);
+    cmp_op!(gt, DataOrd<U>, Gt);
+    cmp_op!(le, DataOrd<U>, Le);
+    cmp_op!(ge, DataOrd<U>, Ge);
+
+    fn func<U>(self, func: Func) -> ValueExpr<U> {
+        ValueExpr::new(Expr::Func(func, vec![self.expr]))
+    }
+
+    /// The value converted to lowercase (LOWER in SQL).
+    pub fn lower(self) -> ValueExpr<T> {
+        self.func(Func::Lower)
+    }
+
+    /// The value converted to uppercase (UPPER in SQL).
+    pub fn upper(self) -> ValueExpr<T> {
+        self.func(Func::Upper)
+    }
+
+    /// The length of the text value, in characters.
+    pub fn length(self) -> ValueExpr<i32> {
+        self.func(Func::Length)
+    }
+
+    /// The absolute value.
+    pub fn abs(self) -> ValueExpr<T> {
+        self.func(Func::Abs)
+    }
+
+    /// A part of the timestamp value, such as the month.
+    pub fn extract(self, part: DatePart) -> ValueExpr<i32> {
+        self.func(Func::Extract(part))
+    }
+
+    /// The timestamp value truncated to the start of `part`, e.g. to
+    /// midnight for `DatePart::Day`.
+    pub fn date_trunc(self, part: DatePart) -> ValueExpr<T> {
+        self.func(Func::DateTrunc(part))
+    }
+}
+impl<T> ValueExpr<Option<T>> {
+    /// The value, or `default` where it is null.
+    pub fn coalesce(self, default: ValueExpr<T>) -> ValueExpr<T> {
+        ValueExpr::new(Expr::Func(Func::Coalesce, vec![self.expr, default.expr]))
+    }
+}
+impl<T> From<ValueExpr<T>> for Expr {
+    fn from(expr: ValueExpr<T>) -> Expr {
+        expr.expr
+    }
+}
+impl<T: ToSql> ValueExpr<T> {
+    /// A literal value.
+    pub fn val(val: T) -> Self {
+        ValueExpr::new(Expr::Val(val.to_sql()))
+    }
+}
+arith_op!(Add, add, Add);
+arith_op!(Sub, sub, Sub);
+arith_op!(Mul, mul, Mul);
+arith_op!(Div, div, Div);
+arith_op!(Rem, rem, Rem);
+
 pub struct ManyFieldExpr<O, T>
 where
     O: DataObject, // owner
@@ -115,18 +439,21 @@ where
             phantomt: PhantomData,
         }
     }
+    /// True if any of the related objects matches `q`. Negate it for
+    /// none of them matching.
     pub fn contains(&self, q: BoolExpr) -> BoolExpr {
-        //let many_tbl = format!("{}_{}_Many", O::TABLE, self.name);
-        BoolExpr::SubqueryJoin {
-            col: O::PKCOL,
-            tbl2: Cow::Borrowed(T::TABLE),
-            col2: Column::new(self.many_table, "owner"),
-            joins: vec![Join::Inner {
-                join_table: self.many_table,
-                col1: Column::new(self.many_table, "has"),
-                col2: Column::new(T::TABLE, T::PKCOL),
-            }],
-            expr: Box::new(q),
+        // Only the many table is correlated with the owner, so that
+        // this works when T is the same type as O.
+        BoolExpr::Exists {
+            col: Column::new(O::TABLE, O::PKCOL),
+            tbl2: Cow::Borrowed(self.many_table),
+            col2: "owner",
+            expr: Box::new(BoolExpr::Subquery {
+                col: "has",
+                tbl2: Cow::Borrowed(T::TABLE),
+                tbl2_col: T::PKCOL,
+                expr: Box::new(q),
+            }),
         }
     }
     pub fn containspk(&self, pk: impl Borrow<<T::PKType as FieldType>::RefType>) -> BoolExpr {
@@ -0,0 +1,154 @@
+use super::{Column, FieldExpr, Join, Query};
+use crate::db::{BackendRow, BackendRows, ConnectionMethods};
+use crate::fkey::ForeignKey;
+use crate::{DataObject, DataResult, Result, SqlType, SqlValRef};
+use fallible_iterator::FallibleIterator;
+use std::marker::PhantomData;
+
+/// A query loading objects together with the objects their foreign
+/// keys refer to, in a single SQL statement. Created with
+/// [Query::join] or [Query::left_join].
+///
+/// `R` is `U` for an inner join, or `Option<U>` for a left join.
+///
+/// # Examples
+/// ```ignore
+/// let posts: Vec<(Post, Blog)> = query!(Post, published == true)
+///     .join(Post::fields().blog())
+///     .load(&conn)?;
+/// ```
+pub struct JoinQuery<T: DataResult, U: DataObject, R: JoinResult<U>> {
+    query: Query<T>,
+    fkey: &'static str,
+    phantom: PhantomData<(U, R)>,
+}
+impl<T: DataResult, U: DataObject, R: JoinResult<U>> JoinQuery<T, U, R> {
+    pub(super) fn new(query: Query<T>, fkey: &impl JoinField<Target = U>) -> Self {
+        JoinQuery {
+            query,
+            fkey: fkey.column(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Executes the query against `conn`.
+    pub fn load(self, conn: &impl ConnectionMethods) -> Result<Vec<(T, R)>> {
+        self.query.check_unbound()?;
+        let table = T::DBO::TABLE;
+        let columns: Vec<Column> = T::COLUMNS
+            .iter()
+            .map(|col| Column::new(table, col.name()))
+            .chain(
+                U::COLUMNS
+                    .iter()
+                    .map(|col| Column::new(U::TABLE, col.name())),
+            )
+            .collect();
+        let col1 = Column::new(table, self.fkey);
+        let col2 = Column::new(U::TABLE, U::PKCOL);
+        let join = if R::LEFT {
+            Join::Left {
+                join_table: U::TABLE,
+                col1,
+                col2,
+            }
+        } else {
+            Join::Inner {
+                join_table: U::TABLE,
+                col1,
+                col2,
+            }
+        };
+        let sort = if self.query.sort.is_empty() {
+            None
+        } else {
+            Some(self.query.sort.as_slice())
+        };
+        conn.query_join(
+            table,
+            &columns,
+            vec![join],
+            self.query.filter,
+            self.query.limit,
+            self.query.offset,
+            sort,
+        )?
+        .mapped(|row| {
+            let left = T::from_row(&OffsetRow::new(row, 0, T::COLUMNS.len()))?;
+            let right = R::from_row(&OffsetRow::new(row, T::COLUMNS.len(), U::COLUMNS.len()))?;
+            Ok((left, right))
+        })
+        .collect()
+    }
+}
+
+/// A foreign key field through which a query may be joined.
+/// Implemented for the [FieldExpr] of a `ForeignKey` or
+/// `Option<ForeignKey>` field.
+pub trait JoinField {
+    /// The type of the object referred to.
+    type Target: DataObject;
+    fn column(&self) -> &'static str;
+}
+impl<U: DataObject> JoinField for FieldExpr<ForeignKey<U>> {
+    type Target = U;
+    fn column(&self) -> &'static str {
+        self.name()
+    }
+}
+impl<U: DataObject> JoinField for FieldExpr<Option<ForeignKey<U>>> {
+    type Target = U;
+    fn column(&self) -> &'static str {
+        self.name()
+    }
+}
+
+/// The type loaded for the joined object in a [JoinQuery]: the
+/// object itself for an inner join, or an `Option` of it for a left
+/// join, which is `None` when there is no matching object.
+pub trait JoinResult<U: DataObject>: Sized {
+    /// Whether this is the result of a left join.
+    const LEFT: bool;
+    fn from_row(row: &dyn BackendRow) -> Result<Self>;
+}
+impl<U: DataObject> JoinResult<U> for U {
+    const LEFT: bool = false;
+    fn from_row(row: &dyn BackendRow) -> Result<Self> {
+        U::from_row(row)
+    }
+}
+impl<U: DataObject> JoinResult<U> for Option<U> {
+    const LEFT: bool = true;
+    fn from_row(row: &dyn BackendRow) -> Result<Self> {
+        let pkidx = U::COLUMNS
+            .iter()
+            .position(|col| col.name() == U::PKCOL)
+            .unwrap_or(0);
+        // The primary key is only null if no row matched
+        if let SqlValRef::Null = row.get(pkidx, U::COLUMNS[pkidx].ty().clone())? {
+            return Ok(None);
+        }
+        U::from_row(row).map(Some)
+    }
+}
+
+/// The columns of one of the tables in a joined row, which start at
+/// column `offset`.
+struct OffsetRow<'r> {
+    row: &'r dyn BackendRow,
+    offset: usize,
+    len: usize,
+}
+impl<'r> OffsetRow<'r> {
+    fn new(row: &'r dyn BackendRow, offset: usize, len: usize) -> Self {
+        OffsetRow { row, offset, len }
+    }
+}
+impl BackendRow for OffsetRow<'_> {
+    fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef<'_>> {
+        self.row.get(self.offset + idx, ty)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
@@ -3,14 +3,25 @@
 //! module directly.
 
 use crate::db::{BackendRows, ConnectionMethods, QueryResult};
-use crate::{DataResult, Result, SqlVal};
-use fallible_iterator::FallibleIterator;
+use crate::{DataResult, Error, Result, SqlVal};
 use std::borrow::Cow;
 use std::marker::PhantomData;
 
 mod fieldexpr;
+mod join;
+mod page;
+mod prepared;
+mod select;
 
-pub use fieldexpr::{DataOrd, FieldExpr, ManyFieldExpr};
+pub use fieldexpr::{DataOrd, FieldExpr, JsonPathExpr, ManyFieldExpr, ValueExpr};
+pub use join::{JoinField, JoinQuery, JoinResult};
+pub use page::{Cursor, Page};
+pub(crate) use prepared::params;
+pub use prepared::{PreparedQuery, QueryParams};
+pub use select::{Projection, Select};
+
+/// Re-exported for use with [Query::iter].
+pub use fallible_iterator::FallibleIterator;
 
 type TblName = Cow<'static, str>;
 
@@ -23,8 +34,117 @@ pub enum Expr {
     Val(SqlVal),
     /// A placeholder for a value.
     Placeholder,
+    /// A named parameter, whose value is supplied when a
+    /// [PreparedQuery] is executed.
+    Param(&'static str),
     /// A boolean condition.
     Condition(Box<BoolExpr>),
+    /// An arithmetic operation on two expressions.
+    Arith(Box<Expr>, ArithOp, Box<Expr>),
+    /// A SQL function applied to arguments.
+    Func(Func, Vec<Expr>),
+    /// The value of `col2` in the row of `tbl2` whose `tbl2_pk` is
+    /// equal to `col` in `tbl`, that is a field of the object a
+    /// foreign key refers to. `tbl` is filled in when the expression
+    /// is used to filter or order a [Query].
+    Related {
+        tbl: Option<TblName>,
+        col: &'static str,
+        tbl2: &'static str,
+        tbl2_pk: &'static str,
+        col2: &'static str,
+    },
+    /// The relevance of the full-text indexed column `col` of `tbl`
+    /// to the full-text search `query`, higher being more relevant.
+    /// `tbl` is filled in as for `Related`.
+    TextRank {
+        tbl: Option<TblName>,
+        col: &'static str,
+        query: Box<Expr>,
+    },
+    /// The value at `path` (keys and array indices separated by dots)
+    /// within the JSON column `col`.
+    JsonPath { col: &'static str, path: String },
+}
+impl Expr {
+    /// Sets the table of any expressions which refer to the table
+    /// they are evaluated on and do not have one.
+    fn set_table(&mut self, table: &TblName) {
+        match self {
+            Expr::Related { tbl, .. } => {
+                tbl.get_or_insert_with(|| table.clone());
+            }
+            Expr::TextRank { tbl, query, .. } => {
+                tbl.get_or_insert_with(|| table.clone());
+                query.set_table(table);
+            }
+            Expr::Arith(a, _, b) => {
+                a.set_table(table);
+                b.set_table(table);
+            }
+            Expr::Func(_, args) => args.iter_mut().for_each(|a| a.set_table(table)),
+            Expr::Condition(c) => c.set_table(table),
+            Expr::Column(_)
+            | Expr::Val(_)
+            | Expr::Placeholder
+            | Expr::Param(_)
+            | Expr::JsonPath { .. } => (),
+        }
+    }
+}
+
+/// A SQL function. Each backend translates these to its own SQL
+/// dialect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Func {
+    /// Converts text to lowercase. Takes one argument.
+    Lower,
+    /// Converts text to uppercase. Takes one argument.
+    Upper,
+    /// The length of text, in characters. Takes one argument.
+    Length,
+    /// Absolute value. Takes one argument.
+    Abs,
+    /// The first of its arguments which is not null.
+    Coalesce,
+    /// A part of a timestamp, as an integer. Takes one argument.
+    Extract(DatePart),
+    /// A timestamp truncated to the start of the given unit. Takes
+    /// one argument.
+    DateTrunc(DatePart),
+}
+
+/// A part of a date and time, for use with [Func::Extract] and
+/// [Func::DateTrunc].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+/// An arithmetic operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// A comparison operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
 }
 
 /// Abstract representation of a boolean expression.
@@ -38,6 +158,11 @@ pub enum BoolExpr {
     Le(&'static str, Expr),
     Ge(&'static str, Expr),
     Like(&'static str, Expr),
+    /// Case-insensitive LIKE.
+    ILike(&'static str, Expr),
+    /// Comparison of two expressions, either of which may refer to
+    /// columns. Used when the left-hand side is more than a column.
+    Cmp(Expr, CmpOp, Expr),
     AllOf(Vec<BoolExpr>),
     And(Box<BoolExpr>, Box<BoolExpr>),
     Or(Box<BoolExpr>, Box<BoolExpr>),
@@ -62,6 +187,61 @@ pub enum BoolExpr {
         joins: Vec<Join>,
         expr: Box<BoolExpr>,
     },
+    /// Expression which is true if there is a row in `tbl2` whose
+    /// `col2` is equal to `col` (a column of the row the expression
+    /// is evaluated on) and for which `expr` is true. `NOT EXISTS`
+    /// when negated. `tbl2` may be the table of `col`.
+    Exists {
+        col: Column,
+        tbl2: TblName,
+        col2: &'static str,
+        expr: Box<BoolExpr>,
+    },
+    /// Expression which is true if the full-text indexed column `col`
+    /// of `tbl` matches the full-text search `query`. `tbl` is filled
+    /// in when the expression is used to filter a [Query].
+    MatchesText {
+        tbl: Option<TblName>,
+        col: &'static str,
+        query: Expr,
+    },
+}
+impl BoolExpr {
+    /// Sets the table of any expressions which refer to the table
+    /// they are evaluated on and do not have one. Subqueries are
+    /// evaluated on their own table.
+    fn set_table(&mut self, table: &TblName) {
+        use BoolExpr::*;
+        match self {
+            True | In(_, _) => (),
+            Eq(_, ex)
+            | Ne(_, ex)
+            | Lt(_, ex)
+            | Gt(_, ex)
+            | Le(_, ex)
+            | Ge(_, ex)
+            | Like(_, ex)
+            | ILike(_, ex) => ex.set_table(table),
+            Cmp(a, _, b) => {
+                a.set_table(table);
+                b.set_table(table);
+            }
+            AllOf(exprs) => exprs.iter_mut().for_each(|e| e.set_table(table)),
+            And(a, b) | Or(a, b) => {
+                a.set_table(table);
+                b.set_table(table);
+            }
+            Not(a) => a.set_table(table),
+            Subquery { tbl2, expr, .. } | SubqueryJoin { tbl2, expr, .. } => expr.set_table(tbl2),
+            Exists {
+                col, tbl2, expr, ..
+            } => expr.set_table(&exists_table(col, tbl2)),
+            MatchesText { tbl, query, .. } => {
+                tbl.get_or_insert_with(|| table.clone());
+                query.set_table(table);
+            }
+        }
+    }
 }
 
 /// Represents the direction of a sort.
@@ -71,11 +251,90 @@ pub enum OrderDirection {
     Descending,
 }
 
+/// Where null values are placed by a sort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
 /// Represents a sorting term (ORDER BY in SQL).
+///
+/// A term sorting by a column, which is all an `Order` could once
+/// hold, is constructed with [Order::new] and its column is available
+/// from [Order::column].
 #[derive(Clone)]
 pub struct Order {
-    pub direction: OrderDirection,
-    pub column: &'static str,
+    direction: OrderDirection,
+    /// The expression to sort by, usually a column.
+    expr: Expr,
+    /// Where null values are placed, if not the database's default.
+    nulls: Option<NullsOrder>,
+}
+impl Order {
+    /// Order by `column` in the given direction.
+    pub fn new(column: &'static str, direction: OrderDirection) -> Self {
+        Order {
+            direction,
+            expr: Expr::Column(column),
+            nulls: None,
+        }
+    }
+
+    /// Ascending order of `expr`.
+    pub fn asc(expr: impl Into<Expr>) -> Self {
+        Order {
+            direction: OrderDirection::Ascending,
+            expr: expr.into(),
+            nulls: None,
+        }
+    }
+
+    /// Descending order of `expr`.
+    pub fn desc(expr: impl Into<Expr>) -> Self {
+        Order {
+            direction: OrderDirection::Descending,
+            expr: expr.into(),
+            nulls: None,
+        }
+    }
+
+    /// Places null values before all others. Returns `self` as this
+    /// method is expected to be chained.
+    pub fn nulls_first(mut self) -> Self {
+        self.nulls = Some(NullsOrder::First);
+        self
+    }
+
+    /// Places null values after all others. Returns `self` as this
+    /// method is expected to be chained.
+    pub fn nulls_last(mut self) -> Self {
+        self.nulls = Some(NullsOrder::Last);
+        self
+    }
+
+    /// The column sorted by, if the expression is simply a column.
+    pub fn column(&self) -> Option<&'static str> {
+        match self.expr {
+            Expr::Column(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The direction of the sort.
+    pub fn direction(&self) -> &OrderDirection {
+        &self.direction
+    }
+
+    /// The expression sorted by.
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Where null values are placed, if not the database's default.
+    pub fn nulls(&self) -> Option<NullsOrder> {
+        self.nulls
+    }
 }
 
 #[derive(Clone)]
@@ -87,6 +346,25 @@ pub enum Join {
         col1: Column,
         col2: Column,
     },
+    /// Left outer join `join_table` where `col1` is equal to
+    /// `col2`
+    Left {
+        join_table: &'static str,
+        col1: Column,
+        col2: Column,
+    },
+}
+
+/// The name the rows of `tbl2` go by within [BoolExpr::Exists] on
+/// `col`. When `tbl2` is the table of `col`, that is when a model
+/// refers to itself, its rows are aliased to tell them apart from
+/// the row the expression is evaluated on.
+pub(crate) fn exists_table(col: &Column, tbl2: &TblName) -> TblName {
+    if col.table() == Some(tbl2.as_ref()) {
+        Cow::Owned(format!("butane_{}_ref", tbl2))
+    } else {
+        tbl2.clone()
+    }
 }
 
 #[derive(Clone)]
@@ -140,7 +418,8 @@ impl<T: DataResult> Query<T> {
     /// Restricts the query to matching only objects for which `expr`
     /// is true. Returns `self` as this method is expected to be
     /// chained.
-    pub fn filter(mut self, expr: BoolExpr) -> Query<T> {
+    pub fn filter(mut self, mut expr: BoolExpr) -> Query<T> {
+        expr.set_table(&self.table);
         self.filter = Some(expr);
         self
     }
@@ -163,8 +442,28 @@ impl<T: DataResult> Query<T> {
     /// this method may be made, with earlier calls taking precedence.
     /// It is recommended to use the `colname!`
     /// macro to construct the column name in a typesafe manner.
-    pub fn order(mut self, column: &'static str, direction: OrderDirection) -> Query<T> {
-        self.sort.push(Order { direction, column });
+    pub fn order(self, column: &'static str, direction: OrderDirection) -> Query<T> {
+        self.order_with(Order::new(column, direction))
+    }
+
+    /// Order the query results by the value of an expression, such
+    /// as `Post::fields().title().value().length()`. Otherwise like
+    /// [order][Query::order].
+    pub fn order_by(self, expr: impl Into<Expr>, direction: OrderDirection) -> Query<T> {
+        self.order_with(Order {
+            direction,
+            expr: expr.into(),
+            nulls: None,
+        })
+    }
+
+    /// Order the query results as given by `order`, which may also
+    /// sort by a field of a related object and specify where null
+    /// values are placed. The `order!` macro is usually more
+    /// convenient. Otherwise like [order][Query::order].
+    pub fn order_with(mut self, mut order: Order) -> Query<T> {
+        order.expr.set_table(&self.table);
+        self.sort.push(order);
         self
     }
 
@@ -178,8 +477,41 @@ impl<T: DataResult> Query<T> {
         self.order(column, OrderDirection::Descending)
     }
 
+    /// Loads only the given fields rather than whole objects. The
+    /// projection is a [FieldExpr], such as `Post::fields().title()`,
+    /// or a tuple of them, and the results are values or tuples of
+    /// values of the fields' types.
+    pub fn select<S: Projection>(self, projection: S) -> Select<T, S> {
+        Select::new(self, projection)
+    }
+
+    /// Loads each result together with the object referred to by its
+    /// foreign key field `fkey`, such as `Post::fields().blog()`,
+    /// using an inner join. Results whose foreign key does not refer
+    /// to an object are omitted. Joining a model to itself is not
+    /// supported.
+    pub fn join<F: JoinField>(self, fkey: F) -> JoinQuery<T, F::Target, F::Target> {
+        JoinQuery::new(self, &fkey)
+    }
+
+    /// Like [join][Query::join], but uses a left join, so that
+    /// results whose foreign key does not refer to an object are
+    /// loaded with `None`.
+    pub fn left_join<F: JoinField>(self, fkey: F) -> JoinQuery<T, F::Target, Option<F::Target>> {
+        JoinQuery::new(self, &fkey)
+    }
+
+    /// Prepares the query for repeated execution with different
+    /// values for its named parameters (written `param!(name)` in
+    /// `filter!`). `names` gives the order in which parameter values
+    /// are supplied to the [PreparedQuery].
+    pub fn prepare<P: QueryParams>(self, names: &[&'static str]) -> Result<PreparedQuery<T, P>> {
+        PreparedQuery::new(self, names)
+    }
+
     /// Executes the query against `conn` and returns the first result (if any).
     pub fn load_first(self, conn: &impl ConnectionMethods) -> Result<Option<T>> {
+        self.check_unbound()?;
         conn.query(&self.table, T::COLUMNS, self.filter, Some(1), None, None)?
             .mapped(T::from_row)
             .nth(0)
@@ -187,6 +519,7 @@ impl<T: DataResult> Query<T> {
 
     /// Executes the query against `conn`.
     pub fn load(self, conn: &impl ConnectionMethods) -> Result<QueryResult<T>> {
+        self.check_unbound()?;
         let sort = if self.sort.is_empty() {
             None
         } else {
@@ -197,8 +530,55 @@ impl<T: DataResult> Query<T> {
             .collect()
     }
 
+    /// Executes the query against `conn`, returning an iterator which
+    /// loads each result as it is reached rather than collecting them
+    /// all up front. Suitable for queries with very many results.
+    ///
+    /// The connection is mutably borrowed until the iterator is
+    /// dropped, so it cannot be used for anything else in the meantime.
+    pub fn iter<'c>(
+        self,
+        conn: &'c mut impl ConnectionMethods,
+    ) -> Result<impl FallibleIterator<Item = T, Error = Error> + 'c>
+    where
+        T: 'c,
+    {
+        self.check_unbound()?;
+        let sort = if self.sort.is_empty() {
+            None
+        } else {
+            Some(self.sort.as_slice())
+        };
+        Ok(conn
+            .query_iter(
+                &self.table,
+                T::COLUMNS,
+                self.filter,
+                self.limit,
+                self.offset,
+                sort,
+            )?
+            .mapped(T::from_row))
+    }
+
     /// Executes the query against `conn` and deletes all matching objects.
     pub fn delete(self, conn: &impl ConnectionMethods) -> Result<usize> {
+        self.check_unbound()?;
         conn.delete_where(&self.table, self.filter.unwrap_or(BoolExpr::True))
     }
+
+    /// Named parameters can only be given values by a `PreparedQuery`.
+    fn check_unbound(&self) -> Result<()> {
+        match self
+            .filter
+            .as_ref()
+            .and_then(|f| prepared::params(f).first().copied())
+        {
+            Some(name) => Err(Error::InvalidQueryParams(format!(
+                "parameter {} is not bound, use Query::prepare",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
 }
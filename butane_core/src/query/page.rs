@@ -0,0 +1,220 @@
+use super::{BoolExpr, CmpOp, Expr, Order, OrderDirection, Query};
+use crate::db::{BackendRows, Column, ConnectionMethods};
+use crate::{DataObject, DataResult, Error, Result, SqlVal};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A position within the results of a query ordered by some
+/// columns, from which the next or previous page of results may be
+/// loaded with [Query::page]. Obtained from a [Page].
+///
+/// Cursors are opaque. For use in URLs and the like, they may be
+/// converted to a string with `to_string` and back with `parse`,
+/// or (de)serialized with serde.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Names of the ordering columns, and whether each is ascending,
+    /// so that the cursor is only used with the query it came from.
+    columns: Vec<(String, bool)>,
+    /// Values of the ordering columns in the row the cursor is
+    /// positioned at.
+    values: Vec<SqlVal>,
+    /// Whether the cursor loads the rows before (rather than after)
+    /// its position.
+    backward: bool,
+}
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_vec(self).map_err(|_| fmt::Error)?;
+        f.write_str(&hex::encode(json))
+    }
+}
+impl FromStr for Cursor {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let json = hex::decode(s).map_err(|e| Error::InvalidCursor(e.to_string()))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// One page of the results of a query, loaded with [Query::page].
+#[derive(Debug)]
+pub struct Page<T> {
+    /// The results on this page, in query order.
+    pub items: Vec<T>,
+    /// Cursor for the page following this one, if there are more results.
+    pub next: Option<Cursor>,
+    /// Cursor for the page preceding this one, if this is not the first page.
+    pub previous: Option<Cursor>,
+}
+
+impl<T: DataResult> Query<T> {
+    /// Loads a page of at most `size` results, using keyset
+    /// pagination. Unlike [offset][Query::offset], the cost of
+    /// loading a page does not depend on how deep it is, and rows
+    /// inserted or deleted concurrently do not cause results to be
+    /// skipped or repeated.
+    ///
+    /// The results are ordered as specified with [order][Query::order],
+    /// followed by the primary key if it is not already included so
+    /// that the order is total. All ordering columns must be among the
+    /// columns of `T` and must not be null. Any limit or offset set on
+    /// the query is ignored.
+    ///
+    /// `cursor` is `None` for the first page, or a cursor from the
+    /// `next` or `previous` field of a previously loaded [Page], and
+    /// must come from a query with the same ordering. `size` must be
+    /// at least 1.
+    pub fn page(
+        self,
+        conn: &impl ConnectionMethods,
+        cursor: Option<&Cursor>,
+        size: i32,
+    ) -> Result<Page<T>> {
+        self.check_unbound()?;
+        if size < 1 {
+            return Err(Error::InvalidQueryParams(format!(
+                "page size must be at least 1, not {}",
+                size
+            )));
+        }
+        let mut sort = self.sort;
+        if !sort.iter().any(|o| o.column() == Some(T::DBO::PKCOL)) {
+            sort.push(Order::new(T::DBO::PKCOL, OrderDirection::Ascending));
+        }
+        let keys: Vec<(usize, &Column)> = sort
+            .iter()
+            .map(|o| key_column::<T>(o))
+            .collect::<Result<_>>()?;
+        let columns: Vec<(String, bool)> = sort
+            .iter()
+            .zip(&keys)
+            .map(|(o, (_, col))| {
+                let ascending = matches!(o.direction, OrderDirection::Ascending);
+                (col.name().to_string(), ascending)
+            })
+            .collect();
+
+        let backward = cursor.map_or(false, |c| c.backward);
+        let mut filter = self.filter;
+        if let Some(cursor) = cursor {
+            if cursor.columns != columns {
+                return Err(Error::InvalidCursor(
+                    "cursor does not match the ordering of the query".into(),
+                ));
+            }
+            if cursor.values.len() != sort.len() {
+                return Err(Error::InvalidCursor(format!(
+                    "cursor has {} values for {} ordering columns",
+                    cursor.values.len(),
+                    sort.len()
+                )));
+            }
+            let after = keyset_expr(&sort, &cursor.values, backward);
+            filter = Some(match filter {
+                Some(filter) => BoolExpr::And(Box::new(filter), Box::new(after)),
+                None => after,
+            });
+        }
+        if backward {
+            // Walk backwards from the cursor, then restore the order
+            // of the page below.
+            for o in sort.iter_mut() {
+                o.direction = match o.direction {
+                    OrderDirection::Ascending => OrderDirection::Descending,
+                    OrderDirection::Descending => OrderDirection::Ascending,
+                };
+            }
+        }
+
+        // Load one extra row to find whether there are any more.
+        let mut rows = conn.query(
+            &self.table,
+            T::COLUMNS,
+            filter,
+            Some(size.saturating_add(1)),
+            None,
+            Some(&sort),
+        )?;
+        let mut items: Vec<(T, Vec<SqlVal>)> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let values = keys
+                .iter()
+                .map(|(idx, col)| Ok(row.get(*idx, col.ty().clone())?.into()))
+                .collect::<Result<Vec<SqlVal>>>()?;
+            items.push((T::from_row(row)?, values));
+        }
+        let more = items.len() > size as usize;
+        items.truncate(size as usize);
+        if backward {
+            items.reverse();
+        }
+
+        let first = items.first().map(|(_, values)| values.clone());
+        let last = items.last().map(|(_, values)| values.clone());
+        let (has_previous, has_next) = if backward {
+            (more, true)
+        } else {
+            // Any cursor was taken from a page before this one.
+            (cursor.is_some(), more)
+        };
+        Ok(Page {
+            items: items.into_iter().map(|(item, _)| item).collect(),
+            next: last.filter(|_| has_next).map(|values| Cursor {
+                columns: columns.clone(),
+                values,
+                backward: false,
+            }),
+            previous: first.filter(|_| has_previous).map(|values| Cursor {
+                columns,
+                values,
+                backward: true,
+            }),
+        })
+    }
+}
+
+/// The index and definition of the column of `T` sorted by `order`.
+fn key_column<T: DataResult>(order: &Order) -> Result<(usize, &'static Column)> {
+    let name = order.column().ok_or_else(|| {
+        Error::InvalidCursor("ordering by expressions other than columns is not supported".into())
+    })?;
+    T::COLUMNS
+        .iter()
+        .enumerate()
+        .find(|(_, col)| col.name() == name)
+        .ok_or_else(|| {
+            Error::InvalidCursor(format!(
+                "ordering column {} is not loaded by the query",
+                name
+            ))
+        })
+}
+
+/// Expression matching rows after `values` in the order given by
+/// `sort`, or before them if `backward`. Equivalent to the row value
+/// comparison `(a, b) > (?, ?)`, but allows mixed sort directions.
+fn keyset_expr(sort: &[Order], values: &[SqlVal], backward: bool) -> BoolExpr {
+    let mut alternatives: Vec<BoolExpr> = Vec::new();
+    for (i, order) in sort.iter().enumerate() {
+        let mut terms: Vec<BoolExpr> = sort[..i]
+            .iter()
+            .zip(values)
+            .map(|(o, v)| BoolExpr::Cmp(o.expr.clone(), CmpOp::Eq, Expr::Val(v.clone())))
+            .collect();
+        let val = Expr::Val(values[i].clone());
+        let ascending = matches!(order.direction, OrderDirection::Ascending);
+        let op = if ascending != backward {
+            CmpOp::Gt
+        } else {
+            CmpOp::Lt
+        };
+        terms.push(BoolExpr::Cmp(order.expr.clone(), op, val));
+        alternatives.push(BoolExpr::AllOf(terms));
+    }
+    alternatives
+        .into_iter()
+        .reduce(|a, b| BoolExpr::Or(Box::new(a), Box::new(b)))
+        .unwrap_or(BoolExpr::True)
+}
@@ -0,0 +1,194 @@
+use super::{BoolExpr, Expr, Query};
+use crate::db::{BackendRows, ConnectionMethods, QueryResult};
+use crate::sqlval::ToSql;
+use crate::{DataResult, Error, Result, SqlVal};
+use fallible_iterator::FallibleIterator;
+use std::marker::PhantomData;
+
+/// A query with named parameters which may be executed repeatedly
+/// with different parameter values. Created with
+/// [Query::prepare]. The statement is prepared by each connection
+/// the first time it is executed there and reused thereafter.
+///
+/// `P` is the type of the parameter values, a tuple with one element
+/// for each parameter name given to `prepare`.
+///
+/// # Examples
+/// ```ignore
+/// let q: PreparedQuery<Post, (bool, i32)> =
+///     query!(Post, published == param!(published) && likes > param!(likes))
+///         .prepare(&["published", "likes"])?;
+/// let popular = q.load(&conn, (true, 100))?;
+/// let obscure = q.load(&conn, (true, 0))?;
+/// ```
+pub struct PreparedQuery<T: DataResult, P: QueryParams> {
+    query: Query<T>,
+    names: Vec<&'static str>,
+    phantom: PhantomData<P>,
+}
+impl<T: DataResult, P: QueryParams> PreparedQuery<T, P> {
+    pub(super) fn new(query: Query<T>, names: &[&'static str]) -> Result<Self> {
+        if names.len() != P::LEN {
+            return Err(Error::InvalidQueryParams(format!(
+                "{} names given for {} parameter values",
+                names.len(),
+                P::LEN
+            )));
+        }
+        let used = query.filter.as_ref().map(params).unwrap_or_default();
+        if let Some(name) = used.iter().find(|p| !names.contains(p)) {
+            return Err(Error::InvalidQueryParams(format!(
+                "no value given for parameter {}",
+                name
+            )));
+        }
+        for (i, name) in names.iter().enumerate() {
+            if !used.contains(name) {
+                return Err(Error::InvalidQueryParams(format!(
+                    "parameter {} is not used by the query",
+                    name
+                )));
+            }
+            if names[..i].contains(name) {
+                return Err(Error::InvalidQueryParams(format!(
+                    "parameter {} is named more than once",
+                    name
+                )));
+            }
+        }
+        Ok(PreparedQuery {
+            query,
+            names: names.to_vec(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Executes the query against `conn` with the given parameter values.
+    pub fn load(&self, conn: &impl ConnectionMethods, params: P) -> Result<QueryResult<T>> {
+        self.execute(conn, params, self.query.limit)
+    }
+
+    /// Executes the query against `conn` with the given parameter
+    /// values and returns the first result (if any).
+    pub fn load_first(&self, conn: &impl ConnectionMethods, params: P) -> Result<Option<T>> {
+        Ok(self.execute(conn, params, Some(1))?.into_iter().next())
+    }
+
+    fn execute(
+        &self,
+        conn: &impl ConnectionMethods,
+        params: P,
+        limit: Option<i32>,
+    ) -> Result<QueryResult<T>> {
+        let bound: Vec<(&'static str, SqlVal)> = self
+            .names
+            .iter()
+            .copied()
+            .zip(params.into_sql_vals())
+            .collect();
+        let sort = if self.query.sort.is_empty() {
+            None
+        } else {
+            Some(self.query.sort.as_slice())
+        };
+        conn.query_prepared(
+            &self.query.table,
+            T::COLUMNS,
+            self.query.filter.clone(),
+            limit,
+            self.query.offset,
+            sort,
+            &bound,
+        )?
+        .mapped(T::from_row)
+        .collect()
+    }
+}
+
+/// Values for the parameters of a [PreparedQuery]. Implemented for
+/// tuples of values implementing [ToSql].
+pub trait QueryParams {
+    /// The number of parameter values.
+    const LEN: usize;
+    fn into_sql_vals(self) -> Vec<SqlVal>;
+}
+
+impl QueryParams for () {
+    const LEN: usize = 0;
+    fn into_sql_vals(self) -> Vec<SqlVal> {
+        Vec::new()
+    }
+}
+
+macro_rules! impl_query_params {
+    ($len:expr, $($ty:ident $idx:tt),+) => {
+        impl<$($ty: ToSql),+> QueryParams for ($($ty,)+) {
+            const LEN: usize = $len;
+            fn into_sql_vals(self) -> Vec<SqlVal> {
+                vec![$(self.$idx.to_sql()),+]
+            }
+        }
+    };
+}
+impl_query_params!(1, A 0);
+impl_query_params!(2, A 0, B 1);
+impl_query_params!(3, A 0, B 1, C 2);
+impl_query_params!(4, A 0, B 1, C 2, D 3);
+impl_query_params!(5, A 0, B 1, C 2, D 3, E 4);
+impl_query_params!(6, A 0, B 1, C 2, D 3, E 4, F 5);
+impl_query_params!(7, A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_query_params!(8, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// The names of the parameters used in `expr`.
+pub(crate) fn params(expr: &BoolExpr) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    add_params(expr, &mut names);
+    names
+}
+
+fn add_params(expr: &BoolExpr, names: &mut Vec<&'static str>) {
+    use BoolExpr::*;
+    match expr {
+        True | In(_, _) => (),
+        Eq(_, ex)
+        | Ne(_, ex)
+        | Lt(_, ex)
+        | Gt(_, ex)
+        | Le(_, ex)
+        | Ge(_, ex)
+        | Like(_, ex)
+        | ILike(_, ex) => add_expr_params(ex, names),
+        Cmp(a, _, b) => {
+            add_expr_params(a, names);
+            add_expr_params(b, names);
+        }
+        AllOf(exprs) => exprs.iter().for_each(|e| add_params(e, names)),
+        And(a, b) | Or(a, b) => {
+            add_params(a, names);
+            add_params(b, names);
+        }
+        Not(a) => add_params(a, names),
+        Subquery { expr, .. } | SubqueryJoin { expr, .. } | Exists { expr, .. } => {
+            add_params(expr, names)
+        }
+        MatchesText { query, .. } => add_expr_params(query, names),
+    }
+}
+
+fn add_expr_params(expr: &Expr, names: &mut Vec<&'static str>) {
+    match expr {
+        Expr::Param(name) => names.push(name),
+        Expr::Condition(c) => add_params(c, names),
+        Expr::Arith(a, _, b) => {
+            add_expr_params(a, names);
+            add_expr_params(b, names);
+        }
+        Expr::Func(_, args) => args.iter().for_each(|e| add_expr_params(e, names)),
+        Expr::TextRank { query, .. } => add_expr_params(query, names),
+        Expr::Column(_)
+        | Expr::Val(_)
+        | Expr::Placeholder
+        | Expr::Related { .. }
+        | Expr::JsonPath { .. } => (),
+    }
+}
@@ -0,0 +1,119 @@
+use super::{FieldExpr, Query};
+use crate::db::{BackendRow, BackendRows, Column, ConnectionMethods};
+use crate::sqlval::FieldType;
+use crate::{DataResult, Result};
+use fallible_iterator::FallibleIterator;
+
+/// A query which loads only some columns. Created with
+/// [Query::select].
+///
+/// # Examples
+/// ```ignore
+/// let titles: Vec<String> = query!(Post, published == true)
+///     .select(Post::fields().title())
+///     .load(&conn)?;
+/// let authors: Vec<(i64, String)> = Blog::query()
+///     .select((Blog::fields().id(), Blog::fields().name()))
+///     .distinct()
+///     .load(&conn)?;
+/// ```
+pub struct Select<T: DataResult, S: Projection> {
+    query: Query<T>,
+    projection: S,
+    distinct: bool,
+}
+impl<T: DataResult, S: Projection> Select<T, S> {
+    pub(super) fn new(query: Query<T>, projection: S) -> Self {
+        Select {
+            query,
+            projection,
+            distinct: false,
+        }
+    }
+
+    /// Removes duplicate results (SELECT DISTINCT in SQL). Returns
+    /// `self` as this method is expected to be chained. Postgres
+    /// requires any columns the query is ordered by to be selected.
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    /// Executes the query against `conn`.
+    pub fn load(self, conn: &impl ConnectionMethods) -> Result<Vec<S::Output>> {
+        self.query.check_unbound()?;
+        let columns = self.projection.columns();
+        let sort = if self.query.sort.is_empty() {
+            None
+        } else {
+            Some(self.query.sort.as_slice())
+        };
+        let rows = if self.distinct {
+            conn.query_distinct(
+                &self.query.table,
+                &columns,
+                self.query.filter,
+                self.query.limit,
+                self.query.offset,
+                sort,
+            )?
+        } else {
+            conn.query(
+                &self.query.table,
+                &columns,
+                self.query.filter,
+                self.query.limit,
+                self.query.offset,
+                sort,
+            )?
+        };
+        rows.mapped(S::from_row).collect()
+    }
+}
+
+/// The columns loaded by a [Select]: a [FieldExpr], whose results
+/// are values of the field type, or a tuple of them, whose results
+/// are tuples of values.
+pub trait Projection {
+    type Output;
+    fn columns(&self) -> Vec<Column>;
+    fn from_row(row: &dyn BackendRow) -> Result<Self::Output>;
+}
+
+impl<F> Projection for FieldExpr<F>
+where
+    F: FieldType + Into<crate::SqlVal>,
+{
+    type Output = F;
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::new(self.name(), F::SQLTYPE)]
+    }
+    fn from_row(row: &dyn BackendRow) -> Result<F> {
+        F::from_sql_ref(row.get(0, F::SQLTYPE)?)
+    }
+}
+
+macro_rules! impl_projection {
+    ($($ty:ident $idx:tt),+) => {
+        impl<$($ty),+> Projection for ($(FieldExpr<$ty>,)+)
+        where
+            $($ty: FieldType + Into<crate::SqlVal>),+
+        {
+            type Output = ($($ty,)+);
+            fn columns(&self) -> Vec<Column> {
+                vec![$(Column::new(self.$idx.name(), $ty::SQLTYPE)),+]
+            }
+            fn from_row(row: &dyn BackendRow) -> Result<Self::Output> {
+                Ok(($($ty::from_sql_ref(row.get($idx, $ty::SQLTYPE)?)?,)+))
+            }
+        }
+    };
+}
+impl_projection!(A 0);
+impl_projection!(A 0, B 1);
+impl_projection!(A 0, B 1, C 2);
+impl_projection!(A 0, B 1, C 2, D 3);
+impl_projection!(A 0, B 1, C 2, D 3, E 4);
+impl_projection!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_projection!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_projection!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
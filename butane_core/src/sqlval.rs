@@ -1,52 +1,140 @@
-use crate::custom::{SqlValCustom, SqlValRefCustom};
-use crate::{DataObject, Error::CannotConvertSqlVal, Result, SqlType};
+use crate::custom::{SqlTypeCustom, SqlValCustom, SqlValRefCustom};
+use crate::{ArrayElement, DataObject, Error::CannotConvertSqlVal, Result, SqlType};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt;
+use std::net::IpAddr;
 
-#[cfg(feature = "pg")]
-use crate::custom::SqlTypeCustom;
-
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
+#[cfg(feature = "datetime")]
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
 #[derive(Clone, Debug)]
 pub enum SqlValRef<'a> {
     Null,
     Bool(bool),
+    SmallInt(i16),
     Int(i32),
     BigInt(i64),
+    UBigInt(u64),
+    HugeInt(i128),
+    UHugeInt(u128),
     Real(f64),
     Text(&'a str),
     Blob(&'a [u8]),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime), // NaiveDateTime is Copy
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<Utc>),
+    #[cfg(feature = "datetime")]
+    Interval(Duration),
+    /// The `time` crate's equivalent of [Timestamp][SqlValRef::Timestamp]
+    #[cfg(feature = "time")]
+    TimeTimestamp(time::PrimitiveDateTime),
+    #[cfg(feature = "time")]
+    TimeDate(time::Date),
+    #[cfg(feature = "time")]
+    TimeTime(time::Time),
+    #[cfg(feature = "time")]
+    TimeTimestampTz(time::OffsetDateTime),
+    #[cfg(feature = "time")]
+    TimeInterval(time::Duration),
+    #[cfg(feature = "decimal")]
+    Numeric(Decimal),
+    #[cfg(feature = "uuid")]
+    Uuid(Uuid),
+    Inet(IpAddr),
+    /// Elements of the given type. Owned when converted from a Rust value.
+    Array(ArrayElement, Cow<'a, [SqlVal]>),
+    /// Serialized JSON. Owned when serialized from a Rust value.
+    Json(Cow<'a, str>),
+    /// The name of a value of a native enum type. Read back as
+    /// [Text][SqlValRef::Text], and treated as text by backends without
+    /// native enums.
+    Enum(&'a str),
     Custom(SqlValRefCustom<'a>),
 }
 impl SqlValRef<'_> {
+    /// Converts to a [SqlVal]. Unlike the `From` conversion, fails
+    /// rather than panicking if a custom value cannot be serialized.
+    pub fn try_into_owned(self) -> Result<SqlVal> {
+        match self {
+            SqlValRef::Custom(v) => Ok(SqlVal::Custom(Box::new(SqlValCustom::try_from(v)?))),
+            v => Ok(v.into()),
+        }
+    }
+
     // if this is Null
     pub fn sqltype(&self) -> Option<SqlType> {
         match self {
             SqlValRef::Null => None,
             SqlValRef::Bool(_) => Some(SqlType::Bool),
+            SqlValRef::SmallInt(_) => Some(SqlType::SmallInt),
             SqlValRef::Int(_) => Some(SqlType::Int),
             SqlValRef::BigInt(_) => Some(SqlType::BigInt),
+            SqlValRef::UBigInt(_) => Some(SqlType::UBigInt),
+            SqlValRef::HugeInt(_) => Some(SqlType::HugeInt),
+            SqlValRef::UHugeInt(_) => Some(SqlType::UHugeInt),
             SqlValRef::Real(_) => Some(SqlType::Real),
             SqlValRef::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlValRef::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "datetime")]
+            SqlValRef::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Interval(_) => Some(SqlType::Interval),
+            #[cfg(feature = "time")]
+            SqlValRef::TimeTimestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "time")]
+            SqlValRef::TimeDate(_) => Some(SqlType::Date),
+            #[cfg(feature = "time")]
+            SqlValRef::TimeTime(_) => Some(SqlType::Time),
+            #[cfg(feature = "time")]
+            SqlValRef::TimeTimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "time")]
+            SqlValRef::TimeInterval(_) => Some(SqlType::Interval),
+            #[cfg(feature = "decimal")]
+            SqlValRef::Numeric(_) => Some(<Decimal as FieldType>::SQLTYPE),
+            #[cfg(feature = "uuid")]
+            SqlValRef::Uuid(_) => Some(SqlType::Uuid),
+            SqlValRef::Inet(_) => Some(SqlType::Inet),
+            SqlValRef::Array(elem, _) => Some(SqlType::Array(*elem)),
             SqlValRef::Blob(_) => Some(SqlType::Blob),
-            #[cfg(feature = "pg")]
+            SqlValRef::Json(_) => Some(SqlType::Json),
+            SqlValRef::Enum(_) => Some(SqlType::Text),
             SqlValRef::Custom(c) => match c {
-                SqlValRefCustom::PgToSql { ty, .. } => {
+                #[cfg(feature = "pg")]
+                SqlValRefCustom::PgToSql { ty, .. } | SqlValRefCustom::PgBytes { ty, .. } => {
                     Some(SqlType::Custom(SqlTypeCustom::Pg(ty.clone())))
                 }
-                SqlValRefCustom::PgBytes { ty, .. } => {
-                    Some(SqlType::Custom(SqlTypeCustom::Pg(ty.clone())))
+                SqlValRefCustom::Sqlite(v) => {
+                    Some(SqlType::Custom(SqlTypeCustom::Sqlite(v.affinity())))
                 }
+                #[cfg(feature = "pg")]
+                SqlValRefCustom::Portable { ty, sqlite, .. }
+                | SqlValRefCustom::PortableBytes { ty, sqlite, .. } => {
+                    Some(SqlType::Custom(SqlTypeCustom::Portable {
+                        pg: ty.clone(),
+                        sqlite: sqlite.affinity(),
+                    }))
+                }
+                #[cfg(not(feature = "pg"))]
+                SqlValRefCustom::Phantom(_) => None,
             },
-            #[cfg(not(feature = "pg"))]
-            SqlValRef::Custom(_) => None,
         }
     }
 }
@@ -61,13 +149,47 @@ impl SqlValRef<'_> {
 pub enum SqlVal {
     Null,
     Bool(bool),
+    SmallInt(i16),
     Int(i32),
     BigInt(i64),
+    UBigInt(u64),
+    HugeInt(i128),
+    UHugeInt(u128),
     Real(f64),
     Text(String),
     Blob(Vec<u8>),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime),
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<Utc>),
+    #[cfg(feature = "datetime")]
+    Interval(#[serde(with = "interval_micros")] Duration),
+    /// The `time` crate's equivalent of [Timestamp][SqlVal::Timestamp]
+    #[cfg(feature = "time")]
+    TimeTimestamp(time::PrimitiveDateTime),
+    #[cfg(feature = "time")]
+    TimeDate(time::Date),
+    #[cfg(feature = "time")]
+    TimeTime(time::Time),
+    #[cfg(feature = "time")]
+    TimeTimestampTz(time::OffsetDateTime),
+    #[cfg(feature = "time")]
+    TimeInterval(time::Duration),
+    #[cfg(feature = "decimal")]
+    Numeric(Decimal),
+    #[cfg(feature = "uuid")]
+    Uuid(Uuid),
+    Inet(IpAddr),
+    /// Elements of the given type.
+    Array(ArrayElement, Vec<SqlVal>),
+    /// Serialized JSON.
+    Json(String),
+    /// The name of a value of a native enum type, as for [SqlValRef::Enum].
+    Enum(String),
     Custom(Box<SqlValCustom>),
 }
 impl SqlVal {
@@ -83,12 +205,14 @@ impl SqlVal {
     }
     pub fn integer(&self) -> Result<i32> {
         match self {
+            SqlVal::SmallInt(val) => Ok(*val as i32),
             SqlVal::Int(val) => Ok(*val),
             _ => Err(CannotConvertSqlVal(SqlType::Int, self.clone())),
         }
     }
     pub fn bigint(&self) -> Result<i64> {
         match self {
+            SqlVal::SmallInt(val) => Ok(*val as i64),
             SqlVal::Int(val) => Ok(*val as i64),
             SqlVal::BigInt(val) => Ok(*val),
             _ => Err(CannotConvertSqlVal(SqlType::BigInt, self.clone())),
@@ -102,13 +226,13 @@ impl SqlVal {
     }
     pub fn text(&self) -> Result<&str> {
         match self {
-            SqlVal::Text(val) => Ok(val),
+            SqlVal::Text(val) | SqlVal::Enum(val) => Ok(val),
             _ => Err(CannotConvertSqlVal(SqlType::Text, self.clone())),
         }
     }
     pub fn owned_text(self) -> Result<String> {
         match self {
-            SqlVal::Text(val) => Ok(val),
+            SqlVal::Text(val) | SqlVal::Enum(val) => Ok(val),
             _ => Err(CannotConvertSqlVal(SqlType::Text, self.clone())),
         }
     }
@@ -144,19 +268,44 @@ impl SqlVal {
         match self {
             SqlVal::Null => None,
             SqlVal::Bool(_) => Some(SqlType::Bool),
+            SqlVal::SmallInt(_) => Some(SqlType::SmallInt),
             SqlVal::Int(_) => Some(SqlType::Int),
             SqlVal::BigInt(_) => Some(SqlType::BigInt),
+            SqlVal::UBigInt(_) => Some(SqlType::UBigInt),
+            SqlVal::HugeInt(_) => Some(SqlType::HugeInt),
+            SqlVal::UHugeInt(_) => Some(SqlType::UHugeInt),
             SqlVal::Real(_) => Some(SqlType::Real),
             SqlVal::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlVal::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlVal::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlVal::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "datetime")]
+            SqlVal::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "datetime")]
+            SqlVal::Interval(_) => Some(SqlType::Interval),
+            #[cfg(feature = "time")]
+            SqlVal::TimeTimestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "time")]
+            SqlVal::TimeDate(_) => Some(SqlType::Date),
+            #[cfg(feature = "time")]
+            SqlVal::TimeTime(_) => Some(SqlType::Time),
+            #[cfg(feature = "time")]
+            SqlVal::TimeTimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "time")]
+            SqlVal::TimeInterval(_) => Some(SqlType::Interval),
+            #[cfg(feature = "decimal")]
+            SqlVal::Numeric(_) => Some(<Decimal as FieldType>::SQLTYPE),
+            #[cfg(feature = "uuid")]
+            SqlVal::Uuid(_) => Some(SqlType::Uuid),
+            SqlVal::Inet(_) => Some(SqlType::Inet),
+            SqlVal::Array(elem, _) => Some(SqlType::Array(*elem)),
             SqlVal::Blob(_) => Some(SqlType::Blob),
-            #[cfg(feature = "pg")]
-            SqlVal::Custom(c) => match c.as_ref() {
-                SqlValCustom::Pg { ty, .. } => Some(SqlType::Custom(SqlTypeCustom::Pg(ty.clone()))),
-            },
-            #[cfg(not(feature = "pg"))]
-            SqlVal::Custom(_) => None,
+            SqlVal::Json(_) => Some(SqlType::Json),
+            SqlVal::Enum(_) => Some(SqlType::Text),
+            SqlVal::Custom(c) => SqlValRef::Custom(c.as_valref()).sqltype(),
         }
     }
 }
@@ -166,13 +315,52 @@ impl fmt::Display for SqlVal {
         match &self {
             SqlVal::Null => f.write_str("NULL"),
             SqlVal::Bool(val) => val.fmt(f),
+            SmallInt(val) => val.fmt(f),
             Int(val) => val.fmt(f),
             BigInt(val) => val.fmt(f),
+            UBigInt(val) => val.fmt(f),
+            HugeInt(val) => val.fmt(f),
+            UHugeInt(val) => val.fmt(f),
             Real(val) => val.fmt(f),
             Text(val) => val.fmt(f),
             Blob(val) => f.write_str(&hex::encode(val)),
             #[cfg(feature = "datetime")]
             Timestamp(val) => val.format("%+").fmt(f),
+            #[cfg(feature = "datetime")]
+            Date(val) => val.fmt(f),
+            #[cfg(feature = "datetime")]
+            Time(val) => val.fmt(f),
+            #[cfg(feature = "datetime")]
+            TimestampTz(val) => val.to_rfc3339().fmt(f),
+            #[cfg(feature = "datetime")]
+            Interval(val) => val.fmt(f),
+            #[cfg(feature = "time")]
+            TimeTimestamp(val) => val.fmt(f),
+            #[cfg(feature = "time")]
+            TimeDate(val) => val.fmt(f),
+            #[cfg(feature = "time")]
+            TimeTime(val) => val.fmt(f),
+            #[cfg(feature = "time")]
+            TimeTimestampTz(val) => val.fmt(f),
+            #[cfg(feature = "time")]
+            TimeInterval(val) => val.fmt(f),
+            #[cfg(feature = "decimal")]
+            Numeric(val) => val.fmt(f),
+            #[cfg(feature = "uuid")]
+            Uuid(val) => val.fmt(f),
+            Inet(val) => val.fmt(f),
+            Array(_, vals) => {
+                f.write_str("[")?;
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    val.fmt(f)?;
+                }
+                f.write_str("]")
+            }
+            Json(val) => val.fmt(f),
+            Enum(val) => val.fmt(f),
             Custom(val) => val.fmt(f),
         }
     }
@@ -224,20 +412,56 @@ pub trait FromSql {
     }
 }
 
+/// # Panics
+///
+/// If a custom value cannot be serialized. See
+/// [SqlValRef::try_into_owned].
 impl From<SqlValRef<'_>> for SqlVal {
     fn from(vref: SqlValRef) -> SqlVal {
         use SqlValRef::*;
         match vref {
             Null => SqlVal::Null,
             Bool(v) => SqlVal::Bool(v),
+            SmallInt(v) => SqlVal::SmallInt(v),
             Int(v) => SqlVal::Int(v),
             BigInt(v) => SqlVal::BigInt(v),
+            UBigInt(v) => SqlVal::UBigInt(v),
+            HugeInt(v) => SqlVal::HugeInt(v),
+            UHugeInt(v) => SqlVal::UHugeInt(v),
             Real(v) => SqlVal::Real(v),
             Text(v) => SqlVal::Text(v.to_string()),
             Blob(v) => SqlVal::Blob(v.into()),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlVal::Timestamp(v),
-            Custom(v) => SqlVal::Custom(Box::new(v.into())),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlVal::Date(v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlVal::Time(v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlVal::TimestampTz(v),
+            #[cfg(feature = "datetime")]
+            Interval(v) => SqlVal::Interval(v),
+            #[cfg(feature = "time")]
+            TimeTimestamp(v) => SqlVal::TimeTimestamp(v),
+            #[cfg(feature = "time")]
+            TimeDate(v) => SqlVal::TimeDate(v),
+            #[cfg(feature = "time")]
+            TimeTime(v) => SqlVal::TimeTime(v),
+            #[cfg(feature = "time")]
+            TimeTimestampTz(v) => SqlVal::TimeTimestampTz(v),
+            #[cfg(feature = "time")]
+            TimeInterval(v) => SqlVal::TimeInterval(v),
+            #[cfg(feature = "decimal")]
+            Numeric(v) => SqlVal::Numeric(v),
+            #[cfg(feature = "uuid")]
+            Uuid(v) => SqlVal::Uuid(v),
+            Inet(v) => SqlVal::Inet(v),
+            Array(elem, v) => SqlVal::Array(elem, v.into_owned()),
+            Json(v) => SqlVal::Json(v.into_owned()),
+            Enum(v) => SqlVal::Enum(v.to_string()),
+            Custom(v) => SqlVal::Custom(Box::new(
+                SqlValCustom::try_from(v).expect("custom value could not be serialized"),
+            )),
         }
     }
 }
@@ -248,13 +472,43 @@ impl<'a> From<&'a SqlVal> for SqlValRef<'a> {
         match val {
             Null => SqlValRef::Null,
             Bool(v) => SqlValRef::Bool(*v),
+            SmallInt(v) => SqlValRef::SmallInt(*v),
             Int(v) => SqlValRef::Int(*v),
             BigInt(v) => SqlValRef::BigInt(*v),
+            UBigInt(v) => SqlValRef::UBigInt(*v),
+            HugeInt(v) => SqlValRef::HugeInt(*v),
+            UHugeInt(v) => SqlValRef::UHugeInt(*v),
             Real(v) => SqlValRef::Real(*v),
             Text(v) => SqlValRef::Text(v.as_ref()),
             Blob(v) => SqlValRef::Blob(v.as_ref()),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlValRef::Timestamp(*v),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlValRef::Date(*v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlValRef::Time(*v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlValRef::TimestampTz(*v),
+            #[cfg(feature = "datetime")]
+            Interval(v) => SqlValRef::Interval(*v),
+            #[cfg(feature = "time")]
+            TimeTimestamp(v) => SqlValRef::TimeTimestamp(*v),
+            #[cfg(feature = "time")]
+            TimeDate(v) => SqlValRef::TimeDate(*v),
+            #[cfg(feature = "time")]
+            TimeTime(v) => SqlValRef::TimeTime(*v),
+            #[cfg(feature = "time")]
+            TimeTimestampTz(v) => SqlValRef::TimeTimestampTz(*v),
+            #[cfg(feature = "time")]
+            TimeInterval(v) => SqlValRef::TimeInterval(*v),
+            #[cfg(feature = "decimal")]
+            Numeric(v) => SqlValRef::Numeric(*v),
+            #[cfg(feature = "uuid")]
+            Uuid(v) => SqlValRef::Uuid(*v),
+            Inet(v) => SqlValRef::Inet(*v),
+            Array(elem, v) => SqlValRef::Array(*elem, Cow::Borrowed(v)),
+            Json(v) => SqlValRef::Json(Cow::Borrowed(v)),
+            Enum(v) => SqlValRef::Enum(v.as_ref()),
             Custom(v) => SqlValRef::Custom(v.as_valref()),
         }
     }
@@ -323,6 +577,12 @@ macro_rules! impl_prim_sql {
     };
     ($prim:ty, $variant:ident, $sqltype:ident, $reftype: ty) => {
         impl_basic_from_sql!($prim, $variant, $sqltype);
+        impl_prim_to_sql!($prim, $variant, $sqltype, $reftype);
+    };
+}
+
+macro_rules! impl_prim_to_sql {
+    ($prim:ty, $variant:ident, $sqltype:ident, $reftype: ty) => {
         impl ToSql for $prim {
             fn to_sql(&self) -> SqlVal {
                 self.clone().into_sql()
@@ -347,14 +607,63 @@ impl_prim_sql!(bool, Bool, Bool);
 impl_prim_sql!(i64, BigInt, BigInt);
 impl_prim_sql!(i32, Int, Int);
 impl_prim_sql!(u32, BigInt, BigInt);
-// TODO need a small int type
 impl_prim_sql!(u16, Int, Int);
-impl_prim_sql!(i16, Int, Int);
+impl_prim_to_sql!(i16, SmallInt, SmallInt, i16);
 impl_prim_sql!(u8, Int, Int);
 impl_prim_sql!(i8, Int, Int);
 impl_prim_sql!(f64, Real, Real);
 impl_prim_sql!(f32, Real, Real);
 
+// i16 columns were Int before SmallInt was added, so values are still
+// read from Int columns which have not been migrated, if they fit.
+impl FromSql for i16 {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::SmallInt(val) => Ok(val),
+            SqlValRef::Int(val) => i16::try_from(val).map_err(|_| crate::Error::OutOfRange),
+            _ => sql_conv_err!(valref, SmallInt),
+        }
+    }
+}
+
+// Integers too wide for BigInt. Values of any integer type are
+// accepted if they fit.
+macro_rules! impl_wide_int_sql {
+    ($prim:ty, $variant:ident) => {
+        impl FromSql for $prim {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                match valref {
+                    SqlValRef::SmallInt(val) => <$prim>::try_from(val).ok(),
+                    SqlValRef::Int(val) => <$prim>::try_from(val).ok(),
+                    SqlValRef::BigInt(val) => <$prim>::try_from(val).ok(),
+                    SqlValRef::UBigInt(val) => <$prim>::try_from(val).ok(),
+                    SqlValRef::HugeInt(val) => <$prim>::try_from(val).ok(),
+                    SqlValRef::UHugeInt(val) => <$prim>::try_from(val).ok(),
+                    _ => return sql_conv_err!(valref, $variant),
+                }
+                .ok_or(crate::Error::OutOfRange)
+            }
+        }
+        impl ToSql for $prim {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant(*self)
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant(*self)
+            }
+        }
+        impl FieldType for $prim {
+            const SQLTYPE: SqlType = SqlType::$variant;
+            type RefType = Self;
+        }
+        impl PrimaryKeyType for $prim {}
+    };
+}
+
+impl_wide_int_sql!(u64, UBigInt);
+impl_wide_int_sql!(i128, HugeInt);
+impl_wide_int_sql!(u128, UHugeInt);
+
 impl FromSql for String {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
         if let SqlValRef::Text(val) = valref {
@@ -421,6 +730,63 @@ impl FieldType for Vec<u8> {
 }
 impl PrimaryKeyType for Vec<u8> {}
 
+impl_basic_from_sql!(IpAddr, Inet, Inet);
+impl ToSql for IpAddr {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Inet(*self)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Inet(*self)
+    }
+}
+impl FieldType for IpAddr {
+    const SQLTYPE: SqlType = SqlType::Inet;
+    type RefType = Self;
+}
+
+// Vec<u8> is a blob rather than an array
+macro_rules! impl_array_sql {
+    ($ty:ty, $elem:ident) => {
+        impl ToSql for Vec<$ty> {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::Array(
+                    ArrayElement::$elem,
+                    self.iter().map(ToSql::to_sql).collect(),
+                )
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::Array(
+                    ArrayElement::$elem,
+                    Cow::Owned(self.iter().map(ToSql::to_sql).collect()),
+                )
+            }
+        }
+        impl FromSql for Vec<$ty> {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                match valref {
+                    SqlValRef::Array(ArrayElement::$elem, vals) => vals
+                        .iter()
+                        .map(|val| <$ty>::from_sql_ref(val.as_ref()))
+                        .collect(),
+                    _ => Err(CannotConvertSqlVal(
+                        SqlType::Array(ArrayElement::$elem),
+                        valref.into(),
+                    )),
+                }
+            }
+        }
+        impl FieldType for Vec<$ty> {
+            const SQLTYPE: SqlType = SqlType::Array(ArrayElement::$elem);
+            type RefType = Self;
+        }
+    };
+}
+impl_array_sql!(bool, Bool);
+impl_array_sql!(i32, Int);
+impl_array_sql!(i64, BigInt);
+impl_array_sql!(f64, Real);
+impl_array_sql!(String, Text);
+
 #[cfg(feature = "datetime")]
 impl_basic_from_sql!(NaiveDateTime, Timestamp, Timestamp);
 #[cfg(feature = "datetime")]
@@ -443,6 +809,89 @@ impl FieldType for NaiveDateTime {
 #[cfg(feature = "datetime")]
 impl PrimaryKeyType for NaiveDateTime {}
 
+#[cfg(feature = "datetime")]
+macro_rules! impl_datetime_sql {
+    ($ty:ty, $variant:ident) => {
+        impl_basic_from_sql!($ty, $variant, $variant);
+        impl ToSql for $ty {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant(*self)
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant(*self)
+            }
+        }
+        impl FieldType for $ty {
+            const SQLTYPE: SqlType = SqlType::$variant;
+            type RefType = Self;
+        }
+    };
+}
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(NaiveDate, Date);
+#[cfg(feature = "datetime")]
+impl PrimaryKeyType for NaiveDate {}
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(NaiveTime, Time);
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(DateTime<Utc>, TimestampTz);
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(Duration, Interval);
+
+/// Stored in UTC, so values read back have an offset of zero.
+#[cfg(feature = "datetime")]
+impl FromSql for DateTime<FixedOffset> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        if let SqlValRef::TimestampTz(val) = valref {
+            Ok(val.into())
+        } else {
+            sql_conv_err!(valref, TimestampTz)
+        }
+    }
+}
+#[cfg(feature = "datetime")]
+impl ToSql for DateTime<FixedOffset> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::TimestampTz(self.with_timezone(&Utc))
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::TimestampTz(self.with_timezone(&Utc))
+    }
+}
+#[cfg(feature = "datetime")]
+impl FieldType for DateTime<FixedOffset> {
+    const SQLTYPE: SqlType = SqlType::TimestampTz;
+    type RefType = Self;
+}
+
+/// The number of microseconds in `interval`, saturating at the bounds
+/// of `i64`.
+#[cfg(feature = "datetime")]
+pub(crate) fn interval_to_micros(interval: &Duration) -> i64 {
+    interval
+        .num_microseconds()
+        .unwrap_or(if *interval < Duration::zero() {
+            i64::MIN
+        } else {
+            i64::MAX
+        })
+}
+
+/// Serializes an interval as its number of microseconds.
+#[cfg(feature = "datetime")]
+mod interval_micros {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(interval: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i64(super::interval_to_micros(interval))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        Ok(Duration::microseconds(i64::deserialize(d)?))
+    }
+}
+
 impl ToSql for &str {
     fn to_sql(&self) -> SqlVal {
         SqlVal::Text((*self).to_string())
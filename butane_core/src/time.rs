@@ -0,0 +1,134 @@
+//! Support for the types of the `time` crate. Values are stored as the
+//! same database types as their chrono equivalents, but do not depend
+//! on chrono.
+
+use crate::{
+    Error::{CannotConvertSqlVal, OutOfRange},
+    FieldType, FromSql, PrimaryKeyType, Result, SqlType, SqlVal, SqlValRef, ToSql,
+};
+use std::convert::TryFrom;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+/// Format of dates in SQL literals and, on backends without a native
+/// type, in storage, as for chrono dates.
+pub(crate) const DATE_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// Formats a time of day like chrono's `%H:%M:%S%.f`, which gives 0, 3,
+/// 6 or 9 fractional digits.
+pub(crate) fn format_time(time: &Time) -> String {
+    let mut fraction = String::new();
+    if time.nanosecond() != 0 {
+        fraction = format!(".{:09}", time.nanosecond());
+        while fraction.ends_with("000") {
+            fraction.truncate(fraction.len() - 3);
+        }
+    }
+    format!(
+        "{:02}:{:02}:{:02}{}",
+        time.hour(),
+        time.minute(),
+        time.second(),
+        fraction
+    )
+}
+
+/// Formats a date like chrono's `%Y-%m-%d`.
+pub(crate) fn format_date(date: &Date) -> Result<String> {
+    date.format(DATE_FORMAT).map_err(|_| OutOfRange)
+}
+
+/// Formats a timestamp with a time zone in UTC, like chrono's
+/// `%Y-%m-%dT%H:%M:%S%.f+00:00`.
+pub(crate) fn format_offset_datetime(dt: &OffsetDateTime) -> Result<String> {
+    let utc = dt.to_offset(UtcOffset::UTC);
+    Ok(format!(
+        "{}T{}+00:00",
+        format_date(&utc.date())?,
+        format_time(&utc.time())
+    ))
+}
+
+/// The number of microseconds in `duration`, or [OutOfRange] if it
+/// does not fit in an `i64`.
+pub(crate) fn duration_to_micros(duration: &Duration) -> Result<i64> {
+    i64::try_from(duration.whole_microseconds()).map_err(|_| OutOfRange)
+}
+
+#[cfg(feature = "datetime")]
+mod from_chrono {
+    //! Backends read temporal values as chrono types when the
+    //! `datetime` feature is also enabled.
+    use super::*;
+    use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+    pub fn date(date: NaiveDate) -> Result<Date> {
+        Date::from_ordinal_date(date.year(), date.ordinal() as u16).map_err(|_| OutOfRange)
+    }
+
+    pub fn time(time: NaiveTime) -> Result<Time> {
+        // chrono represents leap seconds with nanoseconds beyond one second
+        Time::from_hms_nano(
+            time.hour() as u8,
+            time.minute() as u8,
+            time.second() as u8,
+            time.nanosecond().min(999_999_999),
+        )
+        .map_err(|_| OutOfRange)
+    }
+
+    pub fn datetime(dt: NaiveDateTime) -> Result<PrimitiveDateTime> {
+        Ok(PrimitiveDateTime::new(date(dt.date())?, time(dt.time())?))
+    }
+
+    pub fn duration(duration: chrono::Duration) -> Duration {
+        Duration::microseconds(crate::sqlval::interval_to_micros(&duration))
+    }
+}
+
+macro_rules! impl_time_sql {
+    ($ty:ty, $variant:ident, $sqltype:ident, $chrono:pat => $from_chrono:expr) => {
+        impl ToSql for $ty {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant(*self)
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant(*self)
+            }
+        }
+        impl FromSql for $ty {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                match valref {
+                    SqlValRef::$variant(val) => Ok(val),
+                    #[cfg(feature = "datetime")]
+                    SqlValRef::$sqltype($chrono) => $from_chrono,
+                    _ => Err(CannotConvertSqlVal(SqlType::$sqltype, valref.into())),
+                }
+            }
+        }
+        impl FieldType for $ty {
+            const SQLTYPE: SqlType = SqlType::$sqltype;
+            type RefType = Self;
+        }
+    };
+}
+
+impl_time_sql!(Date, TimeDate, Date, val => from_chrono::date(val));
+impl PrimaryKeyType for Date {}
+impl_time_sql!(Time, TimeTime, Time, val => from_chrono::time(val));
+impl_time_sql!(
+    PrimitiveDateTime,
+    TimeTimestamp,
+    Timestamp,
+    val => from_chrono::datetime(val)
+);
+impl PrimaryKeyType for PrimitiveDateTime {}
+// Stored in UTC, so values read back have an offset of zero.
+impl_time_sql!(
+    OffsetDateTime,
+    TimeTimestampTz,
+    TimestampTz,
+    val => from_chrono::datetime(val.naive_utc()).map(PrimitiveDateTime::assume_utc)
+);
+impl_time_sql!(Duration, TimeInterval, Interval, val => Ok(from_chrono::duration(val)));